@@ -0,0 +1,310 @@
+use crate::lexer::LexerError;
+use crate::preprocessor::PreprocessorError;
+use crate::source::{column_of, Expansion, Located, Span};
+use lamp_common::op::OPCODES_STRINGS;
+use serde_json::json;
+use std::cmp::Reverse;
+
+// Diagnostics are what the assembler reports: errors and warnings with their location,
+// the source line they are about and a stable code, printed for humans or as JSON.
+//
+// Codes are grouped by the step that finds them:
+//   E00xx reading and writing files, E01xx parsing, E02xx preprocessing,
+//   E03xx compilation, E04xx linking
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+        }
+    }
+}
+
+// Errors that can be turned into diagnostics
+pub trait ErrorCode {
+    fn code(&self) -> &'static str;
+
+    // A suggestion to fix the error, when there is an obvious one
+    fn help(&self) -> Option<String> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    // Empty when unknown
+    pub file: String,
+    // Index and text of the line
+    pub line: Option<(usize, String)>,
+    pub span: Option<Span>,
+    pub help: Option<String>,
+    // Why the diagnostic is reported, like the lint that found it
+    pub note: Option<String>,
+    pub expansions: Vec<Expansion>,
+}
+
+impl Diagnostic {
+    // A diagnostic that isn't about a given place, like a linking error
+    pub fn new(severity: Severity, code: &'static str, message: String) -> Self {
+        Self {
+            severity,
+            code,
+            message,
+            file: String::new(),
+            line: None,
+            span: None,
+            help: None,
+            note: None,
+            expansions: vec![],
+        }
+    }
+
+    pub fn error<E: ErrorCode + std::fmt::Display>(located: &Located<E>) -> Self {
+        Self::from_located(Severity::Error, located)
+    }
+
+    pub fn from_located<E: ErrorCode + std::fmt::Display>(
+        severity: Severity,
+        located: &Located<E>,
+    ) -> Self {
+        Self {
+            file: located.file.to_string(),
+            line: located.line.clone(),
+            span: located.span,
+            help: located.help.clone().or_else(|| located.error.help()),
+            expansions: located.expansions.clone(),
+            ..Self::new(severity, located.error.code(), located.error.to_string())
+        }
+    }
+
+    pub fn with_note(mut self, note: String) -> Self {
+        self.note = Some(note);
+        self
+    }
+
+    // The span to underline: the given one, or the whole line without its indentation
+    fn underlined(&self) -> Option<Span> {
+        let (_, text) = self.line.as_ref()?;
+        self.span.or_else(|| {
+            let start = text.len() - text.trim_start().len();
+            let end = text.trim_end().len().max(start);
+            Some(Span::new(start, end))
+        })
+    }
+
+    // Like rustc does:
+    //
+    //   error[E0101]: Invalid mnemonic at line 3: 'ADDD'
+    //    --> main.asm:3:5
+    //     |
+    //   3 |     ADDD r1, r2, r3
+    //     |     ^^^^
+    //     = help: did you mean 'ADD'?
+    pub fn render(&self) -> String {
+        let mut out = format!(
+            "{}[{}]: {}\n",
+            self.severity.name(),
+            self.code,
+            self.message
+        );
+        let number = self.line.as_ref().map(|(line, _)| (line + 1).to_string());
+        let margin = " ".repeat(number.as_ref().map_or(0, |n| n.len()));
+
+        match (&self.line, self.underlined()) {
+            (Some((line, text)), Some(span)) => {
+                let text = text.trim_end();
+                out += &format!(
+                    "{}--> {}{}:{}\n",
+                    margin,
+                    self.file_prefix(),
+                    line + 1,
+                    column_of(text, span.start)
+                );
+                // Tabs are kept so the carets line up with the text
+                let padding: String = text
+                    .get(..span.start)
+                    .unwrap_or("")
+                    .chars()
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                let width = text
+                    .get(span.start..span.end)
+                    .map_or(1, |s| s.chars().count().max(1));
+                out += &format!("{} |\n", margin);
+                out += &format!("{} | {}\n", number.unwrap_or_default(), text);
+                out += &format!("{} | {}{}\n", margin, padding, "^".repeat(width));
+            }
+            _ if !self.file.is_empty() => out += &format!("{}--> {}\n", margin, self.file),
+            _ => {}
+        }
+
+        if let Some(help) = &self.help {
+            out += &format!("{} = help: {}\n", margin, help);
+        }
+        if let Some(note) = &self.note {
+            out += &format!("{} = note: {}\n", margin, note);
+        }
+        for expansion in self.expansions.iter().rev() {
+            out += &format!(
+                "{} = note: in expansion of macro \'{}\' at {}\n",
+                margin,
+                expansion.macro_name,
+                if expansion.file.is_empty() {
+                    format!("line {}", expansion.line + 1)
+                } else {
+                    format!("{}:{}", expansion.file, expansion.line + 1)
+                }
+            );
+        }
+        out
+    }
+
+    fn file_prefix(&self) -> String {
+        if self.file.is_empty() {
+            String::new()
+        } else {
+            format!("{}:", self.file)
+        }
+    }
+
+    // One JSON object, for editors and CI. Lines and columns are 1-based.
+    pub fn to_json(&self) -> String {
+        let (line, column, end_column, source) = match (&self.line, self.underlined()) {
+            (Some((line, text)), Some(span)) => (
+                Some(line + 1),
+                Some(column_of(text, span.start)),
+                Some(column_of(text, span.end)),
+                Some(text.as_str()),
+            ),
+            (Some((line, text)), None) => (Some(line + 1), None, None, Some(text.as_str())),
+            _ => (None, None, None, None),
+        };
+        json!({
+            "severity": self.severity.name(),
+            "code": self.code,
+            "message": self.message,
+            "file": self.file,
+            "line": line,
+            "column": column,
+            "end_column": end_column,
+            "source": source,
+            "help": self.help,
+            "note": self.note,
+            "expansions": self
+                .expansions
+                .iter()
+                .map(|e| json!({
+                    "macro": e.macro_name,
+                    "file": &*e.file,
+                    "line": e.line + 1,
+                }))
+                .collect::<Vec<_>>(),
+        })
+        .to_string()
+    }
+}
+
+// Number of single-character edits to go from a to b, swapping two neighbours
+// counting as one (optimal string alignment distance)
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // distances[i][j] is the distance between the first i chars of a and j chars of b
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution = distances[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = substitution
+                .min(distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+// The candidate closest to `name`, if it is close enough to be a typo. Between
+// candidates as close, the one starting like `name` for longer wins.
+pub fn closest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let max = (name.chars().count() / 3).max(1);
+    let shared_prefix = |candidate: &str| {
+        name.chars()
+            .zip(candidate.chars())
+            .take_while(|(a, b)| a == b)
+            .count()
+    };
+    candidates
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max)
+        .min_by_key(|(distance, candidate)| (*distance, Reverse(shared_prefix(candidate))))
+        .map(|(_, candidate)| candidate)
+}
+
+pub fn did_you_mean(suggestion: Option<&str>) -> Option<String> {
+    suggestion.map(|s| format!("did you mean \'{}\'?", s))
+}
+
+impl ErrorCode for LexerError {
+    fn code(&self) -> &'static str {
+        match self {
+            Self::UnexpectedToken(..) => "E0101",
+            Self::InvalidMnemonic(..) => "E0102",
+            Self::InvalidLine(_) => "E0103",
+            Self::InvalidExpression(..) => "E0104",
+        }
+    }
+
+    fn help(&self) -> Option<String> {
+        match self {
+            Self::InvalidMnemonic(_, mnemonic) => did_you_mean(closest(
+                &mnemonic.to_uppercase(),
+                OPCODES_STRINGS.iter().map(|(_, name)| *name),
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl ErrorCode for PreprocessorError {
+    fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidMacroDefinition(..) => "E0201",
+            Self::MacroRedefinition(..) => "E0202",
+            Self::UnterminatedMacro(..) => "E0203",
+            Self::UnexpectedEndm(_) => "E0204",
+            Self::WrongArgumentCount { .. } => "E0205",
+            Self::UnknownParameter(..) => "E0206",
+            Self::RecursionLimit(..) => "E0207",
+            Self::InvalidInclude(..) => "E0208",
+            Self::IncludeNotFound(..) => "E0209",
+            Self::IncludeCycle(..) => "E0210",
+        }
+    }
+
+    fn help(&self) -> Option<String> {
+        match self {
+            Self::UnterminatedMacro(..) => Some("add .endm after the macro\'s body".to_owned()),
+            Self::InvalidInclude(..) => Some("write the path between quotes".to_owned()),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,463 @@
+use crate::lexer::is_identifier;
+use lamp_common::container::SectionKind;
+use std::convert::TryFrom;
+
+// Integer constant expressions found in operands and directives, like `(end - start) / 2`.
+//
+// From the lowest to the highest precedence:
+//   |    ^    &    << >>    + -    * / %    unary - ~ +
+// `.` is the address of the current instruction or directive.
+//
+// Numbers can be written in decimal (42), hexadecimal (0x2A), binary (0b101010) or octal (0o52),
+// with `_` to separate digits (0b1010_1010). Character literals ('A', '\n') give their code.
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BinaryOp {
+    Or,
+    Xor,
+    And,
+    Shl,
+    Shr,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Num(i64),
+    Symbol(String),
+    // `.`
+    Here,
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+// What an expression evaluates to
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Value {
+    Constant(i64),
+    // An address only known at link time: the address of the symbol plus the addend.
+    // `base` gives the section and the offset of the symbol when it is defined in this file,
+    // so the difference between two labels of the same section is a constant.
+    Address {
+        symbol: usize,
+        base: Option<(SectionKind, i64)>,
+        addend: i64,
+    },
+}
+
+// Different types of errors an expression can lead to
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprError {
+    UnexpectedChar(char),
+    UnexpectedEnd,
+    NumberTooLarge(String),
+    InvalidNumber(String),
+    InvalidCharLiteral(String),
+    UndefinedSymbol(String),
+    NotConstant,
+    DivisionByZero,
+    Overflow,
+}
+
+impl std::fmt::Display for ExprError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedChar(c) => write!(f, "unexpected \'{}\'", c),
+            Self::UnexpectedEnd => write!(f, "unexpected end of expression"),
+            Self::NumberTooLarge(n) => write!(f, "number too large: {}", n),
+            Self::InvalidNumber(n) => write!(f, "invalid number: {}", n),
+            Self::InvalidCharLiteral(c) => write!(f, "invalid character literal: {}", c),
+            Self::UndefinedSymbol(name) => write!(f, "undefined symbol \'{}\'", name),
+            Self::NotConstant => write!(
+                f,
+                "addresses can only be added to or subtracted from constants, or subtracted from addresses of the same section"
+            ),
+            Self::DivisionByZero => write!(f, "division by zero"),
+            Self::Overflow => write!(f, "arithmetic overflow"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(i64),
+    Ident(String),
+    Dot,
+    Op(&'static str),
+    LParen,
+    RParen,
+}
+
+const OPERATORS: &[&str] = &["<<", ">>", "|", "^", "&", "+", "-", "*", "/", "%", "~"];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, ExprError> {
+    let mut tokens = Vec::<Token>::new();
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let number: String = chars[start..i].iter().collect();
+            tokens.push(Token::Num(parse_number(&number)?));
+        } else if c == '\'' {
+            let (value, len) = parse_char(&chars[i..])?;
+            tokens.push(Token::Num(value));
+            i += len;
+        } else if c.is_ascii_alphabetic() || c == '_' || c == '.' {
+            let start = i;
+            i += 1;
+            while i < chars.len()
+                && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '.')
+            {
+                i += 1;
+            }
+            let name: String = chars[start..i].iter().collect();
+            if name == "." {
+                tokens.push(Token::Dot);
+            } else if is_identifier(&name) {
+                tokens.push(Token::Ident(name));
+            } else {
+                return Err(ExprError::UnexpectedChar(c));
+            }
+        } else if c == '(' {
+            tokens.push(Token::LParen);
+            i += 1;
+        } else if c == ')' {
+            tokens.push(Token::RParen);
+            i += 1;
+        } else {
+            let rest: String = chars[i..].iter().take(2).collect();
+            match OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+                Some(op) => {
+                    tokens.push(Token::Op(op));
+                    i += op.len();
+                }
+                None => return Err(ExprError::UnexpectedChar(c)),
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_number(number: &str) -> Result<i64, ExprError> {
+    let digits = number.replace('_', "");
+    let (radix, digits) = match digits.get(..2) {
+        Some("0x") | Some("0X") => (16, &digits[2..]),
+        Some("0b") | Some("0B") => (2, &digits[2..]),
+        Some("0o") | Some("0O") => (8, &digits[2..]),
+        _ => (10, &digits[..]),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return Err(ExprError::InvalidNumber(number.to_owned()));
+    }
+    i64::from_str_radix(digits, radix).map_err(|_| ExprError::NumberTooLarge(number.to_owned()))
+}
+
+// Parses the character literal `chars` starts with, giving its value and its length
+fn parse_char(chars: &[char]) -> Result<(i64, usize), ExprError> {
+    // Length of the character between the quotes: `\x41` is 4 chars, `\n` 2 and `A` 1
+    let len = match chars.get(1..3) {
+        Some(['\\', 'x']) => 4,
+        Some(['\\', _]) => 2,
+        _ => 1,
+    };
+    let literal: String = chars.iter().take(len + 2).collect();
+    if chars.len() < len + 2 {
+        return Err(ExprError::UnexpectedEnd);
+    }
+    if chars[len + 1] != '\'' {
+        let end = chars[1..]
+            .iter()
+            .position(|c| *c == '\'')
+            .map_or(chars.len(), |p| p + 2);
+        return Err(ExprError::InvalidCharLiteral(chars[..end].iter().collect()));
+    }
+
+    let value = match &chars[1..=len] {
+        ['\\', 'x', hi, lo] => match (hi.to_digit(16), lo.to_digit(16)) {
+            (Some(hi), Some(lo)) => i64::from(hi * 16 + lo),
+            _ => return Err(ExprError::InvalidCharLiteral(literal)),
+        },
+        ['\\', 'n'] => 10,
+        ['\\', 't'] => 9,
+        ['\\', 'r'] => 13,
+        ['\\', '0'] => 0,
+        ['\\', c @ ('\\' | '\'' | '"')] => *c as i64,
+        ['\''] | ['\\', _] => return Err(ExprError::InvalidCharLiteral(literal)),
+        [c] => *c as i64,
+        _ => unreachable!(),
+    };
+    Ok((value, len + 2))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+// Binary operators by precedence level, from the lowest
+const LEVELS: &[&[(&str, BinaryOp)]] = &[
+    &[("|", BinaryOp::Or)],
+    &[("^", BinaryOp::Xor)],
+    &[("&", BinaryOp::And)],
+    &[("<<", BinaryOp::Shl), (">>", BinaryOp::Shr)],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+    &[
+        ("*", BinaryOp::Mul),
+        ("/", BinaryOp::Div),
+        ("%", BinaryOp::Mod),
+    ],
+];
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token, ExprError> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token.ok_or(ExprError::UnexpectedEnd)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, ExprError> {
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(Token::Op(op)) = self.peek() {
+            match LEVELS[level].iter().find(|(name, _)| name == op) {
+                Some((_, bin_op)) => {
+                    let bin_op = *bin_op;
+                    self.pos += 1;
+                    let rhs = self.binary(level + 1)?;
+                    lhs = Expr::Binary(bin_op, Box::new(lhs), Box::new(rhs));
+                }
+                None => break,
+            }
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ExprError> {
+        match self.next()? {
+            Token::Op("-") => Ok(Expr::Unary(UnaryOp::Neg, Box::new(self.unary()?))),
+            Token::Op("~") => Ok(Expr::Unary(UnaryOp::Not, Box::new(self.unary()?))),
+            Token::Op("+") => self.unary(),
+            Token::Num(n) => Ok(Expr::Num(n)),
+            Token::Ident(name) => Ok(Expr::Symbol(name)),
+            Token::Dot => Ok(Expr::Here),
+            Token::LParen => {
+                let expr = self.binary(0)?;
+                match self.next()? {
+                    Token::RParen => Ok(expr),
+                    other => Err(unexpected(&other)),
+                }
+            }
+            other => Err(unexpected(&other)),
+        }
+    }
+}
+
+fn unexpected(token: &Token) -> ExprError {
+    ExprError::UnexpectedChar(match token {
+        Token::Op(op) => op.chars().next().unwrap(),
+        Token::RParen => ')',
+        Token::LParen => '(',
+        Token::Dot => '.',
+        Token::Num(n) => n.to_string().chars().next().unwrap(),
+        Token::Ident(name) => name.chars().next().unwrap(),
+    })
+}
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Self, ExprError> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            pos: 0,
+        };
+        let expr = parser.binary(0)?;
+        match parser.peek() {
+            Some(token) => Err(unexpected(token)),
+            None => Ok(expr),
+        }
+    }
+
+    // Replaces the symbols `value_of` knows by their value
+    pub fn replace_symbols(&mut self, value_of: &dyn Fn(&str) -> Option<i64>) {
+        match self {
+            Self::Symbol(name) => {
+                if let Some(value) = value_of(name) {
+                    *self = Self::Num(value);
+                }
+            }
+            Self::Unary(_, expr) => expr.replace_symbols(value_of),
+            Self::Binary(_, lhs, rhs) => {
+                lhs.replace_symbols(value_of);
+                rhs.replace_symbols(value_of);
+            }
+            Self::Num(_) | Self::Here => {}
+        }
+    }
+
+    // Whether `.` appears in the expression
+    pub fn uses_here(&self) -> bool {
+        match self {
+            Self::Here => true,
+            Self::Unary(_, expr) => expr.uses_here(),
+            Self::Binary(_, lhs, rhs) => lhs.uses_here() || rhs.uses_here(),
+            Self::Num(_) | Self::Symbol(_) => false,
+        }
+    }
+
+    // Names of the symbols the expression uses
+    pub fn symbols(&self) -> Vec<&str> {
+        match self {
+            Self::Symbol(name) => vec![name.as_str()],
+            Self::Unary(_, expr) => expr.symbols(),
+            Self::Binary(_, lhs, rhs) => {
+                let mut symbols = lhs.symbols();
+                symbols.extend(rhs.symbols());
+                symbols
+            }
+            Self::Num(_) | Self::Here => vec![],
+        }
+    }
+
+    // Evaluates the expression. `here` is the value of `.`, and `resolve` gives the value of symbols.
+    pub fn evaluate(
+        &self,
+        here: Value,
+        resolve: &dyn Fn(&str) -> Option<Value>,
+    ) -> Result<Value, ExprError> {
+        match self {
+            Self::Num(n) => Ok(Value::Constant(*n)),
+            Self::Here => Ok(here),
+            Self::Symbol(name) => {
+                resolve(name).ok_or_else(|| ExprError::UndefinedSymbol(name.clone()))
+            }
+            Self::Unary(op, expr) => {
+                let value = expr.evaluate(here, resolve)?.constant()?;
+                Ok(Value::Constant(match op {
+                    UnaryOp::Neg => value.checked_neg().ok_or(ExprError::Overflow)?,
+                    UnaryOp::Not => !value,
+                }))
+            }
+            Self::Binary(op, lhs, rhs) => {
+                let lhs = lhs.evaluate(here, resolve)?;
+                let rhs = rhs.evaluate(here, resolve)?;
+                binary(*op, lhs, rhs)
+            }
+        }
+    }
+}
+
+impl Value {
+    pub fn constant(self) -> Result<i64, ExprError> {
+        match self {
+            Self::Constant(n) => Ok(n),
+            Self::Address { .. } => Err(ExprError::NotConstant),
+        }
+    }
+}
+
+fn binary(op: BinaryOp, lhs: Value, rhs: Value) -> Result<Value, ExprError> {
+    // Addresses only support a few operations
+    match (op, lhs, rhs) {
+        (
+            BinaryOp::Add,
+            Value::Address {
+                symbol,
+                base,
+                addend,
+            },
+            Value::Constant(n),
+        )
+        | (
+            BinaryOp::Add,
+            Value::Constant(n),
+            Value::Address {
+                symbol,
+                base,
+                addend,
+            },
+        ) => {
+            return Ok(Value::Address {
+                symbol,
+                base,
+                addend: addend.checked_add(n).ok_or(ExprError::Overflow)?,
+            })
+        }
+        (
+            BinaryOp::Sub,
+            Value::Address {
+                symbol,
+                base,
+                addend,
+            },
+            Value::Constant(n),
+        ) => {
+            return Ok(Value::Address {
+                symbol,
+                base,
+                addend: addend.checked_sub(n).ok_or(ExprError::Overflow)?,
+            })
+        }
+        (
+            BinaryOp::Sub,
+            Value::Address {
+                base: Some((section_a, offset_a)),
+                addend: addend_a,
+                ..
+            },
+            Value::Address {
+                base: Some((section_b, offset_b)),
+                addend: addend_b,
+                ..
+            },
+        ) if section_a == section_b => {
+            let a = offset_a.checked_add(addend_a);
+            let b = offset_b.checked_add(addend_b);
+            return match (a, b) {
+                (Some(a), Some(b)) => a.checked_sub(b).map(Value::Constant),
+                _ => None,
+            }
+            .ok_or(ExprError::Overflow);
+        }
+        _ => {}
+    }
+
+    let lhs = lhs.constant()?;
+    let rhs = rhs.constant()?;
+    let result = match op {
+        BinaryOp::Or => Some(lhs | rhs),
+        BinaryOp::Xor => Some(lhs ^ rhs),
+        BinaryOp::And => Some(lhs & rhs),
+        BinaryOp::Shl => u32::try_from(rhs).ok().and_then(|r| lhs.checked_shl(r)),
+        BinaryOp::Shr => u32::try_from(rhs).ok().and_then(|r| lhs.checked_shr(r)),
+        BinaryOp::Add => lhs.checked_add(rhs),
+        BinaryOp::Sub => lhs.checked_sub(rhs),
+        BinaryOp::Mul => lhs.checked_mul(rhs),
+        BinaryOp::Div | BinaryOp::Mod if rhs == 0 => return Err(ExprError::DivisionByZero),
+        BinaryOp::Div => lhs.checked_div(rhs),
+        BinaryOp::Mod => lhs.checked_rem(rhs),
+    };
+    result.map(Value::Constant).ok_or(ExprError::Overflow)
+}
@@ -1,13 +1,66 @@
-use lamp_common::op::{get_op, Opcode};
+use crate::expr::{Expr, ExprError};
+use crate::parser::{Instruction, Line, Parser, Statement};
+use lamp_common::constants::COMMENT_MARKER;
+use lamp_common::op::Opcode;
+use lamp_common::register::{parse_register, DEFAULT_ALIASES};
+use std::collections::HashMap;
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
     // mov for example
     Opcode(Opcode),
+    // r5 or sp for example
+    Register(u8),
     // #15 for example
     Num8(u8),
     // $15 for example
     Ptr8(u8),
+    // #(SIZE * 2) or #0x1F4 for example, evaluated by the assembler to the operand's width
+    NumExpr(Expr),
+    // $(BASE + 1) for example
+    PtrExpr(Expr),
+    // loop or end - 4 for example
+    Expr(Expr),
+}
+
+// Label names follow the usual rules: letters, digits, '_' and '.', not starting with a digit
+pub fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '.' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+// Gives the characters of the line with their byte index, except the ones in character literals
+pub(crate) fn unquoted_chars(line: &str) -> Vec<(usize, char)> {
+    let mut chars = Vec::<(usize, char)>::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if quoted && c == '\\' {
+            escaped = true;
+        } else if c == '\'' {
+            quoted = !quoted;
+        } else if !quoted {
+            chars.push((i, c));
+        }
+    }
+    chars
+}
+
+// Removes the comment ending the line, if any
+pub fn strip_comment(line: &str) -> &str {
+    match unquoted_chars(line)
+        .into_iter()
+        .find(|(i, _)| line[*i..].starts_with(COMMENT_MARKER))
+    {
+        Some((index, _)) => &line[..index],
+        None => line,
+    }
 }
 
 // Different types of errors the lexer can encounter
@@ -16,6 +69,7 @@ pub enum LexerError {
     UnexpectedToken(usize, String),
     InvalidMnemonic(usize, String),
     InvalidLine(usize),
+    InvalidExpression(usize, String, ExprError),
 }
 
 impl std::fmt::Display for LexerError {
@@ -31,11 +85,23 @@ impl std::fmt::Display for LexerError {
             Self::InvalidMnemonic(at, mnemonic) => {
                 write!(f, "Invalid mnemonic at line {}: \'{}\'", at + 1, mnemonic)
             }
+            Self::InvalidExpression(at, expr, e) => {
+                write!(
+                    f,
+                    "Invalid expression at line {}: \'{}\': {}",
+                    at + 1,
+                    expr,
+                    e
+                )
+            }
         }
     }
 }
 
-pub struct Lexer;
+pub struct Lexer {
+    // Register aliases, like sp for r31
+    aliases: HashMap<String, u8>,
+}
 
 #[derive(PartialEq, Debug)]
 pub struct TokenizedLine {
@@ -43,6 +109,19 @@ pub struct TokenizedLine {
     pub operands: Vec<TokenType>,
 }
 
+impl From<Instruction> for TokenizedLine {
+    fn from(instruction: Instruction) -> Self {
+        Self {
+            opcode: instruction.opcode,
+            operands: instruction
+                .operands
+                .into_iter()
+                .map(|operand| operand.token)
+                .collect(),
+        }
+    }
+}
+
 impl TokenizedLine {
     pub fn empty() -> Self {
         TokenizedLine {
@@ -55,26 +134,42 @@ impl TokenizedLine {
 impl Lexer {
     #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        Self {}
+        Self {
+            aliases: DEFAULT_ALIASES
+                .iter()
+                .map(|(alias, index)| (alias.to_string(), *index))
+                .collect(),
+        }
+    }
+
+    // Makes `name` stand for the register
+    pub fn add_alias(&mut self, name: &str, index: u8) {
+        self.aliases.insert(name.to_lowercase(), index);
+    }
+
+    // Gives the register `name` stands for: r0 to r31 or an alias
+    pub fn register(&self, name: &str) -> Option<u8> {
+        parse_register(name).or_else(|| self.aliases.get(&name.to_lowercase()).copied())
     }
 
     #[allow(clippy::ptr_arg)]
     pub fn tokenize(&self, content: &Vec<String>) -> Result<Vec<TokenizedLine>, Vec<LexerError>> {
+        let parser = Parser::new(self);
         let mut tokenized = Vec::<TokenizedLine>::new();
         let mut errors = Vec::<LexerError>::new();
 
-        #[allow(clippy::needless_range_loop)]
-        for i in 0..content.len() {
-            if content[i].starts_with(lamp_common::constants::COMMENT_MARKER) {
-                continue;
-            }
-            match self.tokenize_line(&content[i], i) {
-                Ok(tkn) => {
-                    if errors.is_empty() {
-                        tokenized.push(tkn);
-                    }
-                }
-                Err(e) => errors.push(e),
+        for (i, line) in content.iter().enumerate() {
+            match parser.parse_line(line, i) {
+                Ok(Line {
+                    statement: Some(Statement::Instruction(instruction)),
+                    ..
+                }) => tokenized.push(TokenizedLine::from(instruction)),
+                Ok(Line {
+                    statement: Some(Statement::Directive(_)),
+                    ..
+                }) => errors.push(LexerError::InvalidLine(i)),
+                Ok(_) => {}
+                Err(e) => errors.push(e.error),
             }
         }
         if errors.is_empty() {
@@ -83,65 +178,45 @@ impl Lexer {
         Err(errors)
     }
 
-    // This function tokenizes all the line
+    // Tokenizes a line holding an instruction
     pub fn tokenize_line(&self, line: &str, line_num: usize) -> Result<TokenizedLine, LexerError> {
-        if let Some(index) = line.find(' ') {
-            let to_tokenize = line.split_at(index);
-            // The first token of a line MUST be a mnemonic...
-            let mut tokenized = TokenizedLine::empty();
-            if let Ok(opcode) = get_op(to_tokenize.0.to_owned()) {
-                tokenized.opcode = opcode;
-
-                match self.tokenize_operands(to_tokenize.1.to_owned(), line_num) {
-                    Ok(tkns) => {
-                        tokenized.operands = tkns;
-                        Ok(tokenized)
-                    }
-                    Err(e) => Err(e),
-                }
-            } else {
-                Err(LexerError::InvalidMnemonic(
-                    line_num,
-                    to_tokenize.0.to_owned(),
-                ))
-            }
-        } else {
-            Err(LexerError::InvalidLine(line_num))
+        match Parser::new(self).parse_line(line, line_num) {
+            Ok(Line {
+                statement: Some(Statement::Instruction(instruction)),
+                ..
+            }) => Ok(TokenizedLine::from(instruction)),
+            Ok(_) => Err(LexerError::InvalidLine(line_num)),
+            Err(e) => Err(e.error),
         }
     }
-    // This function tokenizes all operands given
-    pub fn tokenize_operands(
-        &self,
-        line: String,
-        line_num: usize,
-    ) -> Result<Vec<TokenType>, LexerError> {
-        let to_tokenize: Vec<&str> = line.split(',').collect();
-        let mut tokens = Vec::<TokenType>::new();
-
-        for tkn in to_tokenize {
-            let tkn = tkn.split_whitespace().next();
-            match tkn {
-                Some(tkn) => match self.from_number_to_token(tkn.to_string(), line_num) {
-                    Ok(tok) => tokens.push(tok),
-                    Err(e) => return Err(e),
-                },
-                None => break,
-            }
-        }
-        Ok(tokens)
-    }
 
-    // This line takes a number and converts it into a TokenType if possible, or LexerError if not
+    // This line takes an operand and converts it into a TokenType if possible, or LexerError if not.
+    // Plain numbers give Num8 and Ptr8, anything else is parsed as an expression.
     pub fn from_number_to_token(&self, tkn: String, line: usize) -> Result<TokenType, LexerError> {
-        let split = &tkn.split_at(1);
-
-        match split.1.parse::<u8>() {
-            Ok(number) => match split.0 {
-                "#" => Ok(TokenType::Num8(number)),
-                "$" => Ok(TokenType::Ptr8(number)),
-                other => Err(LexerError::UnexpectedToken(line, other.to_owned())),
-            },
-            Err(_) => Err(LexerError::UnexpectedToken(line, tkn)),
+        if let Some(index) = self.register(&tkn) {
+            return Ok(TokenType::Register(index));
+        }
+        let prefix = match tkn.chars().next() {
+            Some(c @ ('#' | '$')) => Some(c),
+            _ => None,
+        };
+        let value = if prefix.is_some() { &tkn[1..] } else { &tkn };
+
+        if let Ok(number) = value.trim().parse::<u8>() {
+            match prefix {
+                Some('#') => return Ok(TokenType::Num8(number)),
+                Some('$') => return Ok(TokenType::Ptr8(number)),
+                _ => {}
+            }
+        }
+        let expr = match Expr::parse(value) {
+            Ok(expr) => expr,
+            Err(e) => return Err(LexerError::InvalidExpression(line, tkn, e)),
+        };
+        match prefix {
+            Some('#') => Ok(TokenType::NumExpr(expr)),
+            Some('$') => Ok(TokenType::PtrExpr(expr)),
+            _ => Ok(TokenType::Expr(expr)),
         }
     }
 }
@@ -1,4 +1,9 @@
+pub mod diagnostic;
+pub mod expr;
 pub mod lexer;
+pub mod parser;
+pub mod preprocessor;
+pub mod source;
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,205 @@
+use crate::lexer::{is_identifier, strip_comment, unquoted_chars, Lexer, LexerError, TokenType};
+use crate::source::Span;
+use lamp_common::op::{get_op, Opcode};
+
+// Parses one line of assembly into its syntax tree. The grammar is:
+//
+//   line        := [label] [directive | instruction] [comment]
+//   label       := identifier ':'
+//   directive   := '.' identifier [argument {',' argument}]
+//   instruction := mnemonic [operand {',' operand}]
+//
+// Any amount of whitespace can separate the parts, and a line can be empty.
+// Every node has the span of its text in the line.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub name: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operand {
+    pub token: TokenType,
+    pub span: Span,
+}
+
+// A directive's argument, left as text because each directive reads them its own way
+#[derive(Debug, Clone, PartialEq)]
+pub struct Argument {
+    pub text: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Directive {
+    // With the leading '.'
+    pub name: String,
+    pub span: Span,
+    pub args: Vec<Argument>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub opcode: Opcode,
+    // Span of the mnemonic
+    pub span: Span,
+    pub operands: Vec<Operand>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Directive(Directive),
+    Instruction(Instruction),
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Line {
+    pub label: Option<Label>,
+    pub statement: Option<Statement>,
+}
+
+// A lexer error, with the part of the line it is about
+#[derive(Debug)]
+pub struct ParseError {
+    pub error: LexerError,
+    pub span: Span,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+pub struct Parser<'a> {
+    lexer: &'a Lexer,
+}
+
+impl<'a> Parser<'a> {
+    // The lexer knows the register aliases
+    pub fn new(lexer: &'a Lexer) -> Self {
+        Self { lexer }
+    }
+
+    pub fn parse_line(&self, text: &str, line_num: usize) -> Result<Line, ParseError> {
+        let text = strip_comment(text);
+        let mut line = Line::default();
+
+        let mut span = word(text, skip_whitespace(text, 0));
+        if text[span.end..].starts_with(':') {
+            let name = &text[span.start..span.end];
+            if !is_identifier(name) {
+                return Err(ParseError {
+                    error: LexerError::UnexpectedToken(line_num, name.to_owned()),
+                    span,
+                });
+            }
+            line.label = Some(Label {
+                name: name.to_owned(),
+                span,
+            });
+            span = word(text, skip_whitespace(text, span.end + 1));
+        }
+
+        if text[span.start..].trim().is_empty() {
+            return Ok(line);
+        }
+        let name = &text[span.start..span.end];
+        // Something other than whitespace right after the word, like in `ADD#1`
+        let rest_start = match text[span.end..].chars().next() {
+            Some(c) if !c.is_whitespace() || name.is_empty() => {
+                let span = Span::new(span.end, span.end + c.len_utf8());
+                return Err(ParseError {
+                    error: LexerError::UnexpectedToken(line_num, c.to_string()),
+                    span,
+                });
+            }
+            _ => span.end,
+        };
+        let args = split_arguments(text, rest_start);
+        if let Some(empty) = args.iter().find(|span| span.start == span.end) {
+            return Err(ParseError {
+                error: LexerError::UnexpectedToken(line_num, ",".to_owned()),
+                span: *empty,
+            });
+        }
+
+        line.statement = Some(if name.starts_with('.') {
+            Statement::Directive(Directive {
+                name: name.to_owned(),
+                span,
+                args: args
+                    .into_iter()
+                    .map(|span| Argument {
+                        text: text[span.start..span.end].to_owned(),
+                        span,
+                    })
+                    .collect(),
+            })
+        } else {
+            let opcode = match get_op(name.to_owned()) {
+                Ok(opcode) => opcode,
+                Err(_) => {
+                    return Err(ParseError {
+                        error: LexerError::InvalidMnemonic(line_num, name.to_owned()),
+                        span,
+                    })
+                }
+            };
+            let mut operands = Vec::<Operand>::with_capacity(args.len());
+            for span in args {
+                let text = &text[span.start..span.end];
+                match self.lexer.from_number_to_token(text.to_owned(), line_num) {
+                    Ok(token) => operands.push(Operand { token, span }),
+                    Err(error) => return Err(ParseError { error, span }),
+                }
+            }
+            Statement::Instruction(Instruction {
+                opcode,
+                span,
+                operands,
+            })
+        });
+        Ok(line)
+    }
+}
+
+fn skip_whitespace(text: &str, from: usize) -> usize {
+    text[from..]
+        .find(|c: char| !c.is_whitespace())
+        .map_or(text.len(), |i| from + i)
+}
+
+// The span of the word (mnemonic, directive or label) starting at `from`
+fn word(text: &str, from: usize) -> Span {
+    let end = text[from..]
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+        .map_or(text.len(), |i| from + i);
+    Span::new(from, end)
+}
+
+// Splits the comma-separated arguments found after `from`, giving their trimmed span.
+// Empty arguments are kept, so `ADD r1,, r2` can be reported.
+fn split_arguments(text: &str, from: usize) -> Vec<Span> {
+    let rest = &text[from..];
+    if rest.trim().is_empty() {
+        return vec![];
+    }
+
+    let trimmed = |start: usize, end: usize| {
+        let piece = &text[start..end];
+        let start = start + (piece.len() - piece.trim_start().len());
+        Span::new(start, start + piece.trim().len())
+    };
+    let mut spans = Vec::<Span>::new();
+    let mut start = from;
+    for (i, c) in unquoted_chars(rest) {
+        if c == ',' {
+            spans.push(trimmed(start, from + i));
+            start = from + i + 1;
+        }
+    }
+    spans.push(trimmed(start, text.len()));
+    spans
+}
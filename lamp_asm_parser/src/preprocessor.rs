@@ -0,0 +1,550 @@
+use crate::lexer::{is_identifier, strip_comment};
+use crate::source::{Expansion, Located, SourceLine};
+use lamp_common::constants::COMMENT_MARKER;
+use lamp_common::op::get_op;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+pub const MACRO_DIRECTIVE: &str = ".macro";
+pub const ENDM_DIRECTIVE: &str = ".endm";
+pub const INCLUDE_DIRECTIVE: &str = ".include";
+// A macro expanding itself forever is stopped once this many invocations are nested
+pub const MAX_EXPANSION_DEPTH: usize = 64;
+
+// Different types of errors the preprocessor can encounter
+#[derive(Debug, PartialEq)]
+pub enum PreprocessorError {
+    InvalidMacroDefinition(usize, String),
+    MacroRedefinition(usize, String),
+    UnterminatedMacro(usize, String),
+    UnexpectedEndm(usize),
+    WrongArgumentCount {
+        line: usize,
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    UnknownParameter(usize, String),
+    RecursionLimit(usize, String),
+    InvalidInclude(usize, String),
+    IncludeNotFound(usize, String, String),
+    IncludeCycle(usize, String),
+}
+
+impl std::fmt::Display for PreprocessorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            /* at + 1 because at is an array/vec index */
+            Self::InvalidMacroDefinition(at, def) => {
+                write!(
+                    f,
+                    "Invalid macro definition at line {}: \'{}\'",
+                    at + 1,
+                    def
+                )
+            }
+            Self::MacroRedefinition(at, name) => {
+                write!(f, "Macro \'{}\' redefined at line {}", name, at + 1)
+            }
+            Self::UnterminatedMacro(at, name) => write!(
+                f,
+                "Macro \'{}\' defined at line {} has no matching {}",
+                name,
+                at + 1,
+                ENDM_DIRECTIVE
+            ),
+            Self::UnexpectedEndm(at) => write!(
+                f,
+                "Unexpected {} at line {}: no macro is being defined",
+                ENDM_DIRECTIVE,
+                at + 1
+            ),
+            Self::WrongArgumentCount {
+                line,
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Macro \'{}\' takes {} argument(s) but {} were given at line {}",
+                name,
+                expected,
+                found,
+                line + 1
+            ),
+            Self::UnknownParameter(at, param) => {
+                write!(
+                    f,
+                    "Unknown macro parameter at line {}: \'\\{}\'",
+                    at + 1,
+                    param
+                )
+            }
+            Self::RecursionLimit(at, name) => write!(
+                f,
+                "Macro \'{}\' invoked at line {} is nested more than {} times",
+                name,
+                at + 1,
+                MAX_EXPANSION_DEPTH
+            ),
+            Self::InvalidInclude(at, line) => write!(
+                f,
+                "Invalid {} at line {}: \'{}\' (expected a quoted path)",
+                INCLUDE_DIRECTIVE,
+                at + 1,
+                line
+            ),
+            Self::IncludeNotFound(at, path, reason) => write!(
+                f,
+                "Cannot include \'{}\' at line {}: {}",
+                path,
+                at + 1,
+                reason
+            ),
+            Self::IncludeCycle(at, path) => write!(
+                f,
+                "\'{}\' included at line {} includes itself",
+                path,
+                at + 1
+            ),
+        }
+    }
+}
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<SourceLine>,
+}
+
+// Expands the macros of a source.
+//
+// A macro is defined with:
+//   .macro name param1, param2
+//   ...
+//   .endm
+// and invoked like an instruction: `name #1, #2`.
+// Inside the body, `\param1` is replaced by the argument's text and `\@` by a number unique
+// to each expansion, which allows a macro to define its own labels (`loop\@:`).
+// Macros can invoke other macros and define new ones.
+//
+// `.include "path"` is replaced by the content of the file, the path being relative
+// to the file containing the directive.
+pub struct Preprocessor {
+    macros: HashMap<String, Macro>,
+    expansion_count: usize,
+    // Files being read, to catch the ones including themselves
+    include_stack: Vec<PathBuf>,
+    // Lines of the source that aren't output, like macro definitions and invocations,
+    // with the number of lines output before them, for listings
+    consumed: Vec<(usize, SourceLine)>,
+}
+
+type PreprocessorResult<T> = Result<T, Located<PreprocessorError>>;
+
+impl Preprocessor {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            macros: HashMap::new(),
+            expansion_count: 0,
+            include_stack: vec![],
+            consumed: vec![],
+        }
+    }
+
+    // The lines process didn't output since the last call, see `consumed`
+    pub fn take_consumed(&mut self) -> Vec<(usize, SourceLine)> {
+        std::mem::take(&mut self.consumed)
+    }
+
+    // Lines coming from a macro are listed through the line invoking it
+    fn consume(&mut self, output: &[SourceLine], line: &SourceLine) {
+        if line.expansions.is_empty() {
+            self.consumed.push((output.len(), line.clone()));
+        }
+    }
+
+    pub fn process(
+        &mut self,
+        lines: Vec<SourceLine>,
+    ) -> Result<Vec<SourceLine>, Vec<Located<PreprocessorError>>> {
+        let mut output = Vec::<SourceLine>::new();
+        let mut errors = Vec::<Located<PreprocessorError>>::new();
+        if let Some(Ok(path)) = lines.first().map(|l| Path::new(&*l.file).canonicalize()) {
+            self.include_stack.push(path);
+        }
+        self.process_lines(lines, 0, &mut output, &mut errors);
+        self.include_stack.clear();
+
+        if errors.is_empty() {
+            return Ok(output);
+        }
+        Err(errors)
+    }
+
+    fn process_lines(
+        &mut self,
+        lines: Vec<SourceLine>,
+        depth: usize,
+        output: &mut Vec<SourceLine>,
+        errors: &mut Vec<Located<PreprocessorError>>,
+    ) {
+        let mut lines = lines.into_iter();
+
+        while let Some(line) = lines.next() {
+            // Lines are kept as they are, so the columns of their spans stay right
+            let code = strip_comment(&line.text).trim().to_owned();
+            let (first_word, rest) = split_first_word(&code);
+            // A macro can be called after a label, like `start: inc2 r1`
+            let (label, call) = split_label(&code);
+            let (call_name, call_args) = split_first_word(call);
+
+            if first_word == MACRO_DIRECTIVE {
+                // The body and .endm, as they are read
+                let mut definition = vec![line.clone()];
+                let mut body = lines.by_ref().inspect(|line| definition.push(line.clone()));
+                if let Err(e) = self.define_macro(&line, rest, &mut body) {
+                    errors.push(e);
+                }
+                for line in &definition {
+                    self.consume(output, line);
+                }
+            } else if first_word == ENDM_DIRECTIVE {
+                errors.push(Located::at(
+                    PreprocessorError::UnexpectedEndm(line.line),
+                    &line,
+                ));
+            } else if first_word == INCLUDE_DIRECTIVE {
+                self.consume(output, &line);
+                match self.include_file(&line, rest) {
+                    Ok((path, included)) => {
+                        self.process_lines(included, depth, output, errors);
+                        self.include_stack.retain(|p| *p != path);
+                    }
+                    Err(e) => errors.push(e),
+                }
+            } else if self.macros.contains_key(call_name) {
+                let name = call_name.to_owned();
+                // A labeled call is listed by its label's line instead
+                if label.is_none() {
+                    self.consume(output, &line);
+                }
+                match self.expand_macro(&line, &name, call_args, depth) {
+                    Ok(mut expanded) => {
+                        // The label goes on a line of its own before the expansion, cut
+                        // from the call so its column stays right
+                        if label.is_some() {
+                            let mut label_line = line.clone();
+                            label_line
+                                .text
+                                .truncate(line.text.find(':').unwrap_or(0) + 1);
+                            expanded.insert(0, label_line);
+                        }
+                        self.process_lines(expanded, depth + 1, output, errors)
+                    }
+                    Err(e) => errors.push(e),
+                }
+            } else {
+                output.push(line);
+            }
+        }
+    }
+
+    // Reads a macro's header and its body, up to the matching .endm
+    #[allow(clippy::result_large_err)]
+    fn define_macro(
+        &mut self,
+        header: &SourceLine,
+        definition: &str,
+        lines: &mut impl Iterator<Item = SourceLine>,
+    ) -> PreprocessorResult<()> {
+        let invalid = || {
+            Located::at(
+                PreprocessorError::InvalidMacroDefinition(header.line, header.text.clone()),
+                header,
+            )
+        };
+
+        let (name, params) = split_first_word(definition);
+        if !is_identifier(name) || get_op(name.to_owned()).is_ok() {
+            return Err(invalid());
+        }
+        let params: Vec<String> = if params.is_empty() {
+            vec![]
+        } else {
+            params.split(',').map(|p| p.trim().to_owned()).collect()
+        };
+        if params.iter().any(|p| !is_identifier(p)) {
+            return Err(invalid());
+        }
+
+        // Nested definitions are part of the body, and get defined when the macro is expanded
+        let mut nesting = 0;
+        let mut body = Vec::<SourceLine>::new();
+        loop {
+            match lines.next() {
+                Some(line) => {
+                    let (first_word, _) = split_first_word(line.text.trim());
+                    if first_word == MACRO_DIRECTIVE {
+                        nesting += 1;
+                    } else if first_word == ENDM_DIRECTIVE {
+                        if nesting == 0 {
+                            break;
+                        }
+                        nesting -= 1;
+                    }
+                    body.push(line);
+                }
+                None => {
+                    return Err(Located::at(
+                        PreprocessorError::UnterminatedMacro(header.line, name.to_owned()),
+                        header,
+                    ))
+                }
+            }
+        }
+
+        if self.macros.contains_key(name) {
+            return Err(Located::at(
+                PreprocessorError::MacroRedefinition(header.line, name.to_owned()),
+                header,
+            ));
+        }
+        self.macros.insert(name.to_owned(), Macro { params, body });
+        Ok(())
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn expand_macro(
+        &mut self,
+        call: &SourceLine,
+        name: &str,
+        args: &str,
+        depth: usize,
+    ) -> PreprocessorResult<Vec<SourceLine>> {
+        if depth >= MAX_EXPANSION_DEPTH {
+            return Err(Located::at(
+                PreprocessorError::RecursionLimit(call.line, name.to_owned()),
+                call,
+            ));
+        }
+
+        let id = self.expansion_count;
+        self.expansion_count += 1;
+
+        let args = split_arguments(args);
+        let mac = &self.macros[name];
+        if args.len() != mac.params.len() {
+            return Err(Located::at(
+                PreprocessorError::WrongArgumentCount {
+                    line: call.line,
+                    name: name.to_owned(),
+                    expected: mac.params.len(),
+                    found: args.len(),
+                },
+                call,
+            ));
+        }
+
+        let mut expansions = call.expansions.clone();
+        expansions.push(Expansion {
+            macro_name: name.to_owned(),
+            file: call.file.clone(),
+            line: call.line,
+        });
+
+        // Lines of a nested definition only get the outer parameters replaced,
+        // `\@` and their own parameters are kept for when the nested macro is expanded.
+        let mut nesting = 0;
+        let mut expanded = Vec::<SourceLine>::with_capacity(mac.body.len());
+        for body_line in &mac.body {
+            let trimmed = body_line.text.trim();
+            if trimmed.starts_with(COMMENT_MARKER) {
+                continue;
+            }
+            let (first_word, _) = split_first_word(trimmed);
+            if first_word == MACRO_DIRECTIVE {
+                nesting += 1;
+            } else if first_word == ENDM_DIRECTIVE {
+                nesting -= 1;
+            }
+            let nested = nesting > 0 || first_word == ENDM_DIRECTIVE;
+
+            let text =
+                substitute(&body_line.text, &mac.params, &args, id, nested).map_err(|param| {
+                    Located::new(
+                        PreprocessorError::UnknownParameter(body_line.line, param),
+                        &body_line.file,
+                        &expansions,
+                    )
+                })?;
+            expanded.push(SourceLine {
+                text,
+                file: body_line.file.clone(),
+                line: body_line.line,
+                expansions: expansions.clone(),
+            });
+        }
+        Ok(expanded)
+    }
+
+    // Reads the file an .include points to. Gives its canonical path, to be removed from the
+    // include stack once the file is processed, and its lines.
+    #[allow(clippy::result_large_err)]
+    fn include_file(
+        &mut self,
+        line: &SourceLine,
+        arg: &str,
+    ) -> PreprocessorResult<(PathBuf, Vec<SourceLine>)> {
+        if arg.len() < 2 || !arg.starts_with('"') || !arg.ends_with('"') {
+            return Err(Located::at(
+                PreprocessorError::InvalidInclude(line.line, line.text.clone()),
+                line,
+            ));
+        }
+        let name = &arg[1..arg.len() - 1];
+        let path = match Path::new(&*line.file).parent() {
+            Some(dir) => dir.join(name),
+            None => PathBuf::from(name),
+        };
+        let not_found = |reason: std::io::Error| {
+            Located::at(
+                PreprocessorError::IncludeNotFound(line.line, name.to_owned(), reason.to_string()),
+                line,
+            )
+        };
+
+        let canonical = path.canonicalize().map_err(not_found)?;
+        if self.include_stack.contains(&canonical) {
+            return Err(Located::at(
+                PreprocessorError::IncludeCycle(line.line, name.to_owned()),
+                line,
+            ));
+        }
+        let content = std::fs::read_to_string(&path).map_err(not_found)?;
+        self.include_stack.push(canonical.clone());
+
+        let file: Rc<str> = path.to_string_lossy().into();
+        let included = content
+            .lines()
+            .enumerate()
+            .map(|(i, text)| SourceLine {
+                text: text.to_owned(),
+                file: file.clone(),
+                line: i,
+                expansions: line.expansions.clone(),
+            })
+            .collect();
+        Ok((canonical, included))
+    }
+}
+
+// `label: rest`, when the line starts with a label
+fn split_label(code: &str) -> (Option<&str>, &str) {
+    match code.split_once(':') {
+        Some((label, rest)) if is_identifier(label) => (Some(label), rest.trim_start()),
+        _ => (None, code),
+    }
+}
+
+fn split_first_word(line: &str) -> (&str, &str) {
+    match line.find(char::is_whitespace) {
+        Some(index) => (&line[..index], line[index..].trim()),
+        None => (line, ""),
+    }
+}
+
+// Splits a macro invocation's arguments on commas,
+// ignoring the ones between parentheses or in character literals
+fn split_arguments(args: &str) -> Vec<String> {
+    let mut result = Vec::<String>::new();
+    if args.trim().is_empty() {
+        return result;
+    }
+
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut current = String::new();
+    for c in args.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '\'' => quoted = !quoted,
+            _ if quoted => {}
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                result.push(current.trim().to_owned());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    result.push(current.trim().to_owned());
+    result
+}
+
+// Replaces the parameters of a body line by the arguments. On error, gives the unknown parameter.
+fn substitute(
+    text: &str,
+    params: &[String],
+    args: &[String],
+    id: usize,
+    nested: bool,
+) -> Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.char_indices().peekable();
+    // Character literals like '\n' are kept as they are, like in split_arguments
+    let mut quoted = false;
+    let mut escaped = false;
+
+    while let Some((_, c)) = chars.next() {
+        if quoted {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '\'' => quoted = false,
+                _ => {}
+            }
+            result.push(c);
+            continue;
+        }
+        if c != '\\' {
+            quoted = c == '\'';
+            result.push(c);
+            continue;
+        }
+        if let Some((_, '@')) = chars.peek() {
+            chars.next();
+            if nested {
+                result.push_str("\\@");
+            } else {
+                result.push_str(&id.to_string());
+            }
+            continue;
+        }
+
+        let mut param = String::new();
+        while let Some((_, c)) = chars.peek() {
+            if c.is_ascii_alphanumeric() || *c == '_' {
+                param.push(*c);
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        match params.iter().position(|p| *p == param) {
+            Some(index) => result.push_str(&args[index]),
+            None if nested => {
+                result.push('\\');
+                result.push_str(&param);
+            }
+            None => return Err(param),
+        }
+    }
+    Ok(result)
+}
@@ -0,0 +1,152 @@
+use std::rc::Rc;
+
+// A range of bytes in a line's text, `end` excluded
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
+// A macro invocation a line comes from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expansion {
+    pub macro_name: String,
+    // File and line of the invocation
+    pub file: Rc<str>,
+    pub line: usize,
+}
+
+// A line ready to be tokenized, with the place it comes from.
+// For a line produced by a macro, `line` is the line inside the macro's body,
+// and `expansions` lists the invocations that led to it, outermost first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLine {
+    pub text: String,
+    pub file: Rc<str>,
+    pub line: usize,
+    pub expansions: Vec<Expansion>,
+}
+
+impl SourceLine {
+    pub fn new(text: &str, file: &Rc<str>, line: usize) -> Self {
+        Self {
+            text: text.to_owned(),
+            file: file.clone(),
+            line,
+            expansions: vec![],
+        }
+    }
+
+    pub fn from_lines(lines: &[String], file: &str) -> Vec<Self> {
+        let file: Rc<str> = file.into();
+        lines
+            .iter()
+            .enumerate()
+            .map(|(i, text)| Self::new(text, &file, i))
+            .collect()
+    }
+}
+
+// An error, with the file it was found in and the macro invocations that led to it.
+// When the error comes from a given line, `line` has its index and its text,
+// and `span` the part of the text the error is about.
+#[derive(Debug)]
+pub struct Located<E> {
+    pub error: E,
+    pub file: Rc<str>,
+    pub line: Option<(usize, String)>,
+    pub span: Option<Span>,
+    // A suggestion to fix the error
+    pub help: Option<String>,
+    pub expansions: Vec<Expansion>,
+}
+
+impl<E> Located<E> {
+    pub fn new(error: E, file: &Rc<str>, expansions: &[Expansion]) -> Self {
+        Self {
+            error,
+            file: file.clone(),
+            line: None,
+            span: None,
+            help: None,
+            expansions: expansions.to_vec(),
+        }
+    }
+
+    // An error found on the given line
+    pub fn at(error: E, line: &SourceLine) -> Self {
+        Self {
+            line: Some((line.line, line.text.clone())),
+            ..Self::new(error, &line.file, &line.expansions)
+        }
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_help(mut self, help: Option<String>) -> Self {
+        self.help = help;
+        self
+    }
+
+    // Converts the error, keeping its location
+    pub fn map<F>(self, convert: impl FnOnce(E) -> F) -> Located<F> {
+        Located {
+            error: convert(self.error),
+            file: self.file,
+            line: self.line,
+            span: self.span,
+            help: self.help,
+            expansions: self.expansions,
+        }
+    }
+
+    // 1-based column the span starts at, in characters
+    pub fn column(&self) -> Option<usize> {
+        match (&self.line, self.span) {
+            (Some((_, text)), Some(span)) => Some(column_of(text, span.start)),
+            _ => None,
+        }
+    }
+}
+
+pub fn column_of(text: &str, index: usize) -> usize {
+    text.get(..index).map_or(0, |prefix| prefix.chars().count()) + 1
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for Located<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.file.is_empty() {
+            write!(f, "{}", self.file)?;
+            if let Some((line, _)) = &self.line {
+                write!(f, ":{}", line + 1)?;
+                if let Some(column) = self.column() {
+                    write!(f, ":{}", column)?;
+                }
+            }
+            write!(f, ": ")?;
+        }
+        write!(f, "{}", self.error)?;
+        // Innermost invocation first, like a backtrace
+        for expansion in self.expansions.iter().rev() {
+            write!(
+                f,
+                "\n    in expansion of macro \'{}\' at ",
+                expansion.macro_name
+            )?;
+            if !expansion.file.is_empty() {
+                write!(f, "{}:", expansion.file)?;
+            }
+            write!(f, "line {}", expansion.line + 1)?;
+        }
+        Ok(())
+    }
+}
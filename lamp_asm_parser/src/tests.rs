@@ -1,4 +1,10 @@
-use crate::lexer::{Lexer, TokenType, TokenizedLine};
+use crate::diagnostic::{closest, Diagnostic};
+use crate::expr::{Expr, ExprError, Value};
+use crate::lexer::{Lexer, LexerError, TokenType, TokenizedLine};
+use crate::parser::{Instruction, Label, Line, Parser, Statement};
+use crate::preprocessor::{Preprocessor, PreprocessorError};
+use crate::source::{Expansion, Located, SourceLine, Span};
+use lamp_common::container::SectionKind;
 use lamp_common::op::Opcode;
 
 #[test]
@@ -8,7 +14,7 @@ fn test_parse() {
 
     let res = lexer.tokenize_line(to_parse, 1);
 
-    assert_eq!(res.is_ok(), true);
+    assert!(res.is_ok());
 
     let expected = TokenizedLine {
         opcode: Opcode::LOAD,
@@ -29,5 +35,373 @@ fn test_parse_error() {
 
     let res = lexer.tokenize_line(to_parse, 1);
 
-    assert_eq!(res.is_err(), true);
+    assert!(res.is_err());
+}
+
+fn preprocess(source: &str) -> Result<Vec<SourceLine>, Vec<Located<PreprocessorError>>> {
+    let lines: Vec<String> = source.lines().map(|s| s.to_string()).collect();
+    Preprocessor::new().process(SourceLine::from_lines(&lines, ""))
+}
+
+#[test]
+fn test_macro_expansion() {
+    let source = "\
+.macro add_to dst, a, b
+    ADD \\a, \\b, \\dst
+done\\@:
+.endm
+add_to #3, #1, #2
+add_to #4, #3, #3";
+
+    let lines = preprocess(source).unwrap();
+    let texts: Vec<&str> = lines.iter().map(|l| l.text.trim()).collect();
+    assert_eq!(
+        texts,
+        vec!["ADD #1, #2, #3", "done0:", "ADD #3, #3, #4", "done1:"]
+    );
+
+    // Expanded lines point to the macro's body and to the invocation
+    assert_eq!(lines[2].line, 1);
+    assert_eq!(
+        lines[2].expansions,
+        vec![Expansion {
+            macro_name: "add_to".to_string(),
+            file: "".into(),
+            line: 5,
+        }]
+    );
+}
+
+#[test]
+fn test_labeled_macro_call() {
+    let source = "\
+.macro inc2 reg
+    INC \\reg
+    INC \\reg
+.endm
+start: inc2 r1
+  again:inc2 r2 ; twice more";
+
+    let lines = preprocess(source).unwrap();
+    let texts: Vec<&str> = lines.iter().map(|l| l.text.trim()).collect();
+    assert_eq!(
+        texts,
+        vec!["start:", "INC r1", "INC r1", "again:", "INC r2", "INC r2"]
+    );
+    // The label keeps its place in the call
+    assert_eq!(lines[3].text, "  again:");
+    assert_eq!(lines[3].line, 5);
+}
+
+#[test]
+fn test_macro_character_literals() {
+    // Escapes in character literals aren't parameters, quotes in arguments are kept
+    let source = "\
+.macro load_char reg, c
+    LOAD \\reg, '\\n'
+    LOAD \\reg, '\\''
+    LOAD \\reg, \\c
+.endm
+load_char r1, ','";
+
+    let lines = preprocess(source).unwrap();
+    let texts: Vec<&str> = lines.iter().map(|l| l.text.trim()).collect();
+    assert_eq!(
+        texts,
+        vec!["LOAD r1, '\\n'", "LOAD r1, '\\''", "LOAD r1, ','"]
+    );
+}
+
+#[test]
+fn test_nested_macros() {
+    let source = "\
+.macro inc_twice reg
+    INC \\reg
+    INC \\reg
+.endm
+.macro inc_four reg
+    inc_twice \\reg
+    inc_twice \\reg
+.endm
+.macro define_inc name
+    .macro \\name r
+        INC \\r
+    .endm
+.endm
+define_inc my_inc
+inc_four #1
+my_inc #2";
+
+    let lines = preprocess(source).unwrap();
+    let texts: Vec<&str> = lines.iter().map(|l| l.text.trim()).collect();
+    assert_eq!(
+        texts,
+        vec!["INC #1", "INC #1", "INC #1", "INC #1", "INC #2"]
+    );
+    assert_eq!(lines[0].expansions.len(), 2);
+}
+
+#[test]
+fn test_macro_errors() {
+    let errors = preprocess(".macro m a\n    INC \\b\n.endm\nm #1").unwrap_err();
+    assert_eq!(
+        errors[0].error,
+        PreprocessorError::UnknownParameter(1, "b".to_string())
+    );
+    assert_eq!(errors[0].expansions[0].line, 3);
+
+    let errors = preprocess(".macro m a\n    INC \\a\n.endm\nm #1, #2").unwrap_err();
+    assert_eq!(
+        errors[0].error,
+        PreprocessorError::WrongArgumentCount {
+            line: 3,
+            name: "m".to_string(),
+            expected: 1,
+            found: 2,
+        }
+    );
+
+    let errors = preprocess(".macro m\n    m\n.endm\nm").unwrap_err();
+    assert_eq!(
+        errors[0].error,
+        PreprocessorError::RecursionLimit(1, "m".to_string())
+    );
+
+    let errors = preprocess(".macro m\n    NOP").unwrap_err();
+    assert_eq!(
+        errors[0].error,
+        PreprocessorError::UnterminatedMacro(0, "m".to_string())
+    );
+}
+
+#[test]
+fn test_expressions() {
+    let constants = |name: &str| match name {
+        "SIZE" => Some(Value::Constant(6)),
+        "start" => Some(Value::Address {
+            symbol: 0,
+            base: Some((SectionKind::Code, 4)),
+            addend: 0,
+        }),
+        "end" => Some(Value::Address {
+            symbol: 1,
+            base: Some((SectionKind::Code, 10)),
+            addend: 0,
+        }),
+        _ => None,
+    };
+    let eval = |text: &str| {
+        Expr::parse(text)
+            .unwrap()
+            .evaluate(Value::Constant(0), &constants)
+    };
+
+    assert_eq!(eval("1 + 2 * 3"), Ok(Value::Constant(7)));
+    assert_eq!(eval("(1 + 2) * 3"), Ok(Value::Constant(9)));
+    assert_eq!(eval("1 << 4 | 3 & ~1"), Ok(Value::Constant(18)));
+    assert_eq!(eval("-SIZE / 4 % 5"), Ok(Value::Constant(-1)));
+    assert_eq!(eval("end - start"), Ok(Value::Constant(6)));
+    assert_eq!(
+        eval("start + SIZE - 1"),
+        Ok(Value::Address {
+            symbol: 0,
+            base: Some((SectionKind::Code, 4)),
+            addend: 5,
+        })
+    );
+
+    assert_eq!(
+        eval("start + 0x7FFFFFFFFFFFFFFF - start"),
+        Err(ExprError::Overflow)
+    );
+    assert_eq!(
+        eval("start - 0x7FFFFFFFFFFFFFFF - end"),
+        Err(ExprError::Overflow)
+    );
+    assert_eq!(eval("start * 2"), Err(ExprError::NotConstant));
+    assert_eq!(eval("SIZE / (3 - 3)"), Err(ExprError::DivisionByZero));
+    assert_eq!(
+        eval("SIZE + other"),
+        Err(ExprError::UndefinedSymbol("other".to_string()))
+    );
+    assert_eq!(Expr::parse("(1 + 2"), Err(ExprError::UnexpectedEnd));
+    assert_eq!(Expr::parse("1 + * 2"), Err(ExprError::UnexpectedChar('*')));
+}
+
+#[test]
+fn test_parse_expression_operands() {
+    let lexer = Lexer::new();
+    let res = lexer
+        .tokenize_line("LOAD #1, table + 2 ; comment", 0)
+        .unwrap();
+    assert_eq!(
+        res.operands,
+        vec![
+            TokenType::Num8(1),
+            TokenType::Expr(Expr::parse("table + 2").unwrap()),
+        ]
+    );
+}
+
+#[test]
+fn test_literals() {
+    let eval = |text: &str| {
+        Expr::parse(text)
+            .and_then(|expr| expr.evaluate(Value::Constant(0), &|_| None))
+            .and_then(Value::constant)
+    };
+
+    assert_eq!(eval("0x1F4"), Ok(500));
+    assert_eq!(eval("0b1010_1010"), Ok(170));
+    assert_eq!(eval("0o17"), Ok(15));
+    assert_eq!(eval("1_000"), Ok(1000));
+    assert_eq!(eval("-0x10"), Ok(-16));
+    assert_eq!(eval("'A'"), Ok(65));
+    assert_eq!(eval("'\\n' + '\\''"), Ok(49));
+    assert_eq!(eval("'\\x7f'"), Ok(127));
+
+    assert_eq!(
+        eval("0xG"),
+        Err(ExprError::InvalidNumber("0xG".to_string()))
+    );
+    assert_eq!(
+        eval("12ab"),
+        Err(ExprError::InvalidNumber("12ab".to_string()))
+    );
+    assert_eq!(
+        eval("'ab'"),
+        Err(ExprError::InvalidCharLiteral("'ab'".to_string()))
+    );
+    assert_eq!(eval("'a"), Err(ExprError::UnexpectedEnd));
+}
+
+#[test]
+fn test_quoted_separators() {
+    let lexer = Lexer::new();
+    let res = lexer.tokenize_line("LOAD #1, ',' ; ';'", 0).unwrap();
+    assert_eq!(
+        res.operands,
+        vec![
+            TokenType::Num8(1),
+            TokenType::Expr(Expr::parse("','").unwrap())
+        ]
+    );
+}
+
+#[test]
+fn test_registers() {
+    let mut lexer = Lexer::new();
+    lexer.add_alias("counter", 7);
+
+    let res = lexer.tokenize_line("ADD r1, SP, counter", 0).unwrap();
+    assert_eq!(
+        res.operands,
+        vec![
+            TokenType::Register(1),
+            TokenType::Register(31),
+            TokenType::Register(7),
+        ]
+    );
+
+    // Not registers, so they are symbols
+    let res = lexer.tokenize_line("JMP r32", 0).unwrap();
+    assert_eq!(
+        res.operands,
+        vec![TokenType::Expr(Expr::parse("r32").unwrap())]
+    );
+    let res = lexer.tokenize_line("JMP r01", 0).unwrap();
+    assert_eq!(
+        res.operands,
+        vec![TokenType::Expr(Expr::parse("r01").unwrap())]
+    );
+}
+
+#[test]
+fn test_parse_lines() {
+    let lexer = Lexer::new();
+    let parser = Parser::new(&lexer);
+
+    assert_eq!(parser.parse_line("", 0).unwrap(), Line::default());
+    assert_eq!(
+        parser.parse_line("  ; only a comment\r", 0).unwrap(),
+        Line::default()
+    );
+
+    let line = parser.parse_line("\tloop:  HLT ; stop\r", 0).unwrap();
+    assert_eq!(
+        line.label,
+        Some(Label {
+            name: "loop".to_string(),
+            span: Span::new(1, 5),
+        })
+    );
+    assert_eq!(
+        line.statement,
+        Some(Statement::Instruction(Instruction {
+            opcode: Opcode::HLT,
+            span: Span::new(8, 11),
+            operands: vec![],
+        }))
+    );
+
+    let line = parser.parse_line("  LOAD   r1 ,  #5", 0).unwrap();
+    match line.statement {
+        Some(Statement::Instruction(instruction)) => {
+            let spans: Vec<Span> = instruction.operands.iter().map(|o| o.span).collect();
+            assert_eq!(spans, vec![Span::new(9, 11), Span::new(15, 17)]);
+        }
+        other => panic!("Expected an instruction, got {:?}", other),
+    }
+
+    let line = parser.parse_line(".byte 'a', ','", 0).unwrap();
+    match line.statement {
+        Some(Statement::Directive(directive)) => {
+            assert_eq!(directive.name, ".byte");
+            let args: Vec<&str> = directive.args.iter().map(|a| a.text.as_str()).collect();
+            assert_eq!(args, vec!["'a'", "','"]);
+        }
+        other => panic!("Expected a directive, got {:?}", other),
+    }
+
+    let error = parser.parse_line("ADD r1,, r2", 0).unwrap_err();
+    assert_eq!(error.span, Span::new(7, 7));
+    let error = parser.parse_line("  FOO r1", 0).unwrap_err();
+    assert_eq!(error.span, Span::new(2, 5));
+}
+
+#[test]
+fn test_diagnostics() {
+    let names = ["ADD", "SUB", "LOAD"];
+    assert_eq!(closest("ADDD", names.iter().copied()), Some("ADD"));
+    assert_eq!(closest("LOD", names.iter().copied()), Some("LOAD"));
+    assert_eq!(closest("XYZ", names.iter().copied()), None);
+    // A swap of two letters is one typo
+    assert_eq!(closest("LAOD", names.iter().copied()), Some("LOAD"));
+    assert_eq!(closest("SBU", names.iter().copied()), Some("SUB"));
+    // MOD and LOAD are as close to LOD, LOAD starts like it
+    let opcodes = ["MOD", "LOAD"];
+    assert_eq!(closest("LOD", opcodes.iter().copied()), Some("LOAD"));
+    assert_eq!(closest("MD", opcodes.iter().copied()), Some("MOD"));
+
+    let file = "main.asm".into();
+    let line = SourceLine::new("    ADDD r1, r2, r3", &file, 2);
+    let error = Located::at(LexerError::InvalidMnemonic(2, "ADDD".to_owned()), &line)
+        .with_span(Span::new(4, 8));
+    let diagnostic = Diagnostic::error(&error);
+    assert_eq!(diagnostic.code, "E0102");
+
+    let rendered = diagnostic.render();
+    let lines: Vec<&str> = rendered.lines().collect();
+    assert_eq!(lines[1], " --> main.asm:3:5");
+    assert_eq!(lines[3], "3 |     ADDD r1, r2, r3");
+    assert_eq!(lines[4], "  |     ^^^^");
+    assert_eq!(lines[5], "  = help: did you mean \'ADD\'?");
+
+    let json: serde_json::Value = serde_json::from_str(&diagnostic.to_json()).unwrap();
+    assert_eq!(json["severity"], "error");
+    assert_eq!(json["code"], "E0102");
+    assert_eq!(json["file"], "main.asm");
+    assert_eq!(json["line"], 3);
+    assert_eq!(json["column"], 5);
+    assert_eq!(json["end_column"], 9);
 }
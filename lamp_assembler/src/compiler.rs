@@ -1,48 +1,1147 @@
-use lamp_asm_parser::lexer::{Lexer, LexerError, TokenType, TokenizedLine};
+use crate::lint::{check_code, CodeItem, CodeItemKind, LintOperand, Warning};
+use crate::listing::ListingLine;
+use lamp_asm_parser::diagnostic::{closest, did_you_mean, ErrorCode};
+use lamp_asm_parser::expr::{Expr, ExprError, Value};
+use lamp_asm_parser::lexer::{is_identifier, Lexer, TokenType};
+use lamp_asm_parser::parser::{Argument, Instruction, Operand, ParseError, Parser, Statement};
+use lamp_asm_parser::preprocessor::{Preprocessor, PreprocessorError};
+use lamp_asm_parser::source::{column_of, Located, SourceLine, Span};
+use lamp_common::container::SectionKind;
+use lamp_common::debug_info::{DebugInfo, LineEntry, MacroOrigin, SourceLocation};
+use lamp_common::object::{ObjectFile, Relocation, RelocationKind, Symbol};
+use lamp_common::op::{get_op, get_operands, instruction_size, Opcode, OperandKind};
+use lamp_common::register::{parse_register, RegisterAlias};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+
+// Different types of errors the compiler can encounter
+#[derive(Debug)]
+pub enum CompileError {
+    Preprocessor(PreprocessorError),
+    Parse(ParseError),
+    DuplicateLabel(usize, String),
+    UndefinedLabel(usize, String),
+    InvalidDirective(usize, String),
+    UnknownSection(usize, String),
+    InstructionOutsideCode(usize),
+    DataInBss(usize),
+    UndefinedGlobal(usize, String),
+    ExternDefined(usize, String),
+    ConstantRedefinition(usize, String),
+    Expression(usize, ExprError),
+    OutOfRange(usize, i64, u8),
+    AddressTooWide(usize),
+    WrongOperandCount {
+        line: usize,
+        opcode: Opcode,
+        expected: usize,
+        found: usize,
+    },
+    WrongOperandType {
+        line: usize,
+        opcode: Opcode,
+        operand: usize,
+        expected: OperandKind,
+    },
+    RegisterInData(usize),
+    ReservedName(usize, String),
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Preprocessor(e) => write!(f, "{}", e),
+            Self::Parse(e) => write!(f, "{}", e),
+            /* at + 1 because at is an array/vec index */
+            Self::DuplicateLabel(at, label) => {
+                write!(f, "Label \'{}\' redefined at line {}", label, at + 1)
+            }
+            Self::UndefinedLabel(at, label) => write!(
+                f,
+                "Undefined label at line {}: \'{}\' (use .extern if it comes from another file)",
+                at + 1,
+                label
+            ),
+            Self::InvalidDirective(at, line) => {
+                write!(f, "Invalid directive at line {}: \'{}\'", at + 1, line)
+            }
+            Self::UnknownSection(at, name) => write!(
+                f,
+                "Unknown section at line {}: \'{}\' (expected code, rodata, data or bss)",
+                at + 1,
+                name
+            ),
+            Self::InstructionOutsideCode(at) => write!(
+                f,
+                "Instruction outside of the code section at line {}",
+                at + 1
+            ),
+            Self::DataInBss(at) => write!(
+                f,
+                "Initialized data in the bss section at line {} (use .space)",
+                at + 1
+            ),
+            Self::UndefinedGlobal(at, name) => write!(
+                f,
+                "Symbol \'{}\' is declared global at line {} but never defined",
+                name,
+                at + 1
+            ),
+            Self::ExternDefined(at, name) => write!(
+                f,
+                "Symbol \'{}\' is declared extern at line {} but defined in this file",
+                name,
+                at + 1
+            ),
+            Self::ConstantRedefinition(at, name) => write!(
+                f,
+                "Constant '{}' redefined at line {} (use .set for constants that change)",
+                name,
+                at + 1
+            ),
+            Self::Expression(at, e) => write!(f, "Invalid expression at line {}: {}", at + 1, e),
+            Self::OutOfRange(at, value, bits) => write!(
+                f,
+                "Value {} at line {} doesn't fit in {} bits",
+                value,
+                at + 1,
+                bits
+            ),
+            Self::AddressTooWide(at) => write!(
+                f,
+                "Addresses take 16 bits, they can't be used as an 8-bit operand at line {}",
+                at + 1
+            ),
+            Self::WrongOperandCount {
+                line,
+                opcode,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{:?} takes {} operands but {} were given at line {}",
+                opcode,
+                expected,
+                found,
+                line + 1
+            ),
+            Self::WrongOperandType {
+                line,
+                opcode,
+                operand,
+                expected,
+            } => write!(
+                f,
+                "Operand {} of {:?} at line {} should be {}",
+                operand + 1,
+                opcode,
+                line + 1,
+                match expected {
+                    OperandKind::Register => "a register (r0 to r31)",
+                    OperandKind::Imm16 => "a value, not a register",
+                }
+            ),
+            Self::RegisterInData(at) => {
+                write!(f, "Registers can\'t be used as data at line {}", at + 1)
+            }
+            Self::ReservedName(at, name) => write!(
+                f,
+                "\'{}\' is a register name and can\'t be redefined at line {}",
+                name,
+                at + 1
+            ),
+        }
+    }
+}
+
+impl ErrorCode for CompileError {
+    fn code(&self) -> &'static str {
+        match self {
+            Self::Preprocessor(e) => e.code(),
+            Self::Parse(e) => e.error.code(),
+            Self::DuplicateLabel(..) => "E0301",
+            Self::UndefinedLabel(..) => "E0302",
+            Self::InvalidDirective(..) => "E0303",
+            Self::UnknownSection(..) => "E0304",
+            Self::InstructionOutsideCode(_) => "E0305",
+            Self::DataInBss(_) => "E0306",
+            Self::UndefinedGlobal(..) => "E0307",
+            Self::ExternDefined(..) => "E0308",
+            Self::ConstantRedefinition(..) => "E0309",
+            Self::Expression(..) => "E0310",
+            Self::OutOfRange(..) => "E0311",
+            Self::AddressTooWide(_) => "E0312",
+            Self::WrongOperandCount { .. } => "E0313",
+            Self::WrongOperandType { .. } => "E0314",
+            Self::RegisterInData(_) => "E0315",
+            Self::ReservedName(..) => "E0316",
+        }
+    }
+
+    fn help(&self) -> Option<String> {
+        match self {
+            Self::Preprocessor(e) => e.help(),
+            Self::Parse(e) => e.error.help(),
+            Self::InvalidDirective(_, line) => {
+                let name = line.split_whitespace().next().unwrap_or("");
+                did_you_mean(closest(name, DIRECTIVES.iter().copied()))
+            }
+            Self::UnknownSection(_, name) => did_you_mean(closest(
+                name,
+                ["code", "rodata", "data", "bss"].iter().copied(),
+            )),
+            Self::InstructionOutsideCode(_) => {
+                Some("switch back with \'.section code\'".to_owned())
+            }
+            Self::WrongOperandType {
+                expected: OperandKind::Register,
+                ..
+            } => Some("registers are written r0 to r31, or with an alias like sp".to_owned()),
+            Self::OutOfRange(_, value, 16) if *value < 0 => {
+                Some("16-bit values are unsigned, from 0 to 65535".to_owned())
+            }
+            _ => None,
+        }
+    }
+}
+
+// Directives handled by the preprocessor and the compiler
+const DIRECTIVES: &[&str] = &[
+    ".macro", ".endm", ".include", ".section", ".global", ".extern", ".equ", ".set", ".alias",
+    ".byte", ".space",
+];
+
+type CompileErrors = Vec<Located<CompileError>>;
+
+// Sections are limited to 16 MiB, the most the VM maps, so `.space` can't ask for
+// gigabytes of zeros
+const SECTION_SIZE_BITS: u8 = 24;
+const MAX_SECTION_SIZE: u32 = 1 << SECTION_SIZE_BITS;
+
+// What a line puts into its section
+enum Item {
+    Instruction(Instruction),
+    Bytes(Vec<Operand>),
+    Space(u32),
+}
+
+// A constant defined with .equ, or with .set when it can be redefined
+struct Constant {
+    value: i64,
+    redefinable: bool,
+}
 
 pub struct Compiler {
     origin: Vec<String>,
-    pub result_buffer: Vec<u8>,
+    path: PathBuf,
+    // Every label and extern symbol, in definition order
+    symbols: Vec<Symbol>,
+    symbol_indexes: HashMap<String, usize>,
+    // Local symbols for the start of each section, used to relocate `.`
+    section_symbols: HashMap<SectionKind, usize>,
+    constants: HashMap<String, Constant>,
+    lexer: Lexer,
+    // Defined with `.alias`, for the debug info
+    aliases: Vec<RegisterAlias>,
+    // What the lints look at: where labels are defined, the symbols used by expressions
+    // and what was compiled in the code section
+    labels: Vec<(String, SourceLine, Span)>,
+    used_symbols: HashSet<String>,
+    code: Vec<CodeItem>,
+    listing: Vec<ListingLine>,
 }
 
 impl Compiler {
-    pub fn new(origin: Vec<String>) -> Self {
+    pub fn new(origin: Vec<String>, path: &Path) -> Self {
         Self {
             origin,
-            result_buffer: vec![],
+            path: path.to_path_buf(),
+            symbols: vec![],
+            symbol_indexes: HashMap::new(),
+            section_symbols: HashMap::new(),
+            constants: HashMap::new(),
+            lexer: Lexer::new(),
+            aliases: vec![],
+            labels: vec![],
+            used_symbols: HashSet::new(),
+            code: vec![],
+            listing: vec![],
         }
     }
 
-    pub fn compile(&mut self) -> Result<usize, Vec<LexerError>> {
-        let tokenization = Lexer::new().tokenize(&self.origin);
+    // Compiles the source to a relocatable object
+    pub fn compile_object(&mut self) -> Result<ObjectFile, CompileErrors> {
+        let file = self.path.to_string_lossy();
+        let mut preprocessor = Preprocessor::new();
+        let lines = preprocessor
+            .process(SourceLine::from_lines(&self.origin, &file))
+            .map_err(|errors| {
+                errors
+                    .into_iter()
+                    .map(|e| e.map(CompileError::Preprocessor))
+                    .collect::<Vec<_>>()
+            })?;
+
+        let consumed = preprocessor.take_consumed();
+
+        self.first_pass(lines, consumed)
+            .and_then(|items| self.second_pass(items))
+            .map_err(|errors| {
+                errors
+                    .into_iter()
+                    .map(|e| {
+                        let help = self.suggestion(&e.error);
+                        e.with_help(help)
+                    })
+                    .collect()
+            })
+    }
+
+    // Suggests a close symbol name for undefined ones
+    fn suggestion(&self, error: &CompileError) -> Option<String> {
+        match error {
+            CompileError::UndefinedLabel(_, name) => did_you_mean(closest(
+                name,
+                self.symbol_indexes
+                    .keys()
+                    .chain(self.constants.keys())
+                    .map(|name| name.as_str()),
+            )),
+            _ => error.help(),
+        }
+    }
 
-        match tokenization {
-            Ok(tokenized_lines) => {
-                for line in tokenized_lines {
-                    let buff = self.compile_token(line);
+    // Parses every line, handles the directives and finds where the labels are.
+    // `consumed` are the lines the preprocessor used up, only listed.
+    fn first_pass(
+        &mut self,
+        lines: Vec<SourceLine>,
+        consumed: Vec<(usize, SourceLine)>,
+    ) -> Result<Vec<(SourceLine, SectionKind, Item)>, CompileErrors> {
+        let mut items = Vec::<(SourceLine, SectionKind, Item)>::new();
+        let mut errors = CompileErrors::new();
+        let mut section = SectionKind::Code;
+        let mut offsets = HashMap::<SectionKind, u32>::new();
+        // Symbols declared with .global and .extern, with the line declaring them
+        let mut globals = Vec::<(String, SourceLine, Span)>::new();
+        let mut externs = Vec::<(String, SourceLine, Span)>::new();
 
-                    for byte in buff {
-                        self.result_buffer.push(byte);
+        let mut consumed = consumed.into_iter().peekable();
+        for (i, line) in lines.into_iter().enumerate() {
+            while let Some((_, consumed_line)) = consumed.next_if(|(before, _)| *before <= i) {
+                self.list_consumed(consumed_line, section, &offsets);
+            }
+            let parsed = match Parser::new(&self.lexer).parse_line(&line.text, line.line) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    let span = e.span;
+                    errors.push(Located::at(CompileError::Parse(e), &line).with_span(span));
+                    continue;
+                }
+            };
+            let offset = *offsets.entry(section).or_insert(0);
+            self.listing.push(ListingLine {
+                line: line.clone(),
+                section,
+                offset,
+                size: 0,
+                labeled: parsed.label.is_some(),
+                references: parsed.statement.as_ref().map_or(vec![], references),
+            });
+
+            if let Some(label) = parsed.label {
+                match self.define_label(&label.name, line.line, section, offset) {
+                    Ok(()) => self.labels.push((label.name, line.clone(), label.span)),
+                    Err(e) => errors.push(Located::at(e, &line).with_span(label.span)),
+                }
+            }
+
+            let statement = match parsed.statement {
+                Some(statement) => statement,
+                None => continue,
+            };
+            let span = statement_span(&statement);
+            let item = match statement {
+                Statement::Instruction(_) if section != SectionKind::Code => {
+                    Err(CompileError::InstructionOutsideCode(line.line))
+                }
+                Statement::Instruction(instruction) => {
+                    match check_operands(&instruction, line.line) {
+                        Ok(()) => Ok(Item::Instruction(instruction)),
+                        Err((e, span)) => {
+                            errors.push(Located::at(e, &line).with_span(span));
+                            continue;
+                        }
                     }
                 }
+                Statement::Directive(directive) => {
+                    let args: Vec<&str> = directive.args.iter().map(|a| a.text.as_str()).collect();
+                    let invalid =
+                        || CompileError::InvalidDirective(line.line, line.text.trim().to_owned());
+                    match (directive.name.as_str(), args.as_slice()) {
+                        (".section", [name]) => match section_from_name(name) {
+                            Some(kind) => {
+                                section = kind;
+                                continue;
+                            }
+                            None => {
+                                errors.push(
+                                    Located::at(
+                                        CompileError::UnknownSection(line.line, name.to_string()),
+                                        &line,
+                                    )
+                                    .with_span(directive.args[0].span),
+                                );
+                                continue;
+                            }
+                        },
+                        (".global", names) | (".extern", names)
+                            if !names.is_empty() && names.iter().all(|n| is_identifier(n)) =>
+                        {
+                            let list = if directive.name == ".global" {
+                                &mut globals
+                            } else {
+                                &mut externs
+                            };
+                            list.extend(
+                                directive
+                                    .args
+                                    .iter()
+                                    .map(|arg| (arg.text.clone(), line.clone(), arg.span)),
+                            );
+                            continue;
+                        }
+                        (".equ", [name, value]) | (".set", [name, value]) => match self
+                            .define_constant(
+                                directive.name == ".set",
+                                name,
+                                value,
+                                &line,
+                                section,
+                                offset,
+                            ) {
+                            Ok(()) => continue,
+                            Err(e) => Err(e),
+                        },
+                        (".alias", [name, register]) => {
+                            match self.define_alias(name, register, &line) {
+                                Ok(()) => continue,
+                                Err(e) => Err(e),
+                            }
+                        }
+                        (".byte", _) if section == SectionKind::Bss => {
+                            Err(CompileError::DataInBss(line.line))
+                        }
+                        // Every operand of .byte is a single byte, with or without '#'
+                        (".byte", values) if !values.is_empty() => {
+                            match self.data_operands(&directive.args, line.line) {
+                                Ok(operands) => Ok(Item::Bytes(operands)),
+                                Err((e, span)) => {
+                                    errors.push(Located::at(e, &line).with_span(span));
+                                    continue;
+                                }
+                            }
+                        }
+                        (".space", [size]) => Expr::parse(size.trim_start_matches('#'))
+                            .map_err(|e| CompileError::Expression(line.line, e))
+                            .and_then(|expr| {
+                                let size = self.constant(&expr, line.line, section, offset)?;
+                                u32::try_from(size)
+                                    .map(Item::Space)
+                                    .map_err(|_| CompileError::OutOfRange(line.line, size, 32))
+                            }),
+                        _ => Err(invalid()),
+                    }
+                }
+            };
+            match item {
+                Ok(mut item) => {
+                    self.fold_constants(&mut item);
+                    if let Some(listed) = self.listing.last_mut() {
+                        listed.size = Self::item_size(&item);
+                    }
+                    let size = Self::item_size(&item);
+                    match offset.checked_add(size) {
+                        Some(end) if end < MAX_SECTION_SIZE => {
+                            offsets.insert(section, end);
+                        }
+                        _ => {
+                            let end = i64::from(offset) + i64::from(size);
+                            errors.push(Located::at(
+                                CompileError::OutOfRange(line.line, end, SECTION_SIZE_BITS),
+                                &line,
+                            ));
+                            continue;
+                        }
+                    }
+                    items.push((line, section, item));
+                }
+                Err(e) => errors.push(Located::at(e, &line).with_span(span)),
+            }
+        }
+
+        for (_, consumed_line) in consumed {
+            self.list_consumed(consumed_line, section, &offsets);
+        }
+
+        for (name, line, span) in externs {
+            match self.symbol_indexes.get(&name) {
+                Some(i) if self.symbols[*i].is_defined() => errors.push(
+                    Located::at(CompileError::ExternDefined(line.line, name), &line)
+                        .with_span(span),
+                ),
+                Some(_) => {}
+                None => self.add_symbol(&name, None, 0),
+            }
+        }
+        for (name, line, span) in globals {
+            match self.symbol_indexes.get(&name) {
+                Some(i) if self.symbols[*i].is_defined() => self.symbols[*i].global = true,
+                _ => errors.push(
+                    Located::at(CompileError::UndefinedGlobal(line.line, name), &line)
+                        .with_span(span),
+                ),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(items)
+    }
+
+    // Encodes every item, now that every label is known
+    fn second_pass(
+        &mut self,
+        items: Vec<(SourceLine, SectionKind, Item)>,
+    ) -> Result<ObjectFile, CompileErrors> {
+        let mut object = ObjectFile::default();
+        let mut errors = CompileErrors::new();
+
+        for (line, section, item) in items {
+            if section == SectionKind::Code {
+                let offset = object.section_size(section);
+                let code_item = self.code_item(&line, &item, offset);
+                self.code.push(code_item);
+            }
+            let (operands, sizes, prefix) = match item {
+                Item::Instruction(instruction) => (
+                    instruction.operands,
+                    get_operands(instruction.opcode)
+                        .iter()
+                        .map(|op| op.size())
+                        .collect(),
+                    vec![instruction.opcode as u8],
+                ),
+                Item::Bytes(operands) => {
+                    let sizes = vec![1; operands.len()];
+                    (operands, sizes, vec![])
+                }
+                // The first pass checked the sections stay below MAX_SECTION_SIZE
+                Item::Space(size) => {
+                    match section {
+                        SectionKind::Bss => match object.bss_size.checked_add(size) {
+                            Some(bss_size) => object.bss_size = bss_size,
+                            None => {
+                                let end = i64::from(object.bss_size) + i64::from(size);
+                                errors.push(Located::at(
+                                    CompileError::OutOfRange(line.line, end, 32),
+                                    &line,
+                                ));
+                            }
+                        },
+                        _ => section_buffer(&mut object, section)
+                            .extend(std::iter::repeat_n(0, size as usize)),
+                    }
+                    continue;
+                }
+            };
+
+            let start = object.section_size(section);
+            match self.compile_operands(
+                operands,
+                &sizes,
+                line.line,
+                section,
+                start,
+                prefix.len() as u32,
+            ) {
+                Ok((bytes, relocations)) => {
+                    let buffer = section_buffer(&mut object, section);
+                    buffer.extend(prefix);
+                    buffer.extend(bytes);
+                    object.relocations.extend(relocations);
+                }
+                Err((e, span)) => errors.push(Located::at(e, &line).with_span(span)),
             }
-            Err(e) => return Err(e),
         }
-        Ok(self.result_buffer.len())
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        object.symbols = self.symbols.clone();
+        Ok(object)
+    }
+
+    // Every line once preprocessed, with where it went
+    pub fn listing(&self) -> &[ListingLine] {
+        &self.listing
+    }
+
+    // Labels with where they are defined, in definition order
+    pub fn labels(&self) -> &[(String, SourceLine, Span)] {
+        &self.labels
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    // A label or an extern symbol. Panics if there is none with this name.
+    pub fn symbol(&self, name: &str) -> &Symbol {
+        &self.symbols[self.symbol_indexes[name]]
+    }
+
+    // Whether the name is a label or an extern symbol, and not a section's symbol
+    pub fn is_label(&self, name: &str) -> bool {
+        self.symbol_indexes.contains_key(name)
+    }
+
+    // Runs the lints on what was compiled. Only meaningful once compile_object succeeded.
+    pub fn lint(&self) -> Vec<Located<Warning>> {
+        let mut warnings = Vec::<Located<Warning>>::new();
+        for (name, line, span) in &self.labels {
+            let symbol = self.symbol(name);
+            // Names starting with '_' are kept on purpose, like the entry point _start
+            if !symbol.global && !name.starts_with('_') && !self.used_symbols.contains(name) {
+                warnings.push(
+                    Located::at(Warning::UnusedLabel(line.line, name.clone()), line)
+                        .with_span(*span),
+                );
+            }
+        }
+
+        let labels: HashSet<u32> = self
+            .labels
+            .iter()
+            .map(|(name, ..)| self.symbol(name))
+            .filter(|symbol| symbol.section == Some(SectionKind::Code))
+            .map(|symbol| symbol.value)
+            .collect();
+        warnings.extend(check_code(&self.code, &labels));
+        warnings.sort_by_key(|w| w.line.as_ref().map(|(line, _)| *line));
+        warnings
+    }
+
+    // Where each instruction of the code section comes from, the labels and the aliases.
+    // Only meaningful once compile_object succeeded.
+    pub fn debug_info(&self) -> DebugInfo {
+        let lines = self
+            .code
+            .iter()
+            .map(|item| LineEntry {
+                address: item.offset,
+                location: SourceLocation {
+                    file: item.line.file.to_string(),
+                    line: item.line.line as u32 + 1,
+                    column: column_of(&item.line.text, item.span.start) as u32,
+                },
+                expansions: item
+                    .line
+                    .expansions
+                    .iter()
+                    .map(|expansion| MacroOrigin {
+                        name: expansion.macro_name.clone(),
+                        file: expansion.file.to_string(),
+                        line: expansion.line as u32 + 1,
+                    })
+                    .collect(),
+            })
+            .collect();
+        let symbols = self
+            .labels
+            .iter()
+            .map(|(name, ..)| self.symbol(name).clone())
+            .collect();
+        DebugInfo {
+            lines,
+            symbols,
+            aliases: self.aliases.clone(),
+        }
     }
 
-    pub fn compile_token(&mut self, token: TokenizedLine) -> Vec<u8> {
+    // Describes an item of the code section for the lints
+    fn code_item(&mut self, line: &SourceLine, item: &Item, offset: u32) -> CodeItem {
+        let (span, kind) = match item {
+            Item::Instruction(instruction) => {
+                let operands = instruction
+                    .operands
+                    .iter()
+                    .map(|operand| {
+                        let value = self.lint_operand(&operand.token, line.line, offset);
+                        (value, operand.span)
+                    })
+                    .collect();
+                (
+                    instruction_span(instruction),
+                    CodeItemKind::Instruction {
+                        opcode: instruction.opcode,
+                        operands,
+                    },
+                )
+            }
+            Item::Bytes(operands) => (
+                Span::new(
+                    operands.first().map_or(0, |o| o.span.start),
+                    operands.last().map_or(0, |o| o.span.end),
+                ),
+                CodeItemKind::Data,
+            ),
+            Item::Space(_) => (Span::new(0, line.text.len()), CodeItemKind::Data),
+        };
+        CodeItem {
+            line: line.clone(),
+            span,
+            offset,
+            size: Self::item_size(item),
+            kind,
+        }
+    }
+
+    fn lint_operand(&mut self, token: &TokenType, line: usize, offset: u32) -> LintOperand {
+        let expr = match token {
+            TokenType::Register(n) => return LintOperand::Register(*n),
+            TokenType::Num8(n) | TokenType::Ptr8(n) => return LintOperand::Constant(i64::from(*n)),
+            TokenType::NumExpr(expr) | TokenType::PtrExpr(expr) | TokenType::Expr(expr) => expr,
+            TokenType::Opcode(_) => return LintOperand::Unknown,
+        };
+        match self.evaluate(expr, line, SectionKind::Code, offset) {
+            Ok(Value::Constant(n)) => LintOperand::Constant(n),
+            Ok(Value::Address {
+                base: Some((SectionKind::Code, base)),
+                addend,
+                ..
+            }) => LintOperand::CodeAddress(base + addend),
+            _ => LintOperand::Unknown,
+        }
+    }
+
+    // Handles `.equ NAME, expr` and `.set NAME, expr`
+    fn define_constant(
+        &mut self,
+        redefinable: bool,
+        name: &str,
+        expr: &str,
+        line: &SourceLine,
+        section: SectionKind,
+        offset: u32,
+    ) -> Result<(), CompileError> {
+        if !is_identifier(name) || get_op(name.to_owned()).is_ok() {
+            return Err(CompileError::InvalidDirective(
+                line.line,
+                line.text.trim().to_owned(),
+            ));
+        }
+        if self.lexer.register(name).is_some() {
+            return Err(CompileError::ReservedName(line.line, name.to_owned()));
+        }
+        if self.symbol_indexes.contains_key(name) {
+            return Err(CompileError::DuplicateLabel(line.line, name.to_owned()));
+        }
+        match self.constants.get(name) {
+            Some(constant) if !(constant.redefinable && redefinable) => {
+                return Err(CompileError::ConstantRedefinition(
+                    line.line,
+                    name.to_owned(),
+                ))
+            }
+            _ => {}
+        }
+
+        let expr = Expr::parse(expr.trim_start_matches('#'))
+            .map_err(|e| CompileError::Expression(line.line, e))?;
+        let value = self.constant(&expr, line.line, section, offset)?;
+        self.constants
+            .insert(name.to_owned(), Constant { value, redefinable });
+        Ok(())
+    }
+
+    // Handles `.alias NAME, register`
+    fn define_alias(
+        &mut self,
+        name: &str,
+        register: &str,
+        line: &SourceLine,
+    ) -> Result<(), CompileError> {
+        let invalid = || CompileError::InvalidDirective(line.line, line.text.trim().to_owned());
+        if !is_identifier(name) || get_op(name.to_owned()).is_ok() {
+            return Err(invalid());
+        }
+        if parse_register(name).is_some() {
+            return Err(CompileError::ReservedName(line.line, name.to_owned()));
+        }
+        if self.symbol_indexes.contains_key(name) || self.constants.contains_key(name) {
+            return Err(CompileError::DuplicateLabel(line.line, name.to_owned()));
+        }
+        match self.lexer.register(register) {
+            Some(index) => {
+                self.lexer.add_alias(name, index);
+                // A redefined alias names its last register
+                self.aliases
+                    .retain(|alias| !alias.name.eq_ignore_ascii_case(name));
+                self.aliases.push(RegisterAlias {
+                    name: name.to_owned(),
+                    register: index,
+                });
+                Ok(())
+            }
+            None => Err(invalid()),
+        }
+    }
+
+    // A line giving no code, where the section is
+    fn list_consumed(
+        &mut self,
+        line: SourceLine,
+        section: SectionKind,
+        offsets: &HashMap<SectionKind, u32>,
+    ) {
+        self.listing.push(ListingLine {
+            line,
+            section,
+            offset: offsets.get(&section).copied().unwrap_or(0),
+            size: 0,
+            labeled: false,
+            references: vec![],
+        });
+    }
+
+    fn define_label(
+        &mut self,
+        name: &str,
+        line: usize,
+        section: SectionKind,
+        offset: u32,
+    ) -> Result<(), CompileError> {
+        if self.lexer.register(name).is_some() {
+            Err(CompileError::ReservedName(line, name.to_owned()))
+        } else if self.symbol_indexes.contains_key(name) || self.constants.contains_key(name) {
+            Err(CompileError::DuplicateLabel(line, name.to_owned()))
+        } else {
+            self.add_symbol(name, Some(section), offset);
+            Ok(())
+        }
+    }
+
+    // Reads the values of .byte
+    fn data_operands(
+        &self,
+        args: &[Argument],
+        line: usize,
+    ) -> Result<Vec<Operand>, (CompileError, Span)> {
+        let mut operands = Vec::<Operand>::with_capacity(args.len());
+        for arg in args {
+            match self.lexer.from_number_to_token(arg.text.clone(), line) {
+                Ok(TokenType::Register(_)) => {
+                    return Err((CompileError::RegisterInData(line), arg.span))
+                }
+                Ok(token) => operands.push(Operand {
+                    token,
+                    span: arg.span,
+                }),
+                Err(error) => {
+                    return Err((
+                        CompileError::Parse(ParseError {
+                            error,
+                            span: arg.span,
+                        }),
+                        arg.span,
+                    ))
+                }
+            }
+        }
+        Ok(operands)
+    }
+
+    // Replaces the constants known so far by their current value,
+    // so redefining a constant with .set only changes the lines after it
+    fn fold_constants(&self, item: &mut Item) {
+        let operands = match item {
+            Item::Instruction(instruction) => &mut instruction.operands,
+            Item::Bytes(operands) => operands,
+            Item::Space(_) => return,
+        };
+        let value_of = |name: &str| self.constants.get(name).map(|c| c.value);
+        for operand in operands.iter_mut() {
+            match &mut operand.token {
+                TokenType::NumExpr(expr) | TokenType::PtrExpr(expr) | TokenType::Expr(expr) => {
+                    expr.replace_symbols(&value_of)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Evaluates an expression found at the given offset of a section
+    fn evaluate(
+        &mut self,
+        expr: &Expr,
+        line: usize,
+        section: SectionKind,
+        offset: u32,
+    ) -> Result<Value, CompileError> {
+        self.used_symbols
+            .extend(expr.symbols().into_iter().map(|name| name.to_owned()));
+        // `.` is relative to a symbol standing for the start of the section
+        let here = if expr.uses_here() {
+            Value::Address {
+                symbol: self.section_symbol(section),
+                base: Some((section, 0)),
+                addend: i64::from(offset),
+            }
+        } else {
+            Value::Constant(0)
+        };
+        expr.evaluate(here, &|name| self.resolve(name))
+            .map_err(|e| match e {
+                ExprError::UndefinedSymbol(name) => CompileError::UndefinedLabel(line, name),
+                e => CompileError::Expression(line, e),
+            })
+    }
+
+    fn constant(
+        &mut self,
+        expr: &Expr,
+        line: usize,
+        section: SectionKind,
+        offset: u32,
+    ) -> Result<i64, CompileError> {
+        self.evaluate(expr, line, section, offset)?
+            .constant()
+            .map_err(|e| CompileError::Expression(line, e))
+    }
+
+    fn resolve(&self, name: &str) -> Option<Value> {
+        if let Some(constant) = self.constants.get(name) {
+            return Some(Value::Constant(constant.value));
+        }
+        let index = *self.symbol_indexes.get(name)?;
+        let symbol = &self.symbols[index];
+        Some(Value::Address {
+            symbol: index,
+            base: symbol.section.map(|kind| (kind, i64::from(symbol.value))),
+            addend: 0,
+        })
+    }
+
+    fn section_symbol(&mut self, section: SectionKind) -> usize {
+        if let Some(index) = self.section_symbols.get(&section) {
+            return *index;
+        }
+        let index = self.symbols.len();
+        self.symbols.push(Symbol {
+            name: format!(".{:?}", section).to_lowercase(),
+            section: Some(section),
+            value: 0,
+            global: false,
+        });
+        self.section_symbols.insert(section, index);
+        index
+    }
+
+    fn add_symbol(&mut self, name: &str, section: Option<SectionKind>, value: u32) {
+        self.symbol_indexes
+            .insert(name.to_owned(), self.symbols.len());
+        self.symbols.push(Symbol {
+            name: name.to_owned(),
+            section,
+            value,
+            global: false,
+        });
+    }
+
+    // Number of bytes the item takes once compiled
+    fn item_size(item: &Item) -> u32 {
+        match item {
+            Item::Instruction(instruction) => instruction_size(instruction.opcode) as u32,
+            Item::Bytes(operands) => operands.len() as u32,
+            Item::Space(size) => *size,
+        }
+    }
+
+    // Encodes the operands of an item starting at the given offset of the section,
+    // after `skip` bytes (the opcode). `sizes` gives the number of bytes of each operand.
+    // Addresses are left to 0, with a relocation for the linker to fill them.
+    fn compile_operands(
+        &mut self,
+        operands: Vec<Operand>,
+        sizes: &[usize],
+        line: usize,
+        section: SectionKind,
+        offset: u32,
+        skip: u32,
+    ) -> Result<(Vec<u8>, Vec<Relocation>), (CompileError, Span)> {
         let mut returned_vec = Vec::<u8>::new();
+        let mut relocations = Vec::<Relocation>::new();
+
+        for (operand, size) in operands.into_iter().zip(sizes) {
+            let at = offset + skip + returned_vec.len() as u32;
+            let span = operand.span;
+            let (bytes, relocation) = self
+                .compile_operand(operand.token, *size, line, section, offset, at)
+                .map_err(|e| (e, span))?;
+            returned_vec.extend(bytes);
+            relocations.extend(relocation);
+        }
+        Ok((returned_vec, relocations))
+    }
+
+    // Encodes an operand of `size` bytes, found in the item at `offset` and going at `at`
+    fn compile_operand(
+        &mut self,
+        token: TokenType,
+        size: usize,
+        line: usize,
+        section: SectionKind,
+        offset: u32,
+        at: u32,
+    ) -> Result<(Vec<u8>, Option<Relocation>), CompileError> {
+        let bits = size as u8 * 8;
+        let max = (1i64 << bits) - 1;
+        // The VM reads 16-bit immediates unsigned, they are also addresses. Bytes of
+        // data can be negative.
+        let signed_min = if size == 1 { -(1i64 << (bits - 1)) } else { 0 };
+        let (expr, min) = match token {
+            TokenType::Register(n) => return Ok((vec![n], None)),
+            TokenType::Num8(n) | TokenType::Ptr8(n) => (Expr::Num(i64::from(n)), 0),
+            TokenType::NumExpr(expr) | TokenType::Expr(expr) => (expr, signed_min),
+            TokenType::PtrExpr(expr) => (expr, 0),
+            TokenType::Opcode(_) => panic!("Should never happen: Unexpected opcode found"),
+        };
+
+        match self.evaluate(&expr, line, section, offset)? {
+            Value::Constant(n) if n < min || n > max => {
+                Err(CompileError::OutOfRange(line, n, bits))
+            }
+            // Big-endian, keeping the lowest bytes
+            Value::Constant(n) => Ok((n.to_be_bytes()[8 - size..].to_vec(), None)),
+            Value::Address { .. } if size != 2 => Err(CompileError::AddressTooWide(line)),
+            Value::Address { symbol, addend, .. } => {
+                let addend = i32::try_from(addend)
+                    .map_err(|_| CompileError::OutOfRange(line, addend, 32))?;
+                let relocation = Relocation {
+                    section,
+                    kind: RelocationKind::Abs16,
+                    offset: at,
+                    symbol: symbol as u32,
+                    addend,
+                };
+                Ok((vec![0, 0], Some(relocation)))
+            }
+        }
+    }
+}
 
-        returned_vec.push(token.opcode as u8);
-        // Should be changed later
-        for operand in token.operands {
-            match operand {
-                TokenType::Num8(n) => returned_vec.push(n),
-                TokenType::Ptr8(n) => returned_vec.push(n),
-                TokenType::Opcode(_) => panic!("Should never happen: Unexpected opcode found"),
+// Names of the symbols a statement uses, for the cross-references
+fn references(statement: &Statement) -> Vec<String> {
+    let mut exprs = Vec::<Expr>::new();
+    match statement {
+        Statement::Instruction(instruction) => {
+            for operand in &instruction.operands {
+                match &operand.token {
+                    TokenType::NumExpr(expr) | TokenType::PtrExpr(expr) | TokenType::Expr(expr) => {
+                        exprs.push(expr.clone())
+                    }
+                    _ => {}
+                }
             }
         }
-        returned_vec
+        Statement::Directive(directive) => {
+            let values = match directive.name.as_str() {
+                ".byte" | ".space" => &directive.args[..],
+                ".equ" | ".set" => directive.args.get(1..).unwrap_or(&[]),
+                _ => &[],
+            };
+            exprs.extend(
+                values.iter().filter_map(|arg| {
+                    Expr::parse(arg.text.trim_start_matches(&['#', '$'][..])).ok()
+                }),
+            );
+        }
+    }
+    let mut names: Vec<String> = exprs
+        .iter()
+        .flat_map(|expr| expr.symbols())
+        .map(|name| name.to_owned())
+        .collect();
+    names.dedup();
+    names
+}
+
+// Checks the number of operands, and that registers only go where the opcode expects one
+fn check_operands(instruction: &Instruction, line: usize) -> Result<(), (CompileError, Span)> {
+    let kinds = get_operands(instruction.opcode);
+    if instruction.operands.len() != kinds.len() {
+        let error = CompileError::WrongOperandCount {
+            line,
+            opcode: instruction.opcode,
+            expected: kinds.len(),
+            found: instruction.operands.len(),
+        };
+        return Err((error, instruction_span(instruction)));
+    }
+    for (i, (operand, kind)) in instruction.operands.iter().zip(kinds).enumerate() {
+        let is_register = matches!(operand.token, TokenType::Register(_));
+        if is_register != (*kind == OperandKind::Register) {
+            let error = CompileError::WrongOperandType {
+                line,
+                opcode: instruction.opcode,
+                operand: i,
+                expected: *kind,
+            };
+            return Err((error, operand.span));
+        }
+    }
+    Ok(())
+}
+
+// From the mnemonic to the last operand
+fn instruction_span(instruction: &Instruction) -> Span {
+    let end = instruction
+        .operands
+        .last()
+        .map_or(instruction.span.end, |operand| operand.span.end);
+    Span::new(instruction.span.start, end)
+}
+
+fn statement_span(statement: &Statement) -> Span {
+    match statement {
+        Statement::Instruction(instruction) => instruction_span(instruction),
+        Statement::Directive(directive) => {
+            let end = directive
+                .args
+                .last()
+                .map_or(directive.span.end, |arg| arg.span.end);
+            Span::new(directive.span.start, end)
+        }
+    }
+}
+
+fn section_from_name(name: &str) -> Option<SectionKind> {
+    match name {
+        "code" => Some(SectionKind::Code),
+        "rodata" => Some(SectionKind::Rodata),
+        "data" => Some(SectionKind::Data),
+        "bss" => Some(SectionKind::Bss),
+        _ => None,
+    }
+}
+
+fn section_buffer(object: &mut ObjectFile, kind: SectionKind) -> &mut Vec<u8> {
+    match kind {
+        SectionKind::Rodata => &mut object.rodata,
+        SectionKind::Data => &mut object.data,
+        _ => &mut object.code,
     }
 }
@@ -0,0 +1,348 @@
+use lamp_asm_parser::diagnostic::{closest, did_you_mean, ErrorCode, Severity};
+use lamp_asm_parser::source::{Located, SourceLine, Span};
+use lamp_common::op::{register_accesses, Opcode};
+use lamp_common::register::register_name;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+// Lints look at the compiled code for likely mistakes. They never stop the compilation,
+// unless they are denied.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Lint {
+    UnreachableCode,
+    UnusedLabel,
+    UninitializedRegister,
+    DivisionByZero,
+    JumpIntoInstruction,
+}
+
+pub const LINTS: &[Lint] = &[
+    Lint::UnreachableCode,
+    Lint::UnusedLabel,
+    Lint::UninitializedRegister,
+    Lint::DivisionByZero,
+    Lint::JumpIntoInstruction,
+];
+
+impl Lint {
+    // The name used on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::UnreachableCode => "unreachable-code",
+            Self::UnusedLabel => "unused-label",
+            Self::UninitializedRegister => "uninitialized-register",
+            Self::DivisionByZero => "division-by-zero",
+            Self::JumpIntoInstruction => "jump-into-instruction",
+        }
+    }
+}
+
+impl FromStr for Lint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match LINTS.iter().find(|lint| lint.name() == s) {
+            Some(lint) => Ok(*lint),
+            None => {
+                let names = LINTS.iter().map(|lint| lint.name());
+                Err(format!(
+                    "unknown lint \'{}\'{}",
+                    s,
+                    did_you_mean(closest(s, names))
+                        .map_or(String::new(), |help| format!(", {}", help))
+                ))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+// What to do with each lint. Every lint warns by default.
+pub struct LintLevels {
+    levels: HashMap<Lint, Level>,
+}
+
+impl LintLevels {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            levels: HashMap::new(),
+        }
+    }
+
+    pub fn set(&mut self, lint: Lint, level: Level) {
+        self.levels.insert(lint, level);
+    }
+
+    // Sets every lint at once, like --deny-warnings
+    pub fn set_all(&mut self, level: Level) {
+        for lint in LINTS {
+            self.set(*lint, level);
+        }
+    }
+
+    pub fn level(&self, lint: Lint) -> Level {
+        self.levels.get(&lint).copied().unwrap_or(Level::Warn)
+    }
+
+    // How a warning is reported, or None when it is allowed
+    pub fn severity(&self, lint: Lint) -> Option<Severity> {
+        match self.level(lint) {
+            Level::Allow => None,
+            Level::Warn => Some(Severity::Warning),
+            Level::Deny => Some(Severity::Error),
+        }
+    }
+}
+
+// Different types of warnings the lints can find
+#[derive(Debug, PartialEq)]
+pub enum Warning {
+    UnreachableCode(usize),
+    UnusedLabel(usize, String),
+    UninitializedRegister(usize, u8),
+    DivisionByZero(usize),
+    // Offset of the target in the code section
+    JumpIntoInstruction(usize, u32),
+}
+
+impl Warning {
+    pub fn lint(&self) -> Lint {
+        match self {
+            Self::UnreachableCode(_) => Lint::UnreachableCode,
+            Self::UnusedLabel(..) => Lint::UnusedLabel,
+            Self::UninitializedRegister(..) => Lint::UninitializedRegister,
+            Self::DivisionByZero(_) => Lint::DivisionByZero,
+            Self::JumpIntoInstruction(..) => Lint::JumpIntoInstruction,
+        }
+    }
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            /* at + 1 because at is an array/vec index */
+            Self::UnreachableCode(at) => write!(f, "Unreachable instruction at line {}", at + 1),
+            Self::UnusedLabel(at, name) => {
+                write!(f, "Label \'{}\' is never used at line {}", name, at + 1)
+            }
+            Self::UninitializedRegister(at, register) => write!(
+                f,
+                "Register {} is read at line {} before anything is written to it",
+                register_name(*register),
+                at + 1
+            ),
+            Self::DivisionByZero(at) => write!(f, "Division by zero at line {}", at + 1),
+            Self::JumpIntoInstruction(at, target) => write!(
+                f,
+                "Jump at line {} goes to offset {:#x}, in the middle of an instruction",
+                at + 1,
+                target
+            ),
+        }
+    }
+}
+
+impl ErrorCode for Warning {
+    fn code(&self) -> &'static str {
+        match self {
+            Self::UnreachableCode(_) => "W0301",
+            Self::UnusedLabel(..) => "W0302",
+            Self::UninitializedRegister(..) => "W0303",
+            Self::DivisionByZero(_) => "W0304",
+            Self::JumpIntoInstruction(..) => "W0305",
+        }
+    }
+
+    fn help(&self) -> Option<String> {
+        match self {
+            Self::UnreachableCode(_) => {
+                Some("add a label if something jumps here, or remove the code".to_owned())
+            }
+            Self::UnusedLabel(..) => {
+                Some("remove it, or start its name with \'_\' to keep it".to_owned())
+            }
+            Self::UninitializedRegister(..) => {
+                Some("registers start at 0, LOAD a value first if that isn't wanted".to_owned())
+            }
+            _ => None,
+        }
+    }
+}
+
+// What the lints need to know about an operand
+#[derive(Debug, Copy, Clone)]
+pub enum LintOperand {
+    Register(u8),
+    Constant(i64),
+    // An offset in the code section of this file
+    CodeAddress(i64),
+    // Anything else: other sections, extern symbols...
+    Unknown,
+}
+
+#[derive(Debug)]
+pub enum CodeItemKind {
+    Instruction {
+        opcode: Opcode,
+        operands: Vec<(LintOperand, Span)>,
+    },
+    // .byte or .space in the code section
+    Data,
+}
+
+// Something compiled in the code section, with where it comes from
+#[derive(Debug)]
+pub struct CodeItem {
+    pub line: SourceLine,
+    pub span: Span,
+    pub offset: u32,
+    pub size: u32,
+    pub kind: CodeItemKind,
+}
+
+// What a register is known to hold at some point of the code
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Known {
+    Constant(i32),
+    CodeAddress(i64),
+}
+
+// Looks for unreachable code, uninitialized registers, divisions by zero and jumps
+// into instructions. `labels` are the offsets of the code labels.
+pub fn check_code(items: &[CodeItem], labels: &HashSet<u32>) -> Vec<Located<Warning>> {
+    let mut warnings = Vec::<Located<Warning>>::new();
+
+    // Places something can jump to: labels, and code addresses used as operands
+    let mut targets = labels.clone();
+    for item in items {
+        if let CodeItemKind::Instruction { operands, .. } = &item.kind {
+            for (operand, _) in operands {
+                if let LintOperand::CodeAddress(offset) = operand {
+                    targets.extend(u32::try_from(*offset).ok());
+                }
+            }
+        }
+    }
+
+    let mut reachable = true;
+    let mut written = [false; 32];
+    // r0 is the zero register, reading it is what it is for
+    written[0] = true;
+    let mut known: [Option<Known>; 32] = [None; 32];
+
+    for item in items {
+        if targets.contains(&item.offset) {
+            // Several paths meet here, nothing is known anymore
+            reachable = true;
+            known = [None; 32];
+        }
+        let (opcode, operands) = match &item.kind {
+            CodeItemKind::Instruction { opcode, operands } => (*opcode, operands),
+            CodeItemKind::Data => continue,
+        };
+        let warn = |warning: Warning, span: Span| Located::at(warning, &item.line).with_span(span);
+
+        if !reachable {
+            warnings.push(warn(Warning::UnreachableCode(item.line.line), item.span));
+            // Only the first instruction of unreachable code is reported
+            reachable = true;
+        }
+
+        let register = |i: usize| match operands.get(i) {
+            Some((LintOperand::Register(n), span)) => Some((*n as usize % 32, *span)),
+            _ => None,
+        };
+        let (reads, writes) = register_accesses(opcode);
+        for (n, span) in reads.iter().filter_map(|i| register(*i)) {
+            if !written[n] {
+                warnings.push(warn(
+                    Warning::UninitializedRegister(item.line.line, n as u8),
+                    span,
+                ));
+                // Once is enough
+                written[n] = true;
+            }
+        }
+
+        match opcode {
+            Opcode::MOD => {
+                if let Some((n, span)) = register(1) {
+                    if known[n] == Some(Known::Constant(0)) {
+                        warnings.push(warn(Warning::DivisionByZero(item.line.line), span));
+                    }
+                }
+            }
+            Opcode::JMP => {
+                if let Some((n, span)) = register(0) {
+                    if let Some(Known::CodeAddress(target)) = known[n] {
+                        let inside = items.iter().any(|other| {
+                            matches!(other.kind, CodeItemKind::Instruction { .. })
+                                && i64::from(other.offset) < target
+                                && target < i64::from(other.offset + other.size)
+                        });
+                        if inside {
+                            warnings.push(warn(
+                                Warning::JumpIntoInstruction(item.line.line, target as u32),
+                                span,
+                            ));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        let value = |i: usize| register(i).and_then(|(n, _)| known[n]);
+        let result = match (opcode, operands.get(1).map(|(operand, _)| *operand)) {
+            // LOAD's immediate takes 16 bits
+            (Opcode::LOAD, Some(LintOperand::Constant(n))) => {
+                Some(Known::Constant(i32::from(n as u16)))
+            }
+            (Opcode::LOAD, Some(LintOperand::CodeAddress(offset))) => {
+                Some(Known::CodeAddress(offset))
+            }
+            (Opcode::INC, _) | (Opcode::DEC, _) => {
+                let step = if opcode == Opcode::INC { 1 } else { -1 };
+                match value(0) {
+                    Some(Known::Constant(n)) => Some(Known::Constant(n.wrapping_add(step))),
+                    Some(Known::CodeAddress(offset)) => {
+                        Some(Known::CodeAddress(offset + i64::from(step)))
+                    }
+                    None => None,
+                }
+            }
+            (Opcode::ADD, _) | (Opcode::SUB, _) | (Opcode::MUL, _) | (Opcode::MOD, _) => {
+                match (value(0), value(1)) {
+                    (Some(Known::Constant(a)), Some(Known::Constant(b))) => match opcode {
+                        Opcode::ADD => Some(a.wrapping_add(b)),
+                        Opcode::SUB => Some(a.wrapping_sub(b)),
+                        Opcode::MUL => Some(a.wrapping_mul(b)),
+                        _ => a.checked_div(b),
+                    }
+                    .map(Known::Constant),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        for (n, _) in writes.iter().filter_map(|i| register(*i)) {
+            written[n] = true;
+            known[n] = result;
+        }
+
+        // HLT doesn't stop the VM, execution goes on after it
+        if opcode == Opcode::JMP {
+            reachable = false;
+        }
+    }
+    warnings
+}
@@ -0,0 +1,249 @@
+use crate::compiler::Compiler;
+use lamp_asm_parser::source::SourceLine;
+use lamp_common::container::{Container, FileType, SectionKind};
+use lamp_ld::Placement;
+use std::collections::HashMap;
+
+// A listing shows, for each source line, its address and the bytes it was compiled to,
+// followed by the symbol table and the cross-references of the labels.
+
+// A source line once preprocessed, with where it went
+#[derive(Debug)]
+pub struct ListingLine {
+    pub line: SourceLine,
+    pub section: SectionKind,
+    // Offset in the section where the line's label or bytes are
+    pub offset: u32,
+    pub size: u32,
+    pub labeled: bool,
+    // Symbols the line uses
+    pub references: Vec<String>,
+}
+
+// Bytes shown per row, and rows shown for a single line before the rest is elided
+const ROW_BYTES: usize = 6;
+const MAX_ROWS: usize = 4;
+
+const ADDRESS_WIDTH: usize = 11;
+const BYTES_WIDTH: usize = ROW_BYTES * 3;
+
+fn section_name(kind: SectionKind) -> String {
+    format!("{:?}", kind).to_lowercase()
+}
+
+fn address(section: SectionKind, address: u32) -> String {
+    format!("{:>6}:{:04X}", section_name(section), address)
+}
+
+// The bytes the container holds for a part of a section
+fn bytes(container: &Container, section: SectionKind, address: u32, size: u32) -> &[u8] {
+    container
+        .section(section)
+        .and_then(|s| {
+            let start = address.checked_sub(s.address)? as usize;
+            s.data.get(start..start + size as usize)
+        })
+        .unwrap_or(&[])
+}
+
+// Adds a row without its trailing spaces
+fn push_row(out: &mut String, row: String) {
+    out.push_str(row.trim_end());
+    out.push('\n');
+}
+
+fn location(line: &SourceLine) -> String {
+    format!("{}:{}", line.file, line.line + 1)
+}
+
+// Writes the listing of one source file. `placement` tells where its sections landed
+// in `container`, which is either the linked executable or the file's own object.
+pub fn render(
+    name: &str,
+    compiler: &Compiler,
+    placement: &Placement,
+    container: &Container,
+) -> String {
+    let mut out = format!("Listing of {}\n", name);
+    if container.file_type == FileType::Object {
+        out += "Relocatable object: addresses are offsets in the sections, and addresses \
+                filled by the linker are shown as 00 00\n";
+    }
+    out += &format!(
+        "\n{:<aw$}  {:<bw$}  {:>5}  Source\n",
+        "Address",
+        "Bytes",
+        "Line",
+        aw = ADDRESS_WIDTH,
+        bw = BYTES_WIDTH
+    );
+
+    let mut file = name.to_owned();
+    for listed in compiler.listing() {
+        if *listed.line.file != *file {
+            file = listed.line.file.to_string();
+            out += &format!(
+                "{:aw$}  {:bw$}  {:>5}  ; {}\n",
+                "",
+                "",
+                "",
+                file,
+                aw = ADDRESS_WIDTH,
+                bw = BYTES_WIDTH
+            );
+        }
+
+        let start = placement.base(listed.section) + listed.offset;
+        let shown_address = if listed.size > 0 || listed.labeled {
+            address(listed.section, start)
+        } else {
+            String::new()
+        };
+        // Lines coming from a macro are marked with a '+'
+        let number = format!(
+            "{}{}",
+            listed.line.line + 1,
+            if listed.line.expansions.is_empty() {
+                ' '
+            } else {
+                '+'
+            }
+        );
+        let text = listed.line.text.trim_end();
+
+        let content = bytes(container, listed.section, start, listed.size);
+        let rows: Vec<String> = if listed.section == SectionKind::Bss && listed.size > 0 {
+            vec![format!("({} bytes)", listed.size)]
+        } else {
+            content
+                .chunks(ROW_BYTES)
+                .map(|row| {
+                    row.iter()
+                        .map(|b| format!("{:02X}", b))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect()
+        };
+
+        push_row(
+            &mut out,
+            format!(
+                "{:<aw$}  {:<bw$}  {:>6} {}",
+                shown_address,
+                rows.first().map_or("", |row| row.as_str()),
+                number,
+                text,
+                aw = ADDRESS_WIDTH,
+                bw = BYTES_WIDTH
+            ),
+        );
+        for (i, row) in rows.iter().enumerate().skip(1) {
+            if i == MAX_ROWS {
+                out += &format!(
+                    "{:<aw$}  ... ({} bytes in total)\n",
+                    "",
+                    listed.size,
+                    aw = ADDRESS_WIDTH
+                );
+                break;
+            }
+            let row_address = address(listed.section, start + (i * ROW_BYTES) as u32);
+            out += &format!("{:<aw$}  {}\n", row_address, row, aw = ADDRESS_WIDTH);
+        }
+    }
+
+    out += &symbol_table(compiler, placement);
+    out += &cross_references(compiler);
+    out
+}
+
+fn symbol_table(compiler: &Compiler, placement: &Placement) -> String {
+    let labels = compiler.labels();
+    let externs: Vec<&str> = compiler
+        .symbols()
+        .iter()
+        .filter(|symbol| !symbol.is_defined())
+        .map(|symbol| symbol.name.as_str())
+        .collect();
+    let width = labels
+        .iter()
+        .map(|(name, ..)| name.len())
+        .chain(externs.iter().map(|name| name.len()))
+        .max()
+        .unwrap_or(0)
+        .max(16);
+
+    let mut out = format!(
+        "\nSymbols\n\n{:<w$}  {:<aw$}  {:<6}  Defined at\n",
+        "Name",
+        "Address",
+        "Scope",
+        w = width,
+        aw = ADDRESS_WIDTH
+    );
+    for (name, line, _) in labels {
+        let symbol = compiler.symbol(name);
+        let section = symbol.section.unwrap_or(SectionKind::Code);
+        out += &format!(
+            "{:<w$}  {:<aw$}  {:<6}  {}\n",
+            name,
+            address(section, placement.base(section) + symbol.value),
+            if symbol.global { "global" } else { "local" },
+            location(line),
+            w = width,
+            aw = ADDRESS_WIDTH
+        );
+    }
+    for name in externs {
+        out += &format!(
+            "{:<w$}  {:<aw$}  extern\n",
+            name,
+            "",
+            w = width,
+            aw = ADDRESS_WIDTH
+        );
+    }
+    out
+}
+
+// Where a line comes from in the source: the macro call for lines of a macro
+fn origin(line: &SourceLine) -> String {
+    match line.expansions.first() {
+        Some(expansion) => format!("{}:{}", expansion.file, expansion.line + 1),
+        None => location(line),
+    }
+}
+
+fn cross_references(compiler: &Compiler) -> String {
+    // Where each symbol is used, in source order
+    let mut uses = HashMap::<&str, Vec<String>>::new();
+    for listed in compiler.listing() {
+        for name in &listed.references {
+            let locations = uses.entry(name.as_str()).or_default();
+            let at = origin(&listed.line);
+            if locations.last() != Some(&at) {
+                locations.push(at);
+            }
+        }
+    }
+
+    let mut names: Vec<&str> = compiler
+        .symbols()
+        .iter()
+        .filter(|symbol| compiler.is_label(&symbol.name))
+        .map(|symbol| symbol.name.as_str())
+        .collect();
+    names.sort_unstable();
+    let width = names.iter().map(|name| name.len()).max().unwrap_or(0);
+
+    let mut out = String::from("\nCross-references\n\n");
+    for name in names {
+        let used = match uses.get(name) {
+            Some(locations) => format!("used at {}", locations.join(", ")),
+            None => "never used".to_owned(),
+        };
+        out += &format!("{:<w$}  {}\n", name, used, w = width);
+    }
+    out
+}
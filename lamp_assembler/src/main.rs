@@ -1,67 +1,297 @@
 use crate::compiler::Compiler;
+use crate::lint::{Level, Lint, LintLevels};
+use lamp_asm_parser::diagnostic::{Diagnostic, Severity};
+use lamp_common::object::ObjectFile;
+use lamp_ld::{LinkInput, Linker, Placement};
 use std::fs::File;
 use std::io::BufWriter;
 use std::io::Read;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use structopt::StructOpt;
 
 mod compiler;
+mod lint;
+mod listing;
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum MessageFormat {
+    Human,
+    // One JSON object per line, on stdout
+    Json,
+}
+
+impl FromStr for MessageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown message format \'{}\'", s)),
+        }
+    }
+}
 
 #[derive(StructOpt, Debug)]
 #[structopt(name = "lamp_asm")]
 struct LampAsm {
-    #[structopt(short)]
-    source: PathBuf,
-    #[structopt(short)]
-    output: PathBuf,
+    /// Source files, linked together when there are several
+    #[structopt(short, required = true, number_of_values = 1)]
+    source: Vec<PathBuf>,
+    /// Where the executable or the object is written
+    #[structopt(short, required_unless = "check")]
+    output: Option<PathBuf>,
+    /// Writes a relocatable object to be linked with lamp_ld, instead of an executable
+    #[structopt(short = "c", long)]
+    object: bool,
+    /// How errors are printed: human or json
+    #[structopt(long, default_value = "human", possible_values = &["human", "json"])]
+    message_format: MessageFormat,
+    /// Writes a listing: the address and bytes of each line, the symbols and where they are used
+    #[structopt(short, long)]
+    listing: Option<PathBuf>,
+    /// Adds debug info: the source line of each instruction and the labels, used by the debugger
+    #[structopt(short = "g", long)]
+    debug_info: bool,
+    /// Only looks for errors and warnings, without writing anything
+    #[structopt(long)]
+    check: bool,
+    /// Turns every warning into an error
+    #[structopt(long)]
+    deny_warnings: bool,
+    /// Lints to ignore
+    #[structopt(short = "A", long, number_of_values = 1)]
+    allow: Vec<Lint>,
+    /// Lints to report as warnings, even with --deny-warnings
+    #[structopt(short = "W", long, number_of_values = 1)]
+    warn: Vec<Lint>,
+    /// Lints to report as errors
+    #[structopt(short = "D", long, number_of_values = 1)]
+    deny: Vec<Lint>,
+}
+
+impl LampAsm {
+    fn lint_levels(&self) -> LintLevels {
+        let mut levels = LintLevels::new();
+        if self.deny_warnings {
+            levels.set_all(Level::Deny);
+        }
+        for (lints, level) in &[
+            (&self.warn, Level::Warn),
+            (&self.allow, Level::Allow),
+            (&self.deny, Level::Deny),
+        ] {
+            for lint in lints.iter() {
+                levels.set(*lint, *level);
+            }
+        }
+        levels
+    }
+}
+
+// Prints the progress and the diagnostics in the chosen format
+struct Reporter {
+    format: MessageFormat,
+}
+
+impl Reporter {
+    fn info(&self, message: &str) {
+        if self.format == MessageFormat::Human {
+            println!("{}", message);
+        }
+    }
+
+    fn report(&self, diagnostic: &Diagnostic) {
+        match self.format {
+            MessageFormat::Human => eprintln!("{}", diagnostic.render()),
+            MessageFormat::Json => println!("{}", diagnostic.to_json()),
+        }
+    }
+
+    fn io_error(&self, code: &'static str, message: String, path: &Path) {
+        let mut diagnostic = Diagnostic::new(Severity::Error, code, message);
+        diagnostic.file = path.display().to_string();
+        self.report(&diagnostic);
+    }
 }
 
 fn main() {
     let args = LampAsm::from_args();
+    let reporter = Reporter {
+        format: args.message_format,
+    };
+    let levels = args.lint_levels();
+
+    if args.object && args.source.len() > 1 {
+        eprintln!("Error: only one source file can be compiled to an object at a time.");
+        std::process::exit(2);
+    }
 
-    let mut file = File::open(&args.source);
-
-    match file {
-        Ok(ref mut file) => {
-            let mut buffer = String::new();
-            match file.read_to_string(&mut buffer) {
-                Ok(k) => {
-                    println!("Source file size is {} bytes.", k);
-                    let lines = buffer.split('\n').map(|s| s.to_string()).collect();
-
-                    let mut compiler = Compiler::new(lines);
-                    match compiler.compile() {
-                        Ok(bin_size) => {
-                            println!("Output's size is {} bytes.", bin_size);
-                            match write_output(&args.output, compiler.result_buffer) {
-                                Ok(_) => println!("Compilation successfully ended."),
-                                Err(e) => println!(
-                                    "Compilation failed: Cannot write the output. Error: {:?}",
-                                    e
-                                ),
+    let mut inputs = Vec::<LinkInput>::new();
+    let mut compilers = Vec::<Compiler>::new();
+    for source in &args.source {
+        match compile_file(source, &reporter, &levels) {
+            Some((mut object, compiler)) => {
+                if args.debug_info {
+                    object.debug = Some(compiler.debug_info());
+                }
+                inputs.push(LinkInput {
+                    name: source.display().to_string(),
+                    object,
+                });
+                compilers.push(compiler);
+            }
+            None => std::process::exit(1),
+        }
+    }
+
+    let container = if args.object {
+        inputs[0].object.to_container()
+    } else {
+        match Linker::new().link(&inputs) {
+            Ok(container) => container,
+            Err(errors) => {
+                reporter.info(&format!(
+                    "Can\'t link the program: {} problems found.",
+                    errors.len()
+                ));
+                for error in errors {
+                    reporter.report(&Diagnostic::new(
+                        Severity::Error,
+                        error.code(),
+                        error.to_string(),
+                    ));
+                }
+                std::process::exit(1);
+            }
+        }
+    };
+
+    if args.check {
+        return;
+    }
+
+    if let Some(path) = &args.listing {
+        let placements = if args.object {
+            vec![Placement::default()]
+        } else {
+            Linker::layout(&inputs)
+        };
+        let listing: Vec<String> = inputs
+            .iter()
+            .zip(&compilers)
+            .zip(&placements)
+            .map(|((input, compiler), placement)| {
+                listing::render(&input.name, compiler, placement, &container)
+            })
+            .collect();
+        if let Err(e) = std::fs::write(path, listing.join("\n")) {
+            reporter.io_error(
+                "E0002",
+                format!("Cannot write the listing. Error: {}", e),
+                path,
+            );
+            std::process::exit(1);
+        }
+    }
+
+    let path = match &args.output {
+        Some(path) => path,
+        None => return,
+    };
+    let output = container.to_bytes();
+    reporter.info(&format!("Output's size is {} bytes.", output.len()));
+    match write_output(path, output) {
+        Ok(_) => reporter.info("Compilation successfully ended."),
+        Err(e) => {
+            reporter.io_error(
+                "E0002",
+                format!("Compilation failed: Cannot write the output. Error: {}", e),
+                path,
+            );
+            std::process::exit(1);
+        }
+    }
+
+    fn compile_file(
+        path: &Path,
+        reporter: &Reporter,
+        levels: &LintLevels,
+    ) -> Option<(ObjectFile, Compiler)> {
+        let mut file = File::open(path);
+
+        match file {
+            Ok(ref mut file) => {
+                let mut buffer = String::new();
+                match file.read_to_string(&mut buffer) {
+                    Ok(k) => {
+                        reporter.info(&format!("Source file size is {} bytes.", k));
+                        let lines = buffer.lines().map(|s| s.to_string()).collect();
+
+                        let mut compiler = Compiler::new(lines, path);
+                        match compiler.compile_object() {
+                            Ok(object) => {
+                                if report_warnings(&compiler, reporter, levels) {
+                                    Some((object, compiler))
+                                } else {
+                                    None
+                                }
                             }
-                        }
-                        Err(errors) => {
-                            eprintln!("Can\'t compile the source file: {} problems found. But don\'t worry ! Here are the listed mistakes: ", errors.len());
-                            for error in errors {
-                                eprintln!("{}", error);
+                            Err(errors) => {
+                                reporter.info(&format!("Can\'t compile the source file: {} problems found. But don\'t worry ! Here are the listed mistakes: ", errors.len()));
+                                for error in errors {
+                                    reporter.report(&Diagnostic::error(&error));
+                                }
+                                None
                             }
                         }
                     }
+                    Err(e) => {
+                        reporter.io_error(
+                            "E0001",
+                            format!("Error: cannot read the file.\nDetails: {}", e),
+                            path,
+                        );
+                        None
+                    }
                 }
-                Err(e) => {
-                    eprintln!("Error: cannot read the file.\nDetails: {:?}", e);
-                }
             }
+            Err(e) => {
+                reporter.io_error("E0001", format!("Error: cannot open the file: {}", e), path);
+                None
+            }
+        }
+    }
+
+    // Reports what the lints found. Returns false when a denied lint was found.
+    fn report_warnings(compiler: &Compiler, reporter: &Reporter, levels: &LintLevels) -> bool {
+        let mut denied = false;
+        for warning in compiler.lint() {
+            let name = warning.error.lint().name();
+            let severity = match levels.severity(warning.error.lint()) {
+                Some(severity) => severity,
+                None => continue,
+            };
+            let note = if severity == Severity::Error {
+                denied = true;
+                format!("\'{}\' is denied", name)
+            } else {
+                format!("found by \'{0}\', use \'-A {0}\' to allow it", name)
+            };
+            reporter.report(&Diagnostic::from_located(severity, &warning).with_note(note));
         }
-        Err(e) => eprintln!("Error: cannot open {:?}: {:?}", args.source, e),
+        !denied
     }
 
     fn write_output(path: &PathBuf, bin: Vec<u8>) -> Result<usize, std::io::Error> {
         let buff = bin.as_slice();
         let file = File::create(path)?;
         let mut writer = BufWriter::new(file);
-        writer.write(&buff)
+        writer.write_all(buff)?;
+        Ok(buff.len())
     }
 }
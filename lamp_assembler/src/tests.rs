@@ -0,0 +1,210 @@
+use crate::compiler::{CompileError, Compiler};
+use crate::lint::{Level, Lint, LintLevels, Warning};
+use crate::listing;
+use lamp_asm_parser::diagnostic::Severity;
+use lamp_common::register::RegisterAlias;
+use lamp_ld::Placement;
+use std::path::Path;
+
+fn lint(source: &str) -> Vec<Warning> {
+    let lines = source.lines().map(|line| line.to_string()).collect();
+    let mut compiler = Compiler::new(lines, Path::new("test.asm"));
+    assert!(compiler.compile_object().is_ok());
+    compiler.lint().into_iter().map(|w| w.error).collect()
+}
+
+fn compile_errors(source: &str) -> Vec<CompileError> {
+    let lines = source.lines().map(|line| line.to_string()).collect();
+    let mut compiler = Compiler::new(lines, Path::new("test.asm"));
+    match compiler.compile_object() {
+        Ok(_) => vec![],
+        Err(errors) => errors.into_iter().map(|e| e.error).collect(),
+    }
+}
+
+#[test]
+fn test_unreachable_code() {
+    let warnings = lint("_start:\nLOAD r1, #1\nJMP r1\nNOP\n_next:\nLOAD r2, #1\nJMP r2\nNOP");
+    assert_eq!(
+        warnings,
+        vec![Warning::UnreachableCode(3), Warning::UnreachableCode(7)]
+    );
+    // HLT is a no-op, what follows it still runs
+    assert!(lint("_start:\nHLT\nLOAD r1, #1\nINC r1").is_empty());
+
+    // A code address used as an operand can be jumped to
+    assert!(lint("LOAD r1, . + 6\nJMP r1\nHLT").is_empty());
+}
+
+#[test]
+fn test_unused_labels() {
+    let warnings = lint(
+        ".global exported\nexported:\nunused:\nused:\n_kept:\nLOAD r1, used\n.section data\ndata: .byte 1",
+    );
+    assert_eq!(
+        warnings,
+        vec![
+            Warning::UnusedLabel(2, "unused".to_owned()),
+            Warning::UnusedLabel(7, "data".to_owned()),
+        ]
+    );
+}
+
+#[test]
+fn test_registers() {
+    let warnings = lint("ADD r1, r0, r2\nADD r1, r2, r3\nINC r4\nLOAD r5, #0\nMOD r2, r5, r6");
+    assert_eq!(
+        warnings,
+        vec![
+            Warning::UninitializedRegister(0, 1),
+            Warning::UninitializedRegister(2, 4),
+            Warning::DivisionByZero(4),
+        ]
+    );
+
+    // Nothing is known after a label, something else may jump there
+    assert!(lint("LOAD r5, #0\n_loop:\nMOD r5, r5, r6").is_empty());
+    // The value is tracked through arithmetic
+    assert_eq!(
+        lint("LOAD r1, #1\nDEC r1\nMOD r1, r1, r2"),
+        vec![Warning::DivisionByZero(2)]
+    );
+}
+
+#[test]
+fn test_aliases_debug_info() {
+    let source = ".alias counter, r5\n.alias tmp, r1\nINC counter\n.alias tmp, r2\nINC tmp";
+    let lines = source.lines().map(|line| line.to_string()).collect();
+    let mut compiler = Compiler::new(lines, Path::new("test.asm"));
+    assert!(compiler.compile_object().is_ok());
+    // A redefined alias keeps its last register
+    assert_eq!(
+        compiler.debug_info().aliases,
+        vec![
+            RegisterAlias {
+                name: "counter".to_string(),
+                register: 5,
+            },
+            RegisterAlias {
+                name: "tmp".to_string(),
+                register: 2,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_jump_into_instruction() {
+    let warnings = lint("_start:\nLOAD r1, target + 1\nJMP r1\ntarget:\nLOAD r2, #3\nHLT");
+    assert_eq!(warnings, vec![Warning::JumpIntoInstruction(2, 7)]);
+    assert!(lint("_start:\nLOAD r1, target\nJMP r1\ntarget:\nHLT").is_empty());
+}
+
+#[test]
+fn test_lint_levels() {
+    let mut levels = LintLevels::new();
+    assert_eq!(levels.severity(Lint::UnusedLabel), Some(Severity::Warning));
+    levels.set_all(Level::Deny);
+    levels.set(Lint::UnusedLabel, Level::Allow);
+    assert_eq!(levels.severity(Lint::UnusedLabel), None);
+    assert_eq!(levels.severity(Lint::DivisionByZero), Some(Severity::Error));
+
+    assert_eq!("unused-label".parse::<Lint>(), Ok(Lint::UnusedLabel));
+    assert!("unused-labl"
+        .parse::<Lint>()
+        .unwrap_err()
+        .contains("did you mean 'unused-label'?"));
+}
+
+#[test]
+fn test_listing() {
+    let source = "_start:\n    LOAD r1, end\n    JMP r1\nend: HLT\n.section data\nvalues: .byte 1, 2, 3, 4, 5, 6, 7";
+    let lines = source.lines().map(|line| line.to_string()).collect();
+    let mut compiler = Compiler::new(lines, Path::new("test.asm"));
+    let object = compiler.compile_object().unwrap();
+    let text = listing::render(
+        "test.asm",
+        &compiler,
+        &Placement::default(),
+        &object.to_container(),
+    );
+    let lines: Vec<&str> = text.lines().collect();
+
+    assert!(lines.contains(&"  code:0000                          1  _start:"));
+    assert!(lines.contains(&"  code:0000  0F 01 00 00             2      LOAD r1, end"));
+    assert!(lines.contains(&"  code:0006  0D                      4  end: HLT"));
+    assert!(lines
+        .contains(&"  data:0000  01 02 03 04 05 06       6  values: .byte 1, 2, 3, 4, 5, 6, 7"));
+    assert!(lines.contains(&"  data:0006  07"));
+    assert!(lines.contains(&"end                 code:0006  local   test.asm:4"));
+    assert!(lines.contains(&"end     used at test.asm:2"));
+    assert!(lines.contains(&"values  never used"));
+}
+
+#[test]
+fn test_listing_macros() {
+    let source = ".macro bump reg\n    INC \\reg\n.endm\n_start:\n    bump r1\n    HLT";
+    let lines = source.lines().map(|line| line.to_string()).collect();
+    let mut compiler = Compiler::new(lines, Path::new("test.asm"));
+    let object = compiler.compile_object().unwrap();
+    let text = listing::render(
+        "test.asm",
+        &compiler,
+        &Placement::default(),
+        &object.to_container(),
+    );
+    let lines: Vec<&str> = text.lines().skip(4).take(6).collect();
+
+    // Every line of the source, those giving no code without an address
+    assert_eq!(
+        lines,
+        vec![
+            "                                     1  .macro bump reg",
+            "                                     2      INC \\reg",
+            "                                     3  .endm",
+            "  code:0000                          4  _start:",
+            "                                     5      bump r1",
+            "  code:0000  05 01                   2+     INC r1",
+        ]
+    );
+}
+
+#[test]
+fn test_section_sizes() {
+    // The offset after the .space doesn't fit in 32 bits
+    let errors = compile_errors(".section data\n.space 0xFFFFFFFF\n.byte 1");
+    assert!(matches!(
+        errors.as_slice(),
+        [CompileError::OutOfRange(1, 0xFFFFFFFF, 24)]
+    ));
+    let errors = compile_errors(".section bss\n.space 0x800000\n.space 0x800000");
+    assert!(matches!(
+        errors.as_slice(),
+        [CompileError::OutOfRange(2, 0x1000000, 24)]
+    ));
+    assert!(compile_errors(".section bss\n.space 0x800000\n.space 0x7FFFFF").is_empty());
+}
+
+#[test]
+fn test_unsigned_immediates() {
+    // The VM doesn't sign-extend LOAD's value, -1 would load 65535
+    let errors = compile_errors("LOAD r1, #-1");
+    assert!(matches!(
+        errors.as_slice(),
+        [CompileError::OutOfRange(0, -1, 16)]
+    ));
+    assert!(compile_errors("LOAD r1, #65535").is_empty());
+    // Bytes of data can still be negative
+    assert!(compile_errors(".section data\n.byte -1").is_empty());
+}
+
+#[test]
+fn test_labeled_macro_call() {
+    let source = ".macro inc2 reg\nINC \\reg\nINC \\reg\n.endm\nNOP\nstart: inc2 r1";
+    let lines = source.lines().map(|line| line.to_string()).collect();
+    let mut compiler = Compiler::new(lines, Path::new("test.asm"));
+    let object = compiler.compile_object().unwrap();
+    assert_eq!(object.code, vec![14, 5, 1, 5, 1]);
+    let start = object.symbols.iter().find(|s| s.name == "start").unwrap();
+    assert_eq!(start.value, 1);
+}
@@ -0,0 +1,318 @@
+use crate::constants::LAMP_BIN_HEADER;
+
+// Layout of a lamp binary (every integer is big-endian, like the operands):
+//
+// Header (24 bytes)
+//   0..4   magic number (LAMP_BIN_HEADER)
+//   4..6   format version
+//   6      file type (see FileType)
+//   7      reserved, must be 0
+//   8..12  flags (word size and ISA extensions)
+//   12..16 entry point, as an offset into the code section
+//   16..18 number of sections
+//   18..20 reserved, must be 0
+//   20..24 checksum of everything following the header
+//
+// Section table (16 bytes per section)
+//   0      section kind (see SectionKind)
+//   1..4   reserved, must be 0
+//   4..8   load address
+//   8..12  offset of the section's content in the file (0 for bss)
+//   12..16 size of the section once loaded
+//
+// Followed by the sections contents, in the same order as the table.
+
+pub const FORMAT_VERSION: u16 = 1;
+pub const HEADER_SIZE: usize = 24;
+pub const SECTION_ENTRY_SIZE: usize = 16;
+
+// When set, the binary targets a 64-bit machine. Otherwise it targets a 32-bit one.
+pub const FLAG_WORD_64: u32 = 1;
+// The high half of the flags is reserved to ISA extensions.
+// No extension exists yet, so any bit set here is unknown to the current tools.
+pub const FLAG_EXTENSIONS_MASK: u32 = 0xFFFF_0000;
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FileType {
+    Executable = 0,
+    // Relocatable object, see the object module
+    Object = 1,
+}
+
+impl FileType {
+    pub fn from_u8(val: u8) -> Option<Self> {
+        match val {
+            0 => Some(Self::Executable),
+            1 => Some(Self::Object),
+            _ => None,
+        }
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SectionKind {
+    // Instructions
+    Code = 1,
+    // Read-only data, strings and constants
+    Rodata = 2,
+    // Initialized data
+    Data = 3,
+    // Zero-initialized data, which takes no room in the file
+    Bss = 4,
+    // Symbol table, see object::encode_symbols
+    Symtab = 5,
+    // Relocation table, only found in objects
+    Reloc = 6,
+    // Source locations and symbols for the debugger, see debug_info
+    Debug = 7,
+}
+
+impl SectionKind {
+    pub fn from_u8(val: u8) -> Option<Self> {
+        match val {
+            1 => Some(Self::Code),
+            2 => Some(Self::Rodata),
+            3 => Some(Self::Data),
+            4 => Some(Self::Bss),
+            5 => Some(Self::Symtab),
+            6 => Some(Self::Reloc),
+            7 => Some(Self::Debug),
+            _ => None,
+        }
+    }
+
+    // Whether the section is mapped into the VM's data memory
+    pub fn is_memory(self) -> bool {
+        matches!(self, Self::Rodata | Self::Data | Self::Bss)
+    }
+
+    // Whether the section content is stored into the file.
+    pub fn has_content(self) -> bool {
+        self != Self::Bss
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub kind: SectionKind,
+    pub address: u32,
+    // Always empty for bss sections
+    pub data: Vec<u8>,
+    pub size: u32,
+}
+
+impl Section {
+    pub fn new(kind: SectionKind, address: u32, data: Vec<u8>) -> Self {
+        let size = data.len() as u32;
+        Self {
+            kind,
+            address,
+            data,
+            size,
+        }
+    }
+
+    pub fn bss(address: u32, size: u32) -> Self {
+        Self {
+            kind: SectionKind::Bss,
+            address,
+            data: vec![],
+            size,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Container {
+    pub file_type: FileType,
+    pub flags: u32,
+    pub entry: u32,
+    pub sections: Vec<Section>,
+}
+
+// Different types of errors that can be found while reading a container
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContainerError {
+    BadMagic,
+    Truncated,
+    UnsupportedVersion(u16),
+    UnknownFileType(u8),
+    UnknownSectionKind(usize, u8),
+    SectionOutOfBounds(usize),
+    ChecksumMismatch { expected: u32, found: u32 },
+}
+
+impl std::fmt::Display for ContainerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "Not a lamp binary (bad magic number)"),
+            Self::Truncated => write!(f, "The binary is truncated"),
+            Self::UnsupportedVersion(v) => write!(
+                f,
+                "Unsupported format version {} (expected {})",
+                v, FORMAT_VERSION
+            ),
+            Self::UnknownFileType(t) => write!(f, "Unknown file type {}", t),
+            Self::UnknownSectionKind(i, k) => {
+                write!(f, "Section {} has an unknown kind ({})", i, k)
+            }
+            Self::SectionOutOfBounds(i) => {
+                write!(f, "Section {} points outside of the file", i)
+            }
+            Self::ChecksumMismatch { expected, found } => write!(
+                f,
+                "The binary is corrupted (checksum is {:#010x}, expected {:#010x})",
+                found, expected
+            ),
+        }
+    }
+}
+
+impl Container {
+    pub fn executable(code: Vec<u8>, entry: u32) -> Self {
+        Self {
+            file_type: FileType::Executable,
+            flags: 0,
+            entry,
+            sections: vec![Section::new(SectionKind::Code, 0, code)],
+        }
+    }
+
+    pub fn word_size(&self) -> u32 {
+        if self.flags & FLAG_WORD_64 != 0 {
+            64
+        } else {
+            32
+        }
+    }
+
+    pub fn extensions(&self) -> u32 {
+        self.flags & FLAG_EXTENSIONS_MASK
+    }
+
+    // Gives the first section of the given kind, if any
+    pub fn section(&self, kind: SectionKind) -> Option<&Section> {
+        self.sections.iter().find(|s| s.kind == kind)
+    }
+
+    // Whether the given bytes start with the lamp magic number
+    pub fn is_container(bytes: &[u8]) -> bool {
+        bytes.starts_with(LAMP_BIN_HEADER)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let table_size = self.sections.len() * SECTION_ENTRY_SIZE;
+        let mut offset = HEADER_SIZE + table_size;
+        let mut body = Vec::<u8>::with_capacity(table_size);
+
+        for section in &self.sections {
+            let file_offset = if section.kind.has_content() {
+                offset
+            } else {
+                0
+            };
+            body.push(section.kind as u8);
+            body.extend_from_slice(&[0, 0, 0]);
+            body.extend_from_slice(&section.address.to_be_bytes());
+            body.extend_from_slice(&(file_offset as u32).to_be_bytes());
+            body.extend_from_slice(&section.size.to_be_bytes());
+            offset += section.data.len();
+        }
+        for section in &self.sections {
+            body.extend_from_slice(&section.data);
+        }
+
+        let mut bytes = Vec::<u8>::with_capacity(HEADER_SIZE + body.len());
+        bytes.extend_from_slice(LAMP_BIN_HEADER);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_be_bytes());
+        bytes.push(self.file_type as u8);
+        bytes.push(0);
+        bytes.extend_from_slice(&self.flags.to_be_bytes());
+        bytes.extend_from_slice(&self.entry.to_be_bytes());
+        bytes.extend_from_slice(&(self.sections.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&[0, 0]);
+        bytes.extend_from_slice(&checksum(&body).to_be_bytes());
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+
+    // Parses a container and checks its integrity.
+    // This doesn't check whether the binary makes sense for a given machine: that's the loader's job.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ContainerError> {
+        if !Self::is_container(bytes) {
+            return Err(ContainerError::BadMagic);
+        }
+        if bytes.len() < HEADER_SIZE {
+            return Err(ContainerError::Truncated);
+        }
+
+        let version = read_u16(bytes, 4);
+        if version != FORMAT_VERSION {
+            return Err(ContainerError::UnsupportedVersion(version));
+        }
+        let file_type =
+            FileType::from_u8(bytes[6]).ok_or(ContainerError::UnknownFileType(bytes[6]))?;
+        let flags = read_u32(bytes, 8);
+        let entry = read_u32(bytes, 12);
+        let section_count = read_u16(bytes, 16) as usize;
+
+        let expected = read_u32(bytes, 20);
+        let found = checksum(&bytes[HEADER_SIZE..]);
+        if expected != found {
+            return Err(ContainerError::ChecksumMismatch { expected, found });
+        }
+
+        if bytes.len() < HEADER_SIZE + section_count * SECTION_ENTRY_SIZE {
+            return Err(ContainerError::Truncated);
+        }
+
+        let mut sections = Vec::<Section>::with_capacity(section_count);
+        for i in 0..section_count {
+            let entry_start = HEADER_SIZE + i * SECTION_ENTRY_SIZE;
+            let kind = SectionKind::from_u8(bytes[entry_start])
+                .ok_or(ContainerError::UnknownSectionKind(i, bytes[entry_start]))?;
+            let address = read_u32(bytes, entry_start + 4);
+            let offset = read_u32(bytes, entry_start + 8) as usize;
+            let size = read_u32(bytes, entry_start + 12);
+
+            if kind.has_content() {
+                let end = offset
+                    .checked_add(size as usize)
+                    .ok_or(ContainerError::SectionOutOfBounds(i))?;
+                if end > bytes.len() {
+                    return Err(ContainerError::SectionOutOfBounds(i));
+                }
+                sections.push(Section::new(kind, address, bytes[offset..end].to_vec()));
+            } else {
+                sections.push(Section::bss(address, size));
+            }
+        }
+
+        Ok(Self {
+            file_type,
+            flags,
+            entry,
+            sections,
+        })
+    }
+}
+
+// FNV-1a, which is more than enough to catch a damaged file.
+pub fn checksum(bytes: &[u8]) -> u32 {
+    let mut hash: u32 = 0x811c_9dc5;
+    for byte in bytes {
+        hash ^= u32::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    hash
+}
+
+pub(crate) fn read_u16(bytes: &[u8], at: usize) -> u16 {
+    (u16::from(bytes[at]) << 8) | u16::from(bytes[at + 1])
+}
+
+pub(crate) fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    (u32::from(read_u16(bytes, at)) << 16) | u32::from(read_u16(bytes, at + 2))
+}
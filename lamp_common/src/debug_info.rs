@@ -0,0 +1,227 @@
+use crate::container::{read_u16, read_u32, SectionKind};
+use crate::object::{decode_symbols, encode_symbols, ObjectError, Symbol};
+use crate::register::{RegisterAlias, REGISTER_COUNT};
+use std::collections::HashMap;
+
+// Debug info section layout (see SectionKind::Debug):
+//   0..4   number of strings, then for each one its length on 2 bytes and its UTF-8 bytes.
+//          File and macro names are indexes into these strings.
+//   then   number of line entries on 4 bytes, then for each one:
+//          0..4 address in the code, 4..8 file, 8..12 line, 12..14 column,
+//          14..16 number of macro expansions, then 12 bytes per expansion (outermost first):
+//          0..4 macro name, 4..8 file and 8..12 line of the call
+//   then   number of register aliases on 2 bytes, then for each one its name on 4 bytes
+//          and its register on 1 byte
+//   then   the symbols up to the end of the section, encoded like the symbol table
+//
+// Lines and columns start at 1. Entries are sorted by address, and an entry covers
+// the code up to the next one.
+
+// Where something comes from in the source
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+// A macro call that led to a line
+#[derive(Debug, Clone, PartialEq)]
+pub struct MacroOrigin {
+    pub name: String,
+    pub file: String,
+    pub line: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineEntry {
+    pub address: u32,
+    pub location: SourceLocation,
+    pub expansions: Vec<MacroOrigin>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DebugInfo {
+    pub lines: Vec<LineEntry>,
+    // Labels, with their address (or offset in their section, in objects)
+    pub symbols: Vec<Symbol>,
+    // Register aliases defined with `.alias`
+    pub aliases: Vec<RegisterAlias>,
+}
+
+impl DebugInfo {
+    // The line the code at `address` comes from
+    pub fn line_at(&self, address: u32) -> Option<&LineEntry> {
+        match self
+            .lines
+            .binary_search_by_key(&address, |entry| entry.address)
+        {
+            Ok(i) => Some(&self.lines[i]),
+            Err(0) => None,
+            Err(i) => Some(&self.lines[i - 1]),
+        }
+    }
+
+    // The closest code label at or before `address`, with the distance to it
+    pub fn symbol_at(&self, address: u32) -> Option<(&str, u32)> {
+        self.symbols
+            .iter()
+            .filter(|s| s.section == Some(SectionKind::Code) && s.value <= address)
+            .max_by_key(|s| s.value)
+            .map(|s| (s.name.as_str(), address - s.value))
+    }
+
+    // Moves everything by where the sections were placed, like the linker does
+    pub fn relocate(&mut self, base: &dyn Fn(SectionKind) -> u32) {
+        for entry in self.lines.iter_mut() {
+            entry.address += base(SectionKind::Code);
+        }
+        for symbol in self.symbols.iter_mut() {
+            if let Some(kind) = symbol.section {
+                symbol.value += base(kind);
+            }
+        }
+    }
+
+    // Appends the debug info of another object, placed after this one
+    pub fn extend(&mut self, other: DebugInfo) {
+        self.lines.extend(other.lines);
+        self.lines.sort_by_key(|entry| entry.address);
+        self.symbols.extend(other.symbols);
+        // An alias defined by several objects is kept once
+        for alias in other.aliases {
+            if !self.aliases.contains(&alias) {
+                self.aliases.push(alias);
+            }
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut strings = Strings::default();
+        let mut index = |name: &str| strings.index(name);
+
+        let mut lines = Vec::<u8>::new();
+        lines.extend_from_slice(&(self.lines.len() as u32).to_be_bytes());
+        for entry in &self.lines {
+            lines.extend_from_slice(&entry.address.to_be_bytes());
+            lines.extend_from_slice(&index(&entry.location.file).to_be_bytes());
+            lines.extend_from_slice(&entry.location.line.to_be_bytes());
+            lines.extend_from_slice(&(entry.location.column as u16).to_be_bytes());
+            lines.extend_from_slice(&(entry.expansions.len() as u16).to_be_bytes());
+            for expansion in &entry.expansions {
+                lines.extend_from_slice(&index(&expansion.name).to_be_bytes());
+                lines.extend_from_slice(&index(&expansion.file).to_be_bytes());
+                lines.extend_from_slice(&expansion.line.to_be_bytes());
+            }
+        }
+
+        lines.extend_from_slice(&(self.aliases.len() as u16).to_be_bytes());
+        for alias in &self.aliases {
+            lines.extend_from_slice(&index(&alias.name).to_be_bytes());
+            lines.push(alias.register);
+        }
+
+        let mut bytes = Vec::<u8>::new();
+        bytes.extend_from_slice(&(strings.list.len() as u32).to_be_bytes());
+        for string in &strings.list {
+            bytes.extend_from_slice(&(string.len() as u16).to_be_bytes());
+            bytes.extend_from_slice(string.as_bytes());
+        }
+        bytes.extend(lines);
+        bytes.extend(encode_symbols(&self.symbols));
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ObjectError> {
+        let mut reader = Reader { bytes, at: 0 };
+        Self::read(&mut reader).ok_or(ObjectError::MalformedDebugInfo)
+    }
+
+    fn read(reader: &mut Reader) -> Option<Self> {
+        let mut strings = Vec::<String>::new();
+        for _ in 0..reader.u32()? {
+            let len = reader.u16()? as usize;
+            let string = std::str::from_utf8(reader.take(len)?).ok()?;
+            strings.push(string.to_owned());
+        }
+        let string = |index: u32| strings.get(index as usize).cloned();
+
+        let mut lines = Vec::<LineEntry>::new();
+        for _ in 0..reader.u32()? {
+            let address = reader.u32()?;
+            let location = SourceLocation {
+                file: string(reader.u32()?)?,
+                line: reader.u32()?,
+                column: u32::from(reader.u16()?),
+            };
+            let mut expansions = Vec::<MacroOrigin>::new();
+            for _ in 0..reader.u16()? {
+                expansions.push(MacroOrigin {
+                    name: string(reader.u32()?)?,
+                    file: string(reader.u32()?)?,
+                    line: reader.u32()?,
+                });
+            }
+            lines.push(LineEntry {
+                address,
+                location,
+                expansions,
+            });
+        }
+
+        let mut aliases = Vec::<RegisterAlias>::new();
+        for _ in 0..reader.u16()? {
+            aliases.push(RegisterAlias {
+                name: string(reader.u32()?)?,
+                register: Some(reader.take(1)?[0]).filter(|r| *r < REGISTER_COUNT)?,
+            });
+        }
+
+        let symbols = decode_symbols(reader.take(reader.bytes.len() - reader.at)?).ok()?;
+        Some(Self {
+            lines,
+            symbols,
+            aliases,
+        })
+    }
+}
+
+// The strings of the section, each one written once
+#[derive(Default)]
+struct Strings {
+    list: Vec<String>,
+    indexes: HashMap<String, u32>,
+}
+
+impl Strings {
+    fn index(&mut self, string: &str) -> u32 {
+        if let Some(index) = self.indexes.get(string) {
+            return *index;
+        }
+        let index = self.list.len() as u32;
+        self.list.push(string.to_owned());
+        self.indexes.insert(string.to_owned(), index);
+        index
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.bytes.get(self.at..self.at + len)?;
+        self.at += len;
+        Some(bytes)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|bytes| read_u16(bytes, 0))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4).map(|bytes| read_u32(bytes, 0))
+    }
+}
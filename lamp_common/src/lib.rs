@@ -1,4 +1,8 @@
+pub mod container;
+pub mod debug_info;
+pub mod object;
 pub mod op;
+pub mod register;
 
 pub mod constants {
     // Do not ask me from where those values come from
@@ -0,0 +1,285 @@
+use crate::container::{
+    read_u16, read_u32, Container, ContainerError, FileType, Section, SectionKind,
+};
+use crate::debug_info::DebugInfo;
+
+// Symbol table layout, one entry after the other:
+//   0      section the symbol is defined in (0 when the symbol is undefined)
+//   1      binding (0 for local, 1 for global)
+//   2..6   value: offset into the section in objects, final address in executables
+//   6..8   length of the name
+//   8..    name, in UTF-8
+//
+// Relocation table layout (14 bytes per relocation):
+//   0      section to patch
+//   1      relocation kind (see RelocationKind)
+//   2..6   offset of the bytes to patch into that section
+//   6..10  index of the symbol in the symbol table
+//   10..14 signed addend, added to the symbol's address
+
+const RELOCATION_ENTRY_SIZE: usize = 14;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    // None when the symbol is defined in another object (.extern)
+    pub section: Option<SectionKind>,
+    pub value: u32,
+    // Global symbols can be used by other objects (.global)
+    pub global: bool,
+}
+
+impl Symbol {
+    pub fn is_defined(&self) -> bool {
+        self.section.is_some()
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RelocationKind {
+    // The symbol's address, as a big-endian u16
+    Abs16 = 1,
+}
+
+impl RelocationKind {
+    pub fn from_u8(val: u8) -> Option<Self> {
+        match val {
+            1 => Some(Self::Abs16),
+            _ => None,
+        }
+    }
+
+    pub fn size(self) -> usize {
+        match self {
+            Self::Abs16 => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relocation {
+    pub section: SectionKind,
+    pub kind: RelocationKind,
+    pub offset: u32,
+    pub symbol: u32,
+    // `label + 4` gives an addend of 4
+    pub addend: i32,
+}
+
+// A relocatable object, as written by `lamp_assembler -c` and read by `lamp_ld`.
+// Every section starts at 0 and gets its final place at link time.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ObjectFile {
+    pub code: Vec<u8>,
+    pub rodata: Vec<u8>,
+    pub data: Vec<u8>,
+    pub bss_size: u32,
+    pub symbols: Vec<Symbol>,
+    pub relocations: Vec<Relocation>,
+    // Only there when the source was assembled with debug info
+    pub debug: Option<DebugInfo>,
+}
+
+// Different types of errors that can be found while reading an object
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectError {
+    InvalidContainer(ContainerError),
+    NotAnObject(FileType),
+    UnexpectedSection(SectionKind),
+    MalformedSymbolTable,
+    MalformedRelocationTable,
+    InvalidRelocation(usize),
+    MalformedDebugInfo,
+}
+
+impl std::fmt::Display for ObjectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidContainer(e) => write!(f, "{}", e),
+            Self::NotAnObject(t) => write!(f, "Not an object file ({:?})", t),
+            Self::UnexpectedSection(kind) => {
+                write!(f, "Unexpected or duplicated {:?} section", kind)
+            }
+            Self::MalformedSymbolTable => write!(f, "The symbol table is malformed"),
+            Self::MalformedRelocationTable => write!(f, "The relocation table is malformed"),
+            Self::MalformedDebugInfo => write!(f, "The debug info is malformed"),
+            Self::InvalidRelocation(i) => write!(
+                f,
+                "Relocation {} points outside of its section or to an unknown symbol",
+                i
+            ),
+        }
+    }
+}
+
+impl From<ContainerError> for ObjectError {
+    fn from(e: ContainerError) -> Self {
+        Self::InvalidContainer(e)
+    }
+}
+
+impl ObjectFile {
+    pub fn section_size(&self, kind: SectionKind) -> u32 {
+        match kind {
+            SectionKind::Code => self.code.len() as u32,
+            SectionKind::Rodata => self.rodata.len() as u32,
+            SectionKind::Data => self.data.len() as u32,
+            SectionKind::Bss => self.bss_size,
+            _ => 0,
+        }
+    }
+
+    pub fn to_container(&self) -> Container {
+        let mut sections = vec![Section::new(SectionKind::Code, 0, self.code.clone())];
+        if !self.rodata.is_empty() {
+            sections.push(Section::new(SectionKind::Rodata, 0, self.rodata.clone()));
+        }
+        if !self.data.is_empty() {
+            sections.push(Section::new(SectionKind::Data, 0, self.data.clone()));
+        }
+        if self.bss_size != 0 {
+            sections.push(Section::bss(0, self.bss_size));
+        }
+        sections.push(Section::new(
+            SectionKind::Symtab,
+            0,
+            encode_symbols(&self.symbols),
+        ));
+        sections.push(Section::new(
+            SectionKind::Reloc,
+            0,
+            encode_relocations(&self.relocations),
+        ));
+        if let Some(debug) = &self.debug {
+            sections.push(Section::new(SectionKind::Debug, 0, debug.encode()));
+        }
+
+        Container {
+            file_type: FileType::Object,
+            flags: 0,
+            entry: 0,
+            sections,
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ObjectError> {
+        Self::from_container(&Container::from_bytes(bytes)?)
+    }
+
+    pub fn from_container(container: &Container) -> Result<Self, ObjectError> {
+        if container.file_type != FileType::Object {
+            return Err(ObjectError::NotAnObject(container.file_type));
+        }
+
+        let mut object = Self::default();
+        let mut seen = Vec::<SectionKind>::new();
+        for section in &container.sections {
+            if seen.contains(&section.kind) {
+                return Err(ObjectError::UnexpectedSection(section.kind));
+            }
+            seen.push(section.kind);
+
+            match section.kind {
+                SectionKind::Code => object.code = section.data.clone(),
+                SectionKind::Rodata => object.rodata = section.data.clone(),
+                SectionKind::Data => object.data = section.data.clone(),
+                SectionKind::Bss => object.bss_size = section.size,
+                SectionKind::Symtab => object.symbols = decode_symbols(&section.data)?,
+                SectionKind::Reloc => object.relocations = decode_relocations(&section.data)?,
+                SectionKind::Debug => object.debug = Some(DebugInfo::decode(&section.data)?),
+            }
+        }
+
+        for (i, reloc) in object.relocations.iter().enumerate() {
+            let end = reloc.offset as usize + reloc.kind.size();
+            if end > object.section_size(reloc.section) as usize
+                || !reloc.section.has_content()
+                || reloc.symbol as usize >= object.symbols.len()
+            {
+                return Err(ObjectError::InvalidRelocation(i));
+            }
+        }
+        Ok(object)
+    }
+}
+
+pub fn encode_symbols(symbols: &[Symbol]) -> Vec<u8> {
+    let mut bytes = Vec::<u8>::new();
+    for symbol in symbols {
+        bytes.push(symbol.section.map_or(0, |kind| kind as u8));
+        bytes.push(symbol.global as u8);
+        bytes.extend_from_slice(&symbol.value.to_be_bytes());
+        bytes.extend_from_slice(&(symbol.name.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(symbol.name.as_bytes());
+    }
+    bytes
+}
+
+pub fn decode_symbols(bytes: &[u8]) -> Result<Vec<Symbol>, ObjectError> {
+    let mut symbols = Vec::<Symbol>::new();
+    let mut at = 0;
+
+    while at < bytes.len() {
+        if at + 8 > bytes.len() {
+            return Err(ObjectError::MalformedSymbolTable);
+        }
+        let section = match bytes[at] {
+            0 => None,
+            kind => Some(SectionKind::from_u8(kind).ok_or(ObjectError::MalformedSymbolTable)?),
+        };
+        let global = match bytes[at + 1] {
+            0 => false,
+            1 => true,
+            _ => return Err(ObjectError::MalformedSymbolTable),
+        };
+        let value = read_u32(bytes, at + 2);
+        let name_len = read_u16(bytes, at + 6) as usize;
+        let name = bytes
+            .get(at + 8..at + 8 + name_len)
+            .and_then(|name| std::str::from_utf8(name).ok())
+            .ok_or(ObjectError::MalformedSymbolTable)?;
+
+        symbols.push(Symbol {
+            name: name.to_owned(),
+            section,
+            value,
+            global,
+        });
+        at += 8 + name_len;
+    }
+    Ok(symbols)
+}
+
+pub fn encode_relocations(relocations: &[Relocation]) -> Vec<u8> {
+    let mut bytes = Vec::<u8>::with_capacity(relocations.len() * RELOCATION_ENTRY_SIZE);
+    for reloc in relocations {
+        bytes.push(reloc.section as u8);
+        bytes.push(reloc.kind as u8);
+        bytes.extend_from_slice(&reloc.offset.to_be_bytes());
+        bytes.extend_from_slice(&reloc.symbol.to_be_bytes());
+        bytes.extend_from_slice(&reloc.addend.to_be_bytes());
+    }
+    bytes
+}
+
+pub fn decode_relocations(bytes: &[u8]) -> Result<Vec<Relocation>, ObjectError> {
+    if !bytes.len().is_multiple_of(RELOCATION_ENTRY_SIZE) {
+        return Err(ObjectError::MalformedRelocationTable);
+    }
+
+    bytes
+        .chunks(RELOCATION_ENTRY_SIZE)
+        .map(|entry| {
+            Ok(Relocation {
+                section: SectionKind::from_u8(entry[0])
+                    .ok_or(ObjectError::MalformedRelocationTable)?,
+                kind: RelocationKind::from_u8(entry[1])
+                    .ok_or(ObjectError::MalformedRelocationTable)?,
+                offset: read_u32(entry, 2),
+                symbol: read_u32(entry, 6),
+                addend: read_u32(entry, 10) as i32,
+            })
+        })
+        .collect()
+}
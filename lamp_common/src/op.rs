@@ -1,5 +1,5 @@
 macro_rules! opcodes {
-  { $($variant: ident = $value: expr, op = $operands: expr),+$(,)? } =>
+  { $($variant: ident = $value: expr, op = [$($operand: ident),*]),+$(,)? } =>
     {
         #[repr(u8)]
         #[derive(Debug, Copy, Clone, PartialEq)]
@@ -7,33 +7,52 @@ macro_rules! opcodes {
             $($variant = $value),+,
         }
         pub const OPCODES: &'static [Opcode] = &[$(Opcode::$variant),*];
-        pub const OPCODES_OPERANDS: &'static [(Opcode, usize)] = &[$((Opcode::$variant , $operands)),*];
-        pub const OPCODES_STRINGS: &'static [(Opcode, &str)] = &[$((Opcode::$variant , stringify!(Opcode::$variant))),*];
+        pub const OPCODES_OPERANDS: &'static [(Opcode, &[OperandKind])] = &[$((Opcode::$variant , &[$(OperandKind::$operand),*])),*];
+        pub const OPCODES_STRINGS: &'static [(Opcode, &str)] = &[$((Opcode::$variant , stringify!($variant))),*];
     }
 }
+// What an operand is encoded as
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OperandKind {
+    // A register index, on 1 byte
+    Register,
+    // A 16-bit value, big-endian
+    Imm16,
+}
+
+impl OperandKind {
+    // Number of bytes the operand takes
+    pub fn size(self) -> usize {
+        match self {
+            Self::Register => 1,
+            Self::Imm16 => 2,
+        }
+    }
+}
+
 // First number is the mnemonic's ID.
-// op is the number of operands the mnemonic needs
+// op is the list of operands the mnemonic needs
 opcodes! {
     // Arithmetical instructions
-    ADD = 1, op = 3,
-    SUB = 2, op = 3,
-    MUL = 3, op = 3,
-    MOD = 4, op = 3,
-    INC = 5, op = 1,
-    DEC = 6, op = 1,
+    ADD = 1, op = [Register, Register, Register],
+    SUB = 2, op = [Register, Register, Register],
+    MUL = 3, op = [Register, Register, Register],
+    MOD = 4, op = [Register, Register, Register],
+    INC = 5, op = [Register],
+    DEC = 6, op = [Register],
     // Control Flow instructions
-    EQ =  7, op = 2,
-    NEQ = 8, op = 2,
-    GT =  9, op = 2,
-    GTE = 10, op = 2,
-    LT =  11, op = 2,
-    LTE = 12, op = 2,
-    HLT = 13, op = 0,
-    NOP = 14, op = 0,
-    LOAD = 15, op = 3,
-    JMP = 16, op = 1,
+    EQ =  7, op = [Register, Register],
+    NEQ = 8, op = [Register, Register],
+    GT =  9, op = [Register, Register],
+    GTE = 10, op = [Register, Register],
+    LT =  11, op = [Register, Register],
+    LTE = 12, op = [Register, Register],
+    HLT = 13, op = [],
+    NOP = 14, op = [],
+    LOAD = 15, op = [Register, Imm16],
+    JMP = 16, op = [Register],
     // Takes the last modulo's remaining and put it into the specified register
-    MODR = 17, op = 1,
+    MODR = 17, op = [Register],
     // Put in the register the specified value
     // MOV = 18,
 }
@@ -49,10 +68,41 @@ pub fn decode_opcode(val: u8) -> Option<Opcode> {
 
 pub fn get_op<'a>(base: String) -> Result<Opcode, &'a str> {
     for (opcode, name) in OPCODES_STRINGS {
-        if base.to_uppercase().contains(name) {
+        if base.to_uppercase() == *name {
             return Ok(*opcode);
         }
     }
 
     Err("No opcode found.")
 }
+
+// The operands the opcode needs, in order
+pub fn get_operands(opcode: Opcode) -> &'static [OperandKind] {
+    OPCODES_OPERANDS
+        .iter()
+        .find(|(code, _)| *code == opcode)
+        .map(|(_, operands)| *operands)
+        .unwrap_or(&[])
+}
+
+// Number of bytes an instruction takes, opcode included
+pub fn instruction_size(opcode: Opcode) -> usize {
+    1 + get_operands(opcode)
+        .iter()
+        .map(|operand| operand.size())
+        .sum::<usize>()
+}
+
+// Indexes of the register operands an instruction reads, and of those it writes
+pub fn register_accesses(opcode: Opcode) -> (&'static [usize], &'static [usize]) {
+    match opcode {
+        Opcode::ADD | Opcode::SUB | Opcode::MUL | Opcode::MOD => (&[0, 1], &[2]),
+        Opcode::INC | Opcode::DEC => (&[0], &[0]),
+        Opcode::EQ | Opcode::NEQ | Opcode::GT | Opcode::GTE | Opcode::LT | Opcode::LTE => {
+            (&[0, 1], &[])
+        }
+        Opcode::JMP => (&[0], &[]),
+        Opcode::LOAD | Opcode::MODR => (&[], &[0]),
+        Opcode::HLT | Opcode::NOP => (&[], &[]),
+    }
+}
@@ -0,0 +1,81 @@
+// Register names, shared by the assembler and the debugger.
+// Registers are written r0 to r31, and some of them have an alias for their usual role.
+
+pub const REGISTER_COUNT: u8 = 32;
+
+// The aliases known by default. The assembler can add more with `.alias`.
+pub const DEFAULT_ALIASES: &[(&str, u8)] = &[
+    // Holds 0 by convention, nothing enforces it
+    ("zero", 0),
+    // Return address
+    ("ra", 29),
+    // Frame pointer
+    ("fp", 30),
+    // Stack pointer
+    ("sp", 31),
+];
+
+// Parses `r0`..`r31` or a default alias
+pub fn parse_register(name: &str) -> Option<u8> {
+    let name = name.to_lowercase();
+    if let Some((_, index)) = DEFAULT_ALIASES.iter().find(|(alias, _)| *alias == name) {
+        return Some(*index);
+    }
+    match name.strip_prefix('r')?.parse::<u8>() {
+        // r007 isn't a register name
+        Ok(index) if index < REGISTER_COUNT && name[1..] == index.to_string() => Some(index),
+        _ => None,
+    }
+}
+
+// The default alias of the register, if it has one
+pub fn register_alias(index: u8) -> Option<&'static str> {
+    DEFAULT_ALIASES
+        .iter()
+        .find(|(_, i)| *i == index)
+        .map(|(alias, _)| *alias)
+}
+
+// The name used to print the register: `r5`, or `r31 (sp)` when it has an alias
+pub fn register_name(index: u8) -> String {
+    match register_alias(index) {
+        Some(alias) => format!("r{} ({})", index, alias),
+        None => format!("r{}", index),
+    }
+}
+
+// A register alias defined by the program with `.alias`, kept in its debug info
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegisterAlias {
+    pub name: String,
+    pub register: u8,
+}
+
+// Like parse_register, also knowing the aliases of the program
+pub fn parse_register_with(name: &str, aliases: &[RegisterAlias]) -> Option<u8> {
+    parse_register(name).or_else(|| {
+        aliases
+            .iter()
+            .find(|alias| alias.name.eq_ignore_ascii_case(name))
+            .map(|alias| alias.register)
+    })
+}
+
+// Like register_name, with the aliases of the program after the default one:
+// `r5 (counter)`, `r31 (sp, top)`
+pub fn register_name_with(index: u8, aliases: &[RegisterAlias]) -> String {
+    let names: Vec<&str> = register_alias(index)
+        .into_iter()
+        .chain(
+            aliases
+                .iter()
+                .filter(|alias| alias.register == index)
+                .map(|alias| alias.name.as_str()),
+        )
+        .collect();
+    if names.is_empty() {
+        format!("r{}", index)
+    } else {
+        format!("r{} ({})", index, names.join(", "))
+    }
+}
@@ -0,0 +1,543 @@
+use lamp_common::container::{Container, ContainerError, FileType, SectionKind};
+use lamp_common::object::{decode_symbols, ObjectError, ObjectFile, Relocation, Symbol};
+use lamp_common::op::{decode_opcode, get_operands, register_accesses, Opcode, OperandKind};
+use lamp_common::register::REGISTER_COUNT;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+// Turns lamp binaries back into assembly that lamp_assembler accepts.
+//
+// The code is decoded from its start to its end. Bytes that aren't a valid instruction
+// (unknown opcode, register out of range, truncated instruction) are kept as .byte,
+// and so are instructions that would overlap a label.
+
+// Bytes per .byte line
+const DATA_LINE_BYTES: usize = 8;
+// Column of the comments
+const COMMENT_COLUMN: usize = 28;
+
+// Different types of errors the disassembler can encounter
+#[derive(Debug, Clone, PartialEq)]
+pub enum DisasmError {
+    InvalidContainer(ContainerError),
+    InvalidObject(ObjectError),
+}
+
+impl std::fmt::Display for DisasmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidContainer(e) => write!(f, "{}", e),
+            Self::InvalidObject(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Operand {
+    Register(u8),
+    Imm16(u16),
+}
+
+// A decoded instruction
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub address: usize,
+    pub opcode: Opcode,
+    pub operands: Vec<Operand>,
+}
+
+impl Instruction {
+    pub fn size(&self) -> usize {
+        lamp_common::op::instruction_size(self.opcode)
+    }
+}
+
+// What the code is made of
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    Instruction(Instruction),
+    // Bytes that aren't an instruction
+    Data { address: usize, bytes: Vec<u8> },
+}
+
+impl Item {
+    pub fn address(&self) -> usize {
+        match self {
+            Self::Instruction(instruction) => instruction.address,
+            Self::Data { address, .. } => *address,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Instruction(instruction) => instruction.size(),
+            Self::Data { bytes, .. } => bytes.len(),
+        }
+    }
+}
+
+// Decodes the instruction at `address`, if there is a valid one
+pub fn decode(code: &[u8], address: usize) -> Option<Instruction> {
+    let opcode = decode_opcode(*code.get(address)?)?;
+    let mut operands = Vec::<Operand>::new();
+    let mut at = address + 1;
+    for kind in get_operands(opcode) {
+        let bytes = code.get(at..at + kind.size())?;
+        operands.push(match kind {
+            OperandKind::Register if bytes[0] < REGISTER_COUNT => Operand::Register(bytes[0]),
+            OperandKind::Register => return None,
+            OperandKind::Imm16 => Operand::Imm16(u16::from_be_bytes([bytes[0], bytes[1]])),
+        });
+        at += kind.size();
+    }
+    Some(Instruction {
+        address,
+        opcode,
+        operands,
+    })
+}
+
+// Decodes the code from `start` to `end`. Nothing crosses a boundary, so labels can be
+// put there: an instruction that would is kept as data.
+pub fn decode_range(
+    code: &[u8],
+    start: usize,
+    end: usize,
+    boundaries: &BTreeSet<usize>,
+) -> Vec<Item> {
+    let end = end.min(code.len());
+    let mut items = Vec::<Item>::new();
+    let mut address = start;
+    while address < end {
+        let next_boundary = boundaries
+            .range(address + 1..)
+            .next()
+            .copied()
+            .unwrap_or(end)
+            .min(end);
+        match decode(code, address) {
+            Some(instruction) if address + instruction.size() <= next_boundary => {
+                address += instruction.size();
+                items.push(Item::Instruction(instruction));
+            }
+            _ => {
+                // Unknown bytes are grouped, up to the next boundary
+                match items.last_mut() {
+                    Some(Item::Data {
+                        address: data_address,
+                        bytes,
+                    }) if *data_address + bytes.len() == address
+                        && !boundaries.contains(&address)
+                        && bytes.len() < DATA_LINE_BYTES =>
+                    {
+                        bytes.push(code[address])
+                    }
+                    _ => items.push(Item::Data {
+                        address,
+                        bytes: vec![code[address]],
+                    }),
+                }
+                address += 1;
+            }
+        }
+    }
+    items
+}
+
+// Decodes `count` items from `address`, like the debugger shows around the pc
+pub fn decode_items(code: &[u8], address: usize, count: usize) -> Vec<Item> {
+    let mut items = Vec::<Item>::new();
+    let mut address = address;
+    while items.len() < count && address < code.len() {
+        let item = match decode(code, address) {
+            Some(instruction) => Item::Instruction(instruction),
+            None => Item::Data {
+                address,
+                bytes: vec![code[address]],
+            },
+        };
+        address += item.size();
+        items.push(item);
+    }
+    items
+}
+
+// How the assembly is written
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    // Adds the address and the bytes of every line in a comment
+    pub annotate: bool,
+    // Marks the instruction at this address with "=>"
+    pub pc: Option<usize>,
+}
+
+// Writes one item. `operand_text` can replace the text of an immediate operand,
+// given the address of its bytes; `note` is added to the comment.
+fn format_item(
+    item: &Item,
+    code: &[u8],
+    options: &Options,
+    operand_text: &dyn Fn(usize) -> Option<String>,
+    note: Option<String>,
+) -> String {
+    let text = match item {
+        Item::Instruction(instruction) => {
+            let mut text = format!("{:?}", instruction.opcode);
+            let mut at = instruction.address + 1;
+            for (i, operand) in instruction.operands.iter().enumerate() {
+                text += if i == 0 { " " } else { ", " };
+                match operand {
+                    Operand::Register(n) => {
+                        text += &format!("r{}", n);
+                        at += 1;
+                    }
+                    Operand::Imm16(n) => {
+                        text += &operand_text(at).unwrap_or_else(|| format!("#{}", n));
+                        at += 2;
+                    }
+                }
+            }
+            text
+        }
+        Item::Data { bytes, .. } => data_text(bytes),
+    };
+
+    let mut comment = Vec::<String>::new();
+    if options.annotate {
+        let bytes = code
+            .get(item.address()..item.address() + item.size())
+            .unwrap_or(&[]);
+        comment.push(format!("{:04X}: {}", item.address(), hex(bytes)));
+    }
+    comment.extend(note);
+
+    let marker = if options.pc == Some(item.address()) {
+        "=>  "
+    } else {
+        "    "
+    };
+    with_comment(format!("{}{}", marker, text), &comment)
+}
+
+// One line of the debugger's disassembly, without labels
+pub fn format_line(item: &Item, code: &[u8], options: &Options) -> String {
+    format_item(item, code, options, &|_| None, None)
+}
+
+// Whether the register a LOAD sets is jumped to by the instructions that follow,
+// before anything else writes it
+fn is_jump_target(items: &[Item], at: usize) -> bool {
+    let register = match &items[at] {
+        Item::Instruction(Instruction {
+            opcode: Opcode::LOAD,
+            operands,
+            ..
+        }) => operands[0],
+        _ => return false,
+    };
+    for item in &items[at + 1..] {
+        let instruction = match item {
+            Item::Instruction(instruction) => instruction,
+            Item::Data { .. } => return false,
+        };
+        if instruction.opcode == Opcode::JMP {
+            return instruction.operands[0] == register;
+        }
+        let (_, writes) = register_accesses(instruction.opcode);
+        if writes.iter().any(|i| instruction.operands[*i] == register) {
+            return false;
+        }
+    }
+    false
+}
+
+fn data_text(bytes: &[u8]) -> String {
+    let values: Vec<String> = bytes.iter().map(|b| format!("{:#04x}", b)).collect();
+    format!(".byte {}", values.join(", "))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn with_comment(text: String, comment: &[String]) -> String {
+    if comment.is_empty() {
+        text
+    } else {
+        format!(
+            "{:<w$}; {}",
+            text,
+            comment.join("  "),
+            w = COMMENT_COLUMN.max(text.len() + 1)
+        )
+    }
+}
+
+// `name`, `name + n` or `name - n`
+fn offset_from(name: &str, offset: i64) -> String {
+    match offset {
+        0 => name.to_owned(),
+        n if n > 0 => format!("{} + {}", name, n),
+        n => format!("{} - {}", name, -n),
+    }
+}
+
+// A binary to disassemble: an executable, an object or a flat binary
+#[derive(Debug, Default, Clone)]
+pub struct Program {
+    pub code: Vec<u8>,
+    pub rodata: Vec<u8>,
+    pub data: Vec<u8>,
+    pub bss_size: u32,
+    // Where the sections are in the data memory. In objects, everything is at 0
+    // because symbols are offsets in their section.
+    pub data_address: u32,
+    pub bss_address: u32,
+    pub symbols: Vec<Symbol>,
+    pub relocations: Vec<Relocation>,
+    pub entry: u32,
+    pub object: bool,
+}
+
+impl Program {
+    // A flat binary made of opcodes only
+    pub fn raw(code: Vec<u8>) -> Self {
+        Self {
+            code,
+            ..Self::default()
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DisasmError> {
+        let container = Container::from_bytes(bytes).map_err(DisasmError::InvalidContainer)?;
+        Self::from_container(&container)
+    }
+
+    pub fn from_container(container: &Container) -> Result<Self, DisasmError> {
+        if container.file_type == FileType::Object {
+            let object =
+                ObjectFile::from_container(container).map_err(DisasmError::InvalidObject)?;
+            return Ok(Self {
+                code: object.code,
+                rodata: object.rodata,
+                data: object.data,
+                bss_size: object.bss_size,
+                symbols: object.symbols,
+                relocations: object.relocations,
+                object: true,
+                ..Self::default()
+            });
+        }
+
+        let mut program = Self {
+            entry: container.entry,
+            ..Self::default()
+        };
+        for section in &container.sections {
+            match section.kind {
+                SectionKind::Code => program.code = section.data.clone(),
+                SectionKind::Rodata => program.rodata = section.data.clone(),
+                SectionKind::Data => {
+                    program.data = section.data.clone();
+                    program.data_address = section.address;
+                }
+                SectionKind::Bss => {
+                    program.bss_size = section.size;
+                    program.bss_address = section.address;
+                }
+                SectionKind::Symtab => {
+                    program.symbols =
+                        decode_symbols(&section.data).map_err(DisasmError::InvalidObject)?
+                }
+                SectionKind::Reloc | SectionKind::Debug => {}
+            }
+        }
+        Ok(program)
+    }
+
+    fn base(&self, kind: SectionKind) -> u32 {
+        match kind {
+            SectionKind::Data => self.data_address,
+            SectionKind::Bss => self.bss_address,
+            _ => 0,
+        }
+    }
+
+    // The symbols that can be written in assembly: not section symbols like `.code`,
+    // and only the first one of a name (executables can have a local label in several files)
+    fn named_symbols(&self) -> Vec<&Symbol> {
+        let mut seen = HashSet::<&str>::new();
+        self.symbols
+            .iter()
+            .filter(|symbol| !symbol.name.starts_with('.') && seen.insert(&symbol.name))
+            .collect()
+    }
+
+    // Labels of a section by offset
+    fn labels(&self, kind: SectionKind) -> BTreeMap<usize, Vec<&str>> {
+        let mut labels = BTreeMap::<usize, Vec<&str>>::new();
+        for symbol in self.named_symbols() {
+            if symbol.section != Some(kind) {
+                continue;
+            }
+            if let Some(offset) = symbol.value.checked_sub(self.base(kind)) {
+                labels
+                    .entry(offset as usize)
+                    .or_default()
+                    .push(&symbol.name);
+            }
+        }
+        labels
+    }
+
+    // The text of a relocated operand, in objects
+    fn relocated(&self, at: usize, item_address: usize) -> Option<String> {
+        let relocation = self
+            .relocations
+            .iter()
+            .find(|r| r.section == SectionKind::Code && r.offset as usize == at)?;
+        let symbol = self.symbols.get(relocation.symbol as usize)?;
+        let addend = i64::from(relocation.addend);
+        if !symbol.name.starts_with('.') {
+            return Some(offset_from(&symbol.name, addend));
+        }
+        // Relative to the start of a section: a label there, or `.`
+        let kind = symbol.section?;
+        let target = i64::from(symbol.value) + addend;
+        match self.labels(kind).get(&(target as usize)) {
+            Some(names) if target >= 0 => Some(names[0].to_owned()),
+            _ if kind == SectionKind::Code => Some(offset_from(".", target - item_address as i64)),
+            _ => None,
+        }
+    }
+
+    // Code labels at the given address, to comment immediates that are jumped to
+    fn code_labels_at(&self, value: u16) -> Option<String> {
+        let labels = self.labels(SectionKind::Code);
+        labels.get(&(value as usize)).map(|names| names.join(", "))
+    }
+
+    // The whole program as assembly
+    pub fn disassemble(&self, options: &Options) -> String {
+        let mut out = String::new();
+        let mut code_labels = self.labels(SectionKind::Code);
+
+        // The entry point is kept with a _start label when it has none
+        let has_start = self.symbols.iter().any(|s| s.name == "_start");
+        if !self.object && self.entry != 0 && !has_start {
+            code_labels
+                .entry(self.entry as usize)
+                .or_default()
+                .push("_start");
+            out += ".global _start\n";
+        }
+        for symbol in self.named_symbols() {
+            if symbol.global && symbol.is_defined() {
+                out += &format!(".global {}\n", symbol.name);
+            } else if !symbol.is_defined() {
+                out += &format!(".extern {}\n", symbol.name);
+            }
+        }
+
+        let boundaries: BTreeSet<usize> = code_labels.keys().copied().collect();
+        let items = decode_range(&self.code, 0, self.code.len(), &boundaries);
+        for (i, item) in items.iter().enumerate() {
+            if let Some(names) = code_labels.get(&item.address()) {
+                for name in names {
+                    out += &format!("{}:\n", name);
+                }
+            }
+            let operand_text = |at: usize| {
+                if self.object {
+                    self.relocated(at, item.address())
+                } else {
+                    None
+                }
+            };
+            // Only values jumped to are code addresses, others may be data at the same offset
+            let note = match item {
+                Item::Instruction(instruction) if !self.object && is_jump_target(&items, i) => {
+                    instruction
+                        .operands
+                        .iter()
+                        .find_map(|operand| match operand {
+                            Operand::Imm16(n) => self.code_labels_at(*n),
+                            _ => None,
+                        })
+                }
+                _ => None,
+            };
+            out += &format_item(item, &self.code, options, &operand_text, note);
+            out += "\n";
+        }
+        // Labels at the very end of the code
+        for (_, names) in code_labels.range(self.code.len()..) {
+            for name in names {
+                out += &format!("{}:\n", name);
+            }
+        }
+
+        for (kind, content) in &[
+            (SectionKind::Rodata, &self.rodata),
+            (SectionKind::Data, &self.data),
+        ] {
+            let labels = self.labels(*kind);
+            if content.is_empty() && labels.is_empty() {
+                continue;
+            }
+            out += &format!("\n.section {:?}\n", kind).to_lowercase();
+            let boundaries: BTreeSet<usize> = labels.keys().copied().collect();
+            let mut offset = 0;
+            while offset <= content.len() {
+                for name in labels.get(&offset).into_iter().flatten() {
+                    out += &format!("{}:\n", name);
+                }
+                if offset == content.len() {
+                    break;
+                }
+                let end = boundaries
+                    .range(offset + 1..)
+                    .next()
+                    .copied()
+                    .unwrap_or(content.len())
+                    .min(content.len())
+                    .min(offset + DATA_LINE_BYTES);
+                let bytes = &content[offset..end];
+                let mut comment = vec![];
+                if options.annotate {
+                    let address = self.base(*kind) as usize + offset;
+                    comment.push(format!("{:04X}: {}", address, hex(bytes)));
+                }
+                out += &with_comment(format!("    {}", data_text(bytes)), &comment);
+                out += "\n";
+                offset = end;
+            }
+        }
+
+        let labels = self.labels(SectionKind::Bss);
+        if self.bss_size != 0 || !labels.is_empty() {
+            out += "\n.section bss\n";
+            let mut offset = 0;
+            let size = self.bss_size as usize;
+            for (at, names) in labels.range(..=size) {
+                if *at > offset {
+                    out += &format!("    .space {}\n", at - offset);
+                    offset = *at;
+                }
+                for name in names {
+                    out += &format!("{}:\n", name);
+                }
+            }
+            if size > offset {
+                out += &format!("    .space {}\n", size - offset);
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests;
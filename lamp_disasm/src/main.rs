@@ -0,0 +1,61 @@
+use lamp_disasm::{Options, Program};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "lamp_disasm")]
+struct LampDisasm {
+    /// Executable or object to disassemble
+    input: PathBuf,
+    /// Where the assembly is written (default: standard output)
+    #[structopt(short)]
+    output: Option<PathBuf>,
+    /// Reads a flat binary made of opcodes only, without any container
+    #[structopt(long)]
+    raw: bool,
+    /// Adds the address and the bytes of each line in a comment
+    #[structopt(short, long)]
+    annotate: bool,
+}
+
+fn main() {
+    let args = LampDisasm::from_args();
+
+    let bytes = match std::fs::read(&args.input) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("Error: cannot read {}: {}", args.input.display(), e);
+            std::process::exit(1);
+        }
+    };
+    let program = if args.raw {
+        Program::raw(bytes)
+    } else {
+        match Program::from_bytes(&bytes) {
+            Ok(program) => program,
+            Err(e) => {
+                eprintln!(
+                    "Error: {} can\'t be disassembled: {} (use --raw for flat binaries)",
+                    args.input.display(),
+                    e
+                );
+                std::process::exit(1);
+            }
+        }
+    };
+
+    let options = Options {
+        annotate: args.annotate,
+        pc: None,
+    };
+    let text = program.disassemble(&options);
+    match &args.output {
+        Some(path) => {
+            if let Err(e) = std::fs::write(path, text) {
+                eprintln!("Error: cannot write {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+        None => print!("{}", text),
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{decode, decode_range, Instruction, Item, Operand, Options, Program};
+use lamp_common::container::{Container, FileType, Section, SectionKind};
+use lamp_common::object::{encode_symbols, ObjectFile, Relocation, RelocationKind, Symbol};
+use lamp_common::op::Opcode;
+use std::collections::BTreeSet;
+
+fn symbol(name: &str, section: Option<SectionKind>, value: u32, global: bool) -> Symbol {
+    Symbol {
+        name: name.to_string(),
+        section,
+        value,
+        global,
+    }
+}
+
+#[test]
+fn test_decode() {
+    let code = [15, 1, 0x01, 0x02, 1, 1, 2, 3, 1, 40, 1, 2, 16];
+    assert_eq!(
+        decode(&code, 0),
+        Some(Instruction {
+            address: 0,
+            opcode: Opcode::LOAD,
+            operands: vec![Operand::Register(1), Operand::Imm16(0x0102)],
+        })
+    );
+    assert_eq!(decode(&code, 4).unwrap().opcode, Opcode::ADD);
+    // Register out of range
+    assert_eq!(decode(&code, 8), None);
+    // Truncated
+    assert_eq!(decode(&code, 12), None);
+    // Unknown opcode
+    assert_eq!(decode(&[0xff], 0), None);
+}
+
+#[test]
+fn test_decode_range() {
+    // An instruction overlapping a boundary is kept as data
+    let code = [15, 1, 0, 0, 0xff, 0xfe, 13];
+    let boundaries: BTreeSet<usize> = [2].iter().copied().collect();
+    let items = decode_range(&code, 0, code.len(), &boundaries);
+    assert_eq!(
+        items,
+        vec![
+            Item::Data {
+                address: 0,
+                bytes: vec![15, 1],
+            },
+            Item::Data {
+                address: 2,
+                bytes: vec![0, 0, 0xff, 0xfe],
+            },
+            Item::Instruction(Instruction {
+                address: 6,
+                opcode: Opcode::HLT,
+                operands: vec![],
+            }),
+        ]
+    );
+}
+
+#[test]
+fn test_disassemble_executable() {
+    // LOAD r1, end; JMP r1; .byte 0xff; end: HLT
+    let container = Container {
+        file_type: FileType::Executable,
+        flags: 0,
+        entry: 0,
+        sections: vec![
+            Section::new(SectionKind::Code, 0, vec![15, 1, 0, 7, 16, 1, 0xff, 13]),
+            Section::new(SectionKind::Rodata, 0, vec![1, 2]),
+            Section::bss(2, 4),
+            Section::new(
+                SectionKind::Symtab,
+                0,
+                encode_symbols(&[
+                    symbol("_start", Some(SectionKind::Code), 0, true),
+                    symbol("end", Some(SectionKind::Code), 7, false),
+                    symbol(".code", Some(SectionKind::Code), 0, false),
+                    symbol("table", Some(SectionKind::Rodata), 0, false),
+                    symbol("buffer", Some(SectionKind::Bss), 4, false),
+                ]),
+            ),
+        ],
+    };
+    let program = Program::from_container(&container).unwrap();
+    assert_eq!(
+        program.disassemble(&Options::default()),
+        ".global _start\n\
+         _start:\n\
+         \x20   LOAD r1, #7             ; end\n\
+         \x20   JMP r1\n\
+         \x20   .byte 0xff\n\
+         end:\n\
+         \x20   HLT\n\
+         \n.section rodata\n\
+         table:\n\
+         \x20   .byte 0x01, 0x02\n\
+         \n.section bss\n\
+         \x20   .space 2\n\
+         buffer:\n\
+         \x20   .space 2\n"
+    );
+
+    let options = Options {
+        annotate: true,
+        pc: Some(4),
+    };
+    let text = program.disassemble(&options);
+    assert!(text.contains("=>  JMP r1                  ; 0004: 10 01\n"));
+}
+
+#[test]
+fn test_disassemble_data_addresses() {
+    // _start: LOAD r6, msg; INC r6; LOAD r1, _start; JMP r1
+    let container = Container {
+        file_type: FileType::Executable,
+        flags: 0,
+        entry: 0,
+        sections: vec![
+            Section::new(
+                SectionKind::Code,
+                0,
+                vec![15, 6, 0, 0, 5, 6, 15, 1, 0, 0, 16, 1],
+            ),
+            Section::new(SectionKind::Rodata, 0, vec![0x68, 0x69]),
+            Section::new(
+                SectionKind::Symtab,
+                0,
+                encode_symbols(&[
+                    symbol("_start", Some(SectionKind::Code), 0, true),
+                    symbol("msg", Some(SectionKind::Rodata), 0, false),
+                ]),
+            ),
+        ],
+    };
+    let program = Program::from_container(&container).unwrap();
+    let text = program.disassemble(&Options::default());
+    // Not jumped to, the 0 is msg's address rather than _start's
+    assert!(text.contains("    LOAD r6, #0\n"));
+    assert!(text.contains("    LOAD r1, #0             ; _start\n"));
+}
+
+#[test]
+fn test_disassemble_object() {
+    // LOAD r1, func + 2; LOAD r2, . + 4; JMP r1
+    let object = ObjectFile {
+        code: vec![15, 1, 0, 0, 15, 2, 0, 0, 16, 1],
+        symbols: vec![
+            symbol("func", None, 0, false),
+            symbol(".code", Some(SectionKind::Code), 0, false),
+        ],
+        relocations: vec![
+            Relocation {
+                section: SectionKind::Code,
+                kind: RelocationKind::Abs16,
+                offset: 2,
+                symbol: 0,
+                addend: 2,
+            },
+            Relocation {
+                section: SectionKind::Code,
+                kind: RelocationKind::Abs16,
+                offset: 6,
+                symbol: 1,
+                addend: 8,
+            },
+        ],
+        ..ObjectFile::default()
+    };
+    let program = Program::from_container(&object.to_container()).unwrap();
+    assert_eq!(
+        program.disassemble(&Options::default()),
+        ".extern func\n    LOAD r1, func + 2\n    LOAD r2, . + 4\n    JMP r1\n"
+    );
+}
@@ -0,0 +1,335 @@
+use lamp_common::container::{Container, FileType, Section, SectionKind};
+use lamp_common::debug_info::DebugInfo;
+use lamp_common::object::{encode_symbols, ObjectFile, RelocationKind, Symbol};
+use std::collections::HashMap;
+
+pub const DEFAULT_ENTRY_SYMBOL: &str = "_start";
+
+// Different types of errors the linker can encounter
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkError {
+    DuplicateSymbol {
+        name: String,
+        first: String,
+        second: String,
+    },
+    UndefinedSymbol {
+        name: String,
+        object: String,
+    },
+    RelocationOutOfRange {
+        name: String,
+        object: String,
+        address: i64,
+    },
+    UndefinedEntry(String),
+    EntryNotInCode(String),
+}
+
+impl std::fmt::Display for LinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DuplicateSymbol {
+                name,
+                first,
+                second,
+            } => write!(
+                f,
+                "Symbol \'{}\' is defined both in {} and {}",
+                name, first, second
+            ),
+            Self::UndefinedSymbol { name, object } => {
+                write!(f, "Undefined symbol \'{}\' used in {}", name, object)
+            }
+            Self::RelocationOutOfRange {
+                name,
+                object,
+                address,
+            } => write!(
+                f,
+                "Address {} computed from \'{}\' in {} doesn\'t fit in 16 bits",
+                address, name, object
+            ),
+            Self::UndefinedEntry(name) => write!(f, "Entry symbol \'{}\' is undefined", name),
+            Self::EntryNotInCode(name) => {
+                write!(f, "Entry symbol \'{}\' is not in the code section", name)
+            }
+        }
+    }
+}
+
+impl LinkError {
+    // Stable code, used in the assembler's diagnostics
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::DuplicateSymbol { .. } => "E0401",
+            Self::UndefinedSymbol { .. } => "E0402",
+            Self::RelocationOutOfRange { .. } => "E0403",
+            Self::UndefinedEntry(_) => "E0404",
+            Self::EntryNotInCode(_) => "E0405",
+        }
+    }
+}
+
+// An object to link, with the name used in error messages (usually its path)
+pub struct LinkInput {
+    pub name: String,
+    pub object: ObjectFile,
+}
+
+// Where an object's sections land in the executable
+#[derive(Default, Clone, Copy)]
+pub struct Placement {
+    code: u32,
+    rodata: u32,
+    data: u32,
+    bss: u32,
+}
+
+impl Placement {
+    // Address of the section, in the code or in the data memory
+    pub fn base(&self, kind: SectionKind) -> u32 {
+        match kind {
+            SectionKind::Code => self.code,
+            SectionKind::Rodata => self.rodata,
+            SectionKind::Data => self.data,
+            SectionKind::Bss => self.bss,
+            _ => 0,
+        }
+    }
+}
+
+// Combines objects into an executable.
+//
+// The code sections are put one after the other, starting at 0.
+// The data memory gets every rodata section, then every data section, then the bss.
+pub struct Linker {
+    // Symbol the execution starts at, global or else local to the first object defining
+    // it. When not set, `_start` is used if it exists.
+    pub entry: Option<String>,
+    // Whether the symbol table should be left out of the executable
+    pub strip: bool,
+}
+
+impl Linker {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            entry: None,
+            strip: false,
+        }
+    }
+
+    pub fn link(&self, inputs: &[LinkInput]) -> Result<Container, Vec<LinkError>> {
+        let placements = Self::layout(inputs);
+        let mut errors = Vec::<LinkError>::new();
+
+        // Global symbols, with the index of the object defining them
+        let mut globals = HashMap::<&str, (usize, &Symbol)>::new();
+        for (i, input) in inputs.iter().enumerate() {
+            for symbol in input.object.symbols.iter() {
+                if !symbol.global || !symbol.is_defined() {
+                    continue;
+                }
+                if let Some((first, _)) = globals.get(symbol.name.as_str()) {
+                    errors.push(LinkError::DuplicateSymbol {
+                        name: symbol.name.clone(),
+                        first: inputs[*first].name.clone(),
+                        second: input.name.clone(),
+                    });
+                } else {
+                    globals.insert(&symbol.name, (i, symbol));
+                }
+            }
+        }
+
+        let address_of = |object: usize, symbol: &Symbol| match symbol.section {
+            Some(kind) => Some(placements[object].base(kind) + symbol.value),
+            None => globals
+                .get(symbol.name.as_str())
+                .map(|(i, s)| placements[*i].base(s.section.unwrap()) + s.value),
+        };
+
+        let mut code = Vec::<u8>::new();
+        let mut rodata = Vec::<u8>::new();
+        let mut data = Vec::<u8>::new();
+        let mut bss_size = 0;
+        for input in inputs {
+            code.extend_from_slice(&input.object.code);
+            rodata.extend_from_slice(&input.object.rodata);
+            data.extend_from_slice(&input.object.data);
+            bss_size += input.object.bss_size;
+        }
+
+        for (i, input) in inputs.iter().enumerate() {
+            for reloc in &input.object.relocations {
+                let symbol = &input.object.symbols[reloc.symbol as usize];
+                let address = match address_of(i, symbol) {
+                    Some(address) => i64::from(address) + i64::from(reloc.addend),
+                    None => {
+                        errors.push(LinkError::UndefinedSymbol {
+                            name: symbol.name.clone(),
+                            object: input.name.clone(),
+                        });
+                        continue;
+                    }
+                };
+
+                // Placements are addresses, data ones start after the rodata
+                let (section, start) = match reloc.section {
+                    SectionKind::Code => (&mut code, 0),
+                    SectionKind::Rodata => (&mut rodata, 0),
+                    _ => (&mut data, placements[0].data),
+                };
+                let at = (placements[i].base(reloc.section) + reloc.offset - start) as usize;
+                match reloc.kind {
+                    RelocationKind::Abs16 => {
+                        if address < 0 || address > i64::from(u16::MAX) {
+                            errors.push(LinkError::RelocationOutOfRange {
+                                name: symbol.name.clone(),
+                                object: input.name.clone(),
+                                address,
+                            });
+                            continue;
+                        }
+                        section[at..at + 2].copy_from_slice(&(address as u16).to_be_bytes());
+                    }
+                }
+            }
+        }
+
+        let entry = match self.find_entry(inputs, &globals, &placements) {
+            Ok(entry) => entry,
+            Err(e) => {
+                errors.push(e);
+                0
+            }
+        };
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let mut container = Container {
+            file_type: FileType::Executable,
+            flags: 0,
+            entry,
+            sections: vec![Section::new(SectionKind::Code, 0, code)],
+        };
+        let data_address = rodata.len() as u32;
+        let bss_address = data_address + data.len() as u32;
+        if !rodata.is_empty() {
+            container
+                .sections
+                .push(Section::new(SectionKind::Rodata, 0, rodata));
+        }
+        if !data.is_empty() {
+            container
+                .sections
+                .push(Section::new(SectionKind::Data, data_address, data));
+        }
+        if bss_size != 0 {
+            container.sections.push(Section::bss(bss_address, bss_size));
+        }
+
+        if !self.strip {
+            let symbols = Self::final_symbols(inputs, &placements);
+            container.sections.push(Section::new(
+                SectionKind::Symtab,
+                0,
+                encode_symbols(&symbols),
+            ));
+            if let Some(debug) = Self::debug_info(inputs, &placements) {
+                container
+                    .sections
+                    .push(Section::new(SectionKind::Debug, 0, debug.encode()));
+            }
+        }
+        Ok(container)
+    }
+
+    // Where each input's sections land, in the same order as the inputs
+    pub fn layout(inputs: &[LinkInput]) -> Vec<Placement> {
+        let total = |kind: SectionKind| -> u32 {
+            inputs
+                .iter()
+                .map(|input| input.object.section_size(kind))
+                .sum()
+        };
+
+        let mut next = Placement {
+            code: 0,
+            rodata: 0,
+            data: total(SectionKind::Rodata),
+            bss: total(SectionKind::Rodata) + total(SectionKind::Data),
+        };
+        let mut placements = Vec::<Placement>::with_capacity(inputs.len());
+        for input in inputs {
+            placements.push(next);
+            next.code += input.object.section_size(SectionKind::Code);
+            next.rodata += input.object.section_size(SectionKind::Rodata);
+            next.data += input.object.section_size(SectionKind::Data);
+            next.bss += input.object.section_size(SectionKind::Bss);
+        }
+        placements
+    }
+
+    // The global symbol named like the entry, else the first local one
+    fn find_entry(
+        &self,
+        inputs: &[LinkInput],
+        globals: &HashMap<&str, (usize, &Symbol)>,
+        placements: &[Placement],
+    ) -> Result<u32, LinkError> {
+        let name = self.entry.as_deref().unwrap_or(DEFAULT_ENTRY_SYMBOL);
+        let local = || {
+            inputs.iter().enumerate().find_map(|(i, input)| {
+                input
+                    .object
+                    .symbols
+                    .iter()
+                    .find(|symbol| symbol.name == name && symbol.is_defined())
+                    .map(|symbol| (i, symbol))
+            })
+        };
+        match globals.get(name).copied().or_else(local) {
+            Some((i, symbol)) if symbol.section == Some(SectionKind::Code) => {
+                Ok(placements[i].code + symbol.value)
+            }
+            Some(_) => Err(LinkError::EntryNotInCode(name.to_owned())),
+            None if self.entry.is_some() => Err(LinkError::UndefinedEntry(name.to_owned())),
+            None => Ok(0),
+        }
+    }
+
+    // The debug info of the inputs that have some, moved to their final addresses
+    fn debug_info(inputs: &[LinkInput], placements: &[Placement]) -> Option<DebugInfo> {
+        let mut merged: Option<DebugInfo> = None;
+        for (input, placement) in inputs.iter().zip(placements) {
+            if let Some(debug) = &input.object.debug {
+                let mut debug = debug.clone();
+                debug.relocate(&|kind| placement.base(kind));
+                merged.get_or_insert_with(DebugInfo::default).extend(debug);
+            }
+        }
+        merged
+    }
+
+    // Every defined symbol, with its final address
+    fn final_symbols(inputs: &[LinkInput], placements: &[Placement]) -> Vec<Symbol> {
+        let mut symbols = Vec::<Symbol>::new();
+        for (input, placement) in inputs.iter().zip(placements) {
+            for symbol in &input.object.symbols {
+                if let Some(kind) = symbol.section {
+                    symbols.push(Symbol {
+                        value: placement.base(kind) + symbol.value,
+                        ..symbol.clone()
+                    });
+                }
+            }
+        }
+        symbols
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,74 @@
+use lamp_common::object::ObjectFile;
+use lamp_ld::{LinkInput, Linker};
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "lamp_ld")]
+struct LampLd {
+    /// Object files to link, in the order their sections are laid out
+    #[structopt(required = true)]
+    objects: Vec<PathBuf>,
+    /// Where the executable is written
+    #[structopt(short)]
+    output: PathBuf,
+    /// Symbol the execution starts at (default: _start if it exists, else the first instruction)
+    #[structopt(short, long)]
+    entry: Option<String>,
+    /// Leaves the symbol table out of the executable
+    #[structopt(short, long)]
+    strip: bool,
+}
+
+fn main() {
+    let args = LampLd::from_args();
+
+    let mut inputs = Vec::<LinkInput>::new();
+    for path in &args.objects {
+        let name = path.display().to_string();
+        match std::fs::read(path) {
+            Ok(bytes) => match ObjectFile::from_bytes(&bytes) {
+                Ok(object) => inputs.push(LinkInput { name, object }),
+                Err(e) => {
+                    eprintln!("Error: {} is not a valid object file: {}", name, e);
+                    std::process::exit(1);
+                }
+            },
+            Err(e) => {
+                eprintln!("Error: cannot read {}: {:?}", name, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let mut linker = Linker::new();
+    linker.entry = args.entry;
+    linker.strip = args.strip;
+
+    match linker.link(&inputs) {
+        Ok(container) => match write_output(&args.output, container.to_bytes()) {
+            Ok(size) => println!("Linking successfully ended ({} bytes).", size),
+            Err(e) => {
+                eprintln!("Linking failed: Cannot write the output. Error: {:?}", e);
+                std::process::exit(1);
+            }
+        },
+        Err(errors) => {
+            eprintln!("Can\'t link: {} problems found.", errors.len());
+            for error in errors {
+                eprintln!("{}", error);
+            }
+            std::process::exit(1);
+        }
+    }
+
+    fn write_output(path: &PathBuf, bin: Vec<u8>) -> Result<usize, std::io::Error> {
+        let file = File::create(path)?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(&bin)?;
+        Ok(bin.len())
+    }
+}
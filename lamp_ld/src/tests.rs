@@ -0,0 +1,230 @@
+use crate::{LinkError, LinkInput, Linker};
+use lamp_common::container::SectionKind;
+use lamp_common::debug_info::{DebugInfo, LineEntry, MacroOrigin, SourceLocation};
+use lamp_common::object::{decode_symbols, ObjectFile, Relocation, RelocationKind, Symbol};
+use lamp_common::register::RegisterAlias;
+
+fn symbol(name: &str, section: Option<SectionKind>, value: u32, global: bool) -> Symbol {
+    Symbol {
+        name: name.to_string(),
+        section,
+        value,
+        global,
+    }
+}
+
+fn abs16(section: SectionKind, offset: u32, symbol: u32) -> Relocation {
+    Relocation {
+        section,
+        kind: RelocationKind::Abs16,
+        offset,
+        symbol,
+        addend: 0,
+    }
+}
+
+// LOAD r1, func; JMP r1 with func coming from another object
+fn main_object() -> ObjectFile {
+    ObjectFile {
+        code: vec![15, 1, 0, 0, 16, 1],
+        symbols: vec![
+            symbol("_start", Some(SectionKind::Code), 0, true),
+            symbol("func", None, 0, false),
+        ],
+        relocations: vec![abs16(SectionKind::Code, 2, 1)],
+        ..ObjectFile::default()
+    }
+}
+
+// func: LOAD r2, msg; with msg in rodata (at offset 1), and a pointer to msg in data
+fn lib_object() -> ObjectFile {
+    ObjectFile {
+        code: vec![15, 2, 0, 0],
+        rodata: vec![72, 105],
+        data: vec![0, 0],
+        bss_size: 4,
+        symbols: vec![
+            symbol("func", Some(SectionKind::Code), 0, true),
+            symbol("msg", Some(SectionKind::Rodata), 1, false),
+        ],
+        relocations: vec![
+            abs16(SectionKind::Code, 2, 1),
+            abs16(SectionKind::Data, 0, 1),
+        ],
+        debug: None,
+    }
+}
+
+fn inputs(objects: Vec<ObjectFile>) -> Vec<LinkInput> {
+    objects
+        .into_iter()
+        .enumerate()
+        .map(|(i, object)| LinkInput {
+            name: format!("{}.o", i),
+            object,
+        })
+        .collect()
+}
+
+#[test]
+fn test_link_objects() {
+    // lib first, so main's code and the entry point get moved
+    let container = Linker::new()
+        .link(&inputs(vec![lib_object(), main_object()]))
+        .unwrap();
+
+    assert_eq!(container.entry, 4);
+    let code = container.section(SectionKind::Code).unwrap();
+    assert_eq!(code.data, vec![15, 2, 0, 1, 15, 1, 0, 0, 16, 1]);
+
+    let data = container.section(SectionKind::Data).unwrap();
+    assert_eq!(data.address, 2);
+    assert_eq!(data.data, vec![0, 1]);
+    let bss = container.section(SectionKind::Bss).unwrap();
+    assert_eq!((bss.address, bss.size), (4, 4));
+
+    let symbols = decode_symbols(&container.section(SectionKind::Symtab).unwrap().data).unwrap();
+    assert!(symbols.contains(&symbol("_start", Some(SectionKind::Code), 4, true)));
+}
+
+#[test]
+fn test_link_errors() {
+    let errors = Linker::new()
+        .link(&inputs(vec![main_object()]))
+        .unwrap_err();
+    assert_eq!(
+        errors,
+        vec![LinkError::UndefinedSymbol {
+            name: "func".to_string(),
+            object: "0.o".to_string(),
+        }]
+    );
+
+    let errors = Linker::new()
+        .link(&inputs(vec![lib_object(), lib_object()]))
+        .unwrap_err();
+    assert_eq!(
+        errors,
+        vec![LinkError::DuplicateSymbol {
+            name: "func".to_string(),
+            first: "0.o".to_string(),
+            second: "1.o".to_string(),
+        }]
+    );
+
+    let mut linker = Linker::new();
+    linker.entry = Some("main".to_string());
+    let errors = linker.link(&inputs(vec![lib_object()])).unwrap_err();
+    assert_eq!(errors, vec![LinkError::UndefinedEntry("main".to_string())]);
+}
+
+#[test]
+fn test_local_entry() {
+    // helper: INC r1; _start: INC r2, with _start not global
+    let object = ObjectFile {
+        code: vec![5, 1, 5, 2],
+        symbols: vec![
+            symbol("helper", Some(SectionKind::Code), 0, false),
+            symbol("_start", Some(SectionKind::Code), 2, false),
+        ],
+        ..ObjectFile::default()
+    };
+    let container = Linker::new()
+        .link(&inputs(vec![lib_object(), object]))
+        .unwrap();
+    assert_eq!(container.entry, 6);
+}
+
+#[test]
+fn test_relocation_addend() {
+    // LOAD r1, func + 3
+    let mut main = main_object();
+    main.relocations[0].addend = 3;
+    let container = Linker::new()
+        .link(&inputs(vec![lib_object(), main.clone()]))
+        .unwrap();
+    let code = container.section(SectionKind::Code).unwrap();
+    assert_eq!(code.data[6..8], [0, 3]);
+
+    main.relocations[0].addend = -1;
+    let errors = Linker::new()
+        .link(&inputs(vec![lib_object(), main]))
+        .unwrap_err();
+    assert_eq!(
+        errors,
+        vec![LinkError::RelocationOutOfRange {
+            name: "func".to_string(),
+            object: "1.o".to_string(),
+            address: -1,
+        }]
+    );
+}
+
+#[test]
+fn test_object_round_trip() {
+    let mut object = lib_object();
+    object.relocations[1].addend = -2;
+    let bytes = object.to_container().to_bytes();
+    assert_eq!(ObjectFile::from_bytes(&bytes).unwrap(), object);
+}
+
+fn line(address: u32, file: &str, line: u32, expansions: Vec<MacroOrigin>) -> LineEntry {
+    LineEntry {
+        address,
+        location: SourceLocation {
+            file: file.to_string(),
+            line,
+            column: 5,
+        },
+        expansions,
+    }
+}
+
+#[test]
+fn test_debug_info() {
+    let origin = MacroOrigin {
+        name: "load_msg".to_string(),
+        file: "lib.lasm".to_string(),
+        line: 7,
+    };
+    let alias = RegisterAlias {
+        name: "counter".to_string(),
+        register: 5,
+    };
+    let mut lib = lib_object();
+    lib.debug = Some(DebugInfo {
+        lines: vec![line(0, "lib.lasm", 2, vec![origin.clone()])],
+        symbols: lib.symbols.clone(),
+        aliases: vec![alias.clone()],
+    });
+    let mut main = main_object();
+    main.debug = Some(DebugInfo {
+        lines: vec![
+            line(0, "main.lasm", 1, vec![]),
+            line(4, "main.lasm", 2, vec![]),
+        ],
+        symbols: vec![main.symbols[0].clone()],
+        aliases: vec![alias.clone()],
+    });
+
+    // Objects keep their debug info
+    let bytes = lib.to_container().to_bytes();
+    assert_eq!(ObjectFile::from_bytes(&bytes).unwrap(), lib);
+
+    // The linker moves it with the code, here main comes after lib's 4 bytes
+    let container = Linker::new().link(&inputs(vec![lib, main])).unwrap();
+    let section = container.section(SectionKind::Debug).unwrap();
+    let debug = DebugInfo::decode(&section.data).unwrap();
+    let addresses: Vec<u32> = debug.lines.iter().map(|entry| entry.address).collect();
+    assert_eq!(addresses, vec![0, 4, 8]);
+    assert_eq!(debug.line_at(3).unwrap().expansions, vec![origin]);
+    assert_eq!(debug.line_at(9).unwrap().location.line, 2);
+    assert_eq!(debug.symbol_at(6), Some(("_start", 2)));
+    // Both objects define the alias
+    assert_eq!(debug.aliases, vec![alias]);
+
+    let mut linker = Linker::new();
+    linker.strip = true;
+    let container = linker.link(&inputs(vec![lib_object()])).unwrap();
+    assert!(container.section(SectionKind::Debug).is_none());
+}
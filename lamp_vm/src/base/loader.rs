@@ -0,0 +1,141 @@
+use super::vm::VM;
+use lamp_common::container::{Container, ContainerError, FileType, SectionKind};
+
+// The VM refuses to map more data memory than this, so a corrupted bss size can't eat all the RAM.
+pub const MAX_MEMORY_SIZE: usize = 16 * 1024 * 1024;
+
+// Different types of errors the loader can encounter
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadError {
+    InvalidContainer(ContainerError),
+    NotAnExecutable(FileType),
+    UnsupportedWordSize(u32),
+    UnsupportedExtensions(u32),
+    MissingCodeSection,
+    EmptyCodeSection,
+    DuplicateSection(SectionKind),
+    MisplacedCodeSection(u32),
+    EntryOutOfBounds(u32),
+    OverlappingSections(SectionKind, SectionKind),
+    MemoryTooLarge(usize),
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidContainer(e) => write!(f, "{}", e),
+            Self::NotAnExecutable(t) => write!(f, "The binary is not an executable ({:?})", t),
+            Self::UnsupportedWordSize(size) => write!(
+                f,
+                "The binary targets a {}-bit machine, but this VM is 32-bit",
+                size
+            ),
+            Self::UnsupportedExtensions(ext) => {
+                write!(f, "The binary uses unknown ISA extensions ({:#010x})", ext)
+            }
+            Self::MissingCodeSection => write!(f, "The binary has no code section"),
+            Self::EmptyCodeSection => write!(f, "The code section of the binary is empty"),
+            Self::DuplicateSection(kind) => {
+                write!(f, "The binary has more than one {:?} section", kind)
+            }
+            Self::MisplacedCodeSection(addr) => write!(
+                f,
+                "The code section must be loaded at address 0, not {:#x}",
+                addr
+            ),
+            Self::EntryOutOfBounds(entry) => {
+                write!(f, "The entry point {:#x} is outside of the code", entry)
+            }
+            Self::OverlappingSections(a, b) => {
+                write!(f, "The {:?} and {:?} sections overlap", a, b)
+            }
+            Self::MemoryTooLarge(size) => write!(
+                f,
+                "The binary needs {} bytes of memory, the limit is {}",
+                size, MAX_MEMORY_SIZE
+            ),
+        }
+    }
+}
+
+impl From<ContainerError> for LoadError {
+    fn from(e: ContainerError) -> Self {
+        Self::InvalidContainer(e)
+    }
+}
+
+// Reads a lamp binary, checks it can run on this VM and maps it.
+pub fn load(bytes: &[u8]) -> Result<VM, LoadError> {
+    let container = Container::from_bytes(bytes)?;
+    map(&container)
+}
+
+pub fn map(container: &Container) -> Result<VM, LoadError> {
+    if container.file_type != FileType::Executable {
+        return Err(LoadError::NotAnExecutable(container.file_type));
+    }
+    if container.word_size() != 32 {
+        return Err(LoadError::UnsupportedWordSize(container.word_size()));
+    }
+    if container.extensions() != 0 {
+        return Err(LoadError::UnsupportedExtensions(container.extensions()));
+    }
+
+    for (i, section) in container.sections.iter().enumerate() {
+        if container.sections[..i]
+            .iter()
+            .any(|s| s.kind == section.kind)
+        {
+            return Err(LoadError::DuplicateSection(section.kind));
+        }
+    }
+
+    let code = container
+        .section(SectionKind::Code)
+        .ok_or(LoadError::MissingCodeSection)?;
+    if code.address != 0 {
+        return Err(LoadError::MisplacedCodeSection(code.address));
+    }
+    if code.data.is_empty() {
+        return Err(LoadError::EmptyCodeSection);
+    }
+    if container.entry as usize >= code.data.len() {
+        return Err(LoadError::EntryOutOfBounds(container.entry));
+    }
+
+    // Other sections like the symbol table are only useful to the tools
+    let data_sections: Vec<_> = container
+        .sections
+        .iter()
+        .filter(|s| s.kind.is_memory())
+        .collect();
+
+    let mut memory_size = 0usize;
+    for (i, section) in data_sections.iter().enumerate() {
+        let start = section.address as usize;
+        let end = start + section.size as usize;
+        for other in &data_sections[..i] {
+            let other_start = other.address as usize;
+            let other_end = other_start + other.size as usize;
+            if start < other_end && other_start < end {
+                return Err(LoadError::OverlappingSections(other.kind, section.kind));
+            }
+        }
+        memory_size = memory_size.max(end);
+    }
+    if memory_size > MAX_MEMORY_SIZE {
+        return Err(LoadError::MemoryTooLarge(memory_size));
+    }
+
+    let mut memory = vec![0u8; memory_size];
+    for section in data_sections {
+        let start = section.address as usize;
+        memory[start..start + section.data.len()].copy_from_slice(&section.data);
+    }
+
+    Ok(VM::with_memory(
+        code.data.clone(),
+        memory,
+        container.entry as usize,
+    ))
+}
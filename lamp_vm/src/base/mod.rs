@@ -1,2 +1,5 @@
+pub mod loader;
 pub mod opcodes;
+pub mod profile;
+pub mod trace;
 pub mod vm;
@@ -0,0 +1,211 @@
+// Counts how many times each instruction is executed, to find where a program
+// spends its time. Counts are grouped by address, opcode, label and source line.
+//
+// The instruction set has no CALL nor RET, so there are no call stacks to follow yet:
+// the stack of an instruction is the label it is in, then the macros it was expanded
+// from, like the debugger's stack trace.
+use super::vm::{VMResult, VM};
+use lamp_common::container::SectionKind;
+use lamp_common::debug_info::DebugInfo;
+use lamp_common::object::Symbol;
+use lamp_common::op::Opcode;
+use lamp_disasm::{decode, decode_items, format_line, Options};
+use std::collections::HashMap;
+
+// Lines of each table of the report
+pub const REPORT_LINES: usize = 10;
+
+// Name of the frame of code before the first label, like flamegraph tools call it
+const UNKNOWN: &str = "[unknown]";
+
+pub struct Profile {
+    // Executions of the instruction at each address
+    counts: Vec<u64>,
+    total: u64,
+}
+
+impl Profile {
+    pub fn new(vm: &VM) -> Self {
+        Self {
+            counts: vec![0; vm.get_program().len()],
+            total: 0,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn count(&self, address: usize) -> u64 {
+        self.counts.get(address).copied().unwrap_or(0)
+    }
+
+    // Runs the program to its end, or until it faults, like VM::run.
+    // An instruction that faults counts as executed.
+    pub fn run(&mut self, vm: &mut VM) -> VMResult {
+        loop {
+            if let Some(count) = self.counts.get_mut(vm.get_pc()) {
+                *count += 1;
+            }
+            self.total += 1;
+            let result = vm.cycle();
+            if result.is_err() || vm.get_pc() >= vm.get_program().len() {
+                return result;
+            }
+        }
+    }
+
+    // Addresses executed, the most executed first
+    pub fn hot_spots(&self) -> Vec<(usize, u64)> {
+        let mut spots: Vec<(usize, u64)> = self
+            .counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(address, count)| (address, *count))
+            .collect();
+        spots.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        spots
+    }
+
+    pub fn by_opcode(&self, code: &[u8]) -> Vec<(Opcode, u64)> {
+        let mut counts = Vec::<(Opcode, u64)>::new();
+        for (address, count) in self.hot_spots() {
+            let opcode = match decode(code, address) {
+                Some(instruction) => instruction.opcode,
+                None => continue,
+            };
+            match counts.iter_mut().find(|(op, _)| *op == opcode) {
+                Some((_, total)) => *total += count,
+                None => counts.push((opcode, count)),
+            }
+        }
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then((a.0 as u8).cmp(&(b.0 as u8))));
+        counts
+    }
+
+    pub fn by_label(&self, symbols: &[Symbol]) -> Vec<(String, u64)> {
+        self.group(|address| label_name(symbols, address))
+    }
+
+    // Counts by `file:line`, the line of a macro being where it is invoked
+    pub fn by_line(&self, info: &DebugInfo) -> Vec<(String, u64)> {
+        self.group(|address| match info.line_at(address as u32) {
+            Some(entry) => {
+                let (file, line) = match entry.expansions.first() {
+                    Some(outer) => (&outer.file, outer.line),
+                    None => (&entry.location.file, entry.location.line),
+                };
+                format!("{}:{}", file, line)
+            }
+            None => UNKNOWN.to_owned(),
+        })
+    }
+
+    // Lines like `loop;div 12`, the format flamegraph tools read: the frames from the
+    // outermost, then the number of instructions executed in the innermost one
+    pub fn folded(&self, symbols: &[Symbol], info: Option<&DebugInfo>) -> Vec<String> {
+        let mut stacks = self.group(|address| {
+            let mut frames = vec![label_name(symbols, address)];
+            if let Some(entry) = info.and_then(|info| info.line_at(address as u32)) {
+                frames.extend(entry.expansions.iter().map(|e| e.name.clone()));
+            }
+            frames.join(";")
+        });
+        stacks.sort();
+        stacks
+            .iter()
+            .map(|(stack, count)| format!("{} {}", stack, count))
+            .collect()
+    }
+
+    // Sums the counts of the addresses by key. The keys executed as much come in the
+    // order of their first address.
+    fn group(&self, key: impl Fn(usize) -> String) -> Vec<(String, u64)> {
+        let mut counts = HashMap::<String, (usize, u64)>::new();
+        for (address, count) in self.hot_spots() {
+            let entry = counts.entry(key(address)).or_insert((address, 0));
+            entry.0 = entry.0.min(address);
+            entry.1 += count;
+        }
+        let mut counts: Vec<(String, (usize, u64))> = counts.into_iter().collect();
+        counts.sort_by(|(_, a), (_, b)| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts
+            .into_iter()
+            .map(|(key, (_, count))| (key, count))
+            .collect()
+    }
+
+    // Tables of the `top` hot spots, labels, lines and opcodes
+    pub fn report(
+        &self,
+        code: &[u8],
+        symbols: &[Symbol],
+        info: Option<&DebugInfo>,
+        top: usize,
+    ) -> String {
+        let percent = |count: u64| 100.0 * count as f64 / self.total.max(1) as f64;
+        let mut out = format!("{} instructions executed\n", self.total);
+
+        out += "\nHot spots:\n      count       %  address  instruction\n";
+        for (address, count) in self.hot_spots().into_iter().take(top) {
+            let text = decode_items(code, address, 1)
+                .first()
+                .map(|item| format_line(item, code, &Options::default()))
+                .unwrap_or_default();
+            let mut location = label_at(symbols, address);
+            if let Some(entry) = info.and_then(|info| info.line_at(address as u32)) {
+                location += &format!(" {}:{}", entry.location.file, entry.location.line);
+            }
+            out += &format!(
+                "{:>11} {:>6.2}%  {:#06x}   {:<24}{}\n",
+                count,
+                percent(count),
+                address,
+                text.trim(),
+                location
+            );
+        }
+
+        let mut tables = vec![("Labels", self.by_label(symbols))];
+        if let Some(info) = info {
+            tables.push(("Source lines", self.by_line(info)));
+        }
+        let opcodes = self
+            .by_opcode(code)
+            .into_iter()
+            .map(|(opcode, count)| (format!("{:?}", opcode), count))
+            .collect();
+        tables.push(("Opcodes", opcodes));
+        for (title, counts) in tables {
+            out += &format!("\n{}:\n      count       %  name\n", title);
+            for (name, count) in counts.into_iter().take(top) {
+                out += &format!("{:>11} {:>6.2}%  {}\n", count, percent(count), name);
+            }
+        }
+        out
+    }
+}
+
+// The closest code label at or before the address
+fn closest_label(symbols: &[Symbol], address: usize) -> Option<&Symbol> {
+    symbols
+        .iter()
+        .filter(|s| s.section == Some(SectionKind::Code) && s.value as usize <= address)
+        .max_by_key(|s| s.value)
+}
+
+fn label_name(symbols: &[Symbol], address: usize) -> String {
+    closest_label(symbols, address)
+        .map(|symbol| symbol.name.clone())
+        .unwrap_or_else(|| UNKNOWN.to_owned())
+}
+
+// Like `<loop+4>`
+fn label_at(symbols: &[Symbol], address: usize) -> String {
+    match closest_label(symbols, address) {
+        Some(symbol) if symbol.value as usize == address => format!("<{}>", symbol.name),
+        Some(symbol) => format!("<{}+{}>", symbol.name, address - symbol.value as usize),
+        None => UNKNOWN.to_owned(),
+    }
+}
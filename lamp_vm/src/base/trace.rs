@@ -0,0 +1,226 @@
+// Records the instructions a program executes, to look at what it did afterwards.
+// Each instruction gives one line: its number, its address, its text and what it
+// changed. Registers and flags are compared before and after it. No instruction
+// writes the data memory, so it isn't.
+use super::vm::{VMResult, VM};
+use lamp_common::object::Symbol;
+use lamp_common::op::{get_op, Opcode};
+use lamp_disasm::{decode, decode_items, format_line, Options};
+use serde_json::{json, Map, Value};
+use std::io::Write;
+use std::ops::Range;
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TraceFormat {
+    // One JSON object per line
+    Jsonl,
+    Text,
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "jsonl" => Ok(Self::Jsonl),
+            "text" => Ok(Self::Text),
+            _ => Err(format!("unknown trace format \'{}\'", s)),
+        }
+    }
+}
+
+pub fn parse_opcode(text: &str) -> Result<Opcode, String> {
+    get_op(text.to_owned()).map_err(|_| format!("unknown opcode \'{}\'", text))
+}
+
+// `start..end`, the end being excluded. Both are addresses or labels of the code.
+pub fn parse_range(text: &str, symbols: &[Symbol]) -> Result<Range<usize>, String> {
+    let address = |text: &str| {
+        let text = text.trim();
+        let number = match text.strip_prefix("0x") {
+            Some(hex) => usize::from_str_radix(hex, 16).ok(),
+            None => text.parse::<usize>().ok(),
+        };
+        number
+            .or_else(|| {
+                symbols
+                    .iter()
+                    .find(|s| s.name == text && s.section.is_some())
+                    .map(|s| s.value as usize)
+            })
+            .ok_or_else(|| format!("\'{}\' is neither an address nor a label", text))
+    };
+    match text.split_once("..") {
+        Some((start, end)) => Ok(address(start)?..address(end)?),
+        None => Err(format!("invalid range \'{}\': expected start..end", text)),
+    }
+}
+
+// Which instructions are written. Every instruction is, without ranges nor opcodes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TraceFilter {
+    pub ranges: Vec<Range<usize>>,
+    pub opcodes: Vec<Opcode>,
+}
+
+impl TraceFilter {
+    pub fn matches(&self, pc: usize, opcode: Option<Opcode>) -> bool {
+        (self.ranges.is_empty() || self.ranges.iter().any(|range| range.contains(&pc)))
+            && (self.opcodes.is_empty() || opcode.is_some_and(|op| self.opcodes.contains(&op)))
+    }
+}
+
+// The state an instruction can change
+struct State {
+    registers: [i32; 32],
+    eq_flag: bool,
+    modulo_remainder: i32,
+}
+
+impl State {
+    fn of(vm: &VM) -> Self {
+        Self {
+            registers: *vm.get_registers(),
+            eq_flag: vm.get_eq_flag(),
+            modulo_remainder: vm.get_modulo_remainder(),
+        }
+    }
+}
+
+// One line of the trace
+struct Record {
+    number: u64,
+    pc: usize,
+    text: String,
+    registers: Vec<(u8, i32)>,
+    eq_flag: Option<bool>,
+    modulo_remainder: Option<i32>,
+    fault: Option<String>,
+}
+
+impl Record {
+    fn to_json(&self) -> Value {
+        let mut record = Map::new();
+        record.insert("seq".to_owned(), json!(self.number));
+        record.insert("pc".to_owned(), json!(self.pc));
+        record.insert("instruction".to_owned(), json!(self.text));
+        if !self.registers.is_empty() {
+            let registers: Map<String, Value> = self
+                .registers
+                .iter()
+                .map(|(n, value)| (format!("r{}", n), json!(value)))
+                .collect();
+            record.insert("registers".to_owned(), Value::Object(registers));
+        }
+        if let Some(eq_flag) = self.eq_flag {
+            record.insert("eq_flag".to_owned(), json!(eq_flag));
+        }
+        if let Some(remainder) = self.modulo_remainder {
+            record.insert("modulo_remainder".to_owned(), json!(remainder));
+        }
+        if let Some(fault) = &self.fault {
+            record.insert("fault".to_owned(), json!(fault));
+        }
+        Value::Object(record)
+    }
+
+    fn to_text(&self) -> String {
+        let mut changes = Vec::<String>::new();
+        for (n, value) in &self.registers {
+            changes.push(format!("r{}={}", n, value));
+        }
+        if let Some(eq_flag) = self.eq_flag {
+            changes.push(format!("eq={}", eq_flag));
+        }
+        if let Some(remainder) = self.modulo_remainder {
+            changes.push(format!("remainder={}", remainder));
+        }
+        if let Some(fault) = &self.fault {
+            changes.push(format!("fault: {}", fault));
+        }
+        format!(
+            "{:>8} {:#06x}  {:<24}{}",
+            self.number,
+            self.pc,
+            self.text,
+            changes.join(" ")
+        )
+        .trim_end()
+        .to_owned()
+    }
+}
+
+pub struct Tracer<W: Write> {
+    out: W,
+    format: TraceFormat,
+    filter: TraceFilter,
+    // Number of instructions executed, written or not
+    count: u64,
+}
+
+impl<W: Write> Tracer<W> {
+    pub fn new(out: W, format: TraceFormat, filter: TraceFilter) -> Self {
+        Self {
+            out,
+            format,
+            filter,
+            count: 0,
+        }
+    }
+
+    // Executes one instruction, and writes it when the filter lets it through
+    pub fn cycle(&mut self, vm: &mut VM) -> std::io::Result<VMResult> {
+        let pc = vm.get_pc();
+        let code = vm.get_program();
+        let opcode = decode(code, pc).map(|instruction| instruction.opcode);
+        let written = self.filter.matches(pc, opcode);
+        let text = if written {
+            decode_items(code, pc, 1)
+                .first()
+                .map(|item| format_line(item, code, &Options::default()))
+                .unwrap_or_default()
+                .trim()
+                .to_owned()
+        } else {
+            String::new()
+        };
+
+        let before = State::of(vm);
+        let result = vm.cycle();
+        self.count += 1;
+        if !written {
+            return Ok(result);
+        }
+        let after = State::of(vm);
+        let record = Record {
+            number: self.count,
+            pc,
+            text,
+            registers: (0..32u8)
+                .filter(|n| before.registers[*n as usize] != after.registers[*n as usize])
+                .map(|n| (n, after.registers[n as usize]))
+                .collect(),
+            eq_flag: Some(after.eq_flag).filter(|flag| *flag != before.eq_flag),
+            modulo_remainder: Some(after.modulo_remainder)
+                .filter(|remainder| *remainder != before.modulo_remainder),
+            fault: result.err().map(|e| e.to_string()),
+        };
+        match self.format {
+            TraceFormat::Jsonl => writeln!(self.out, "{}", record.to_json())?,
+            TraceFormat::Text => writeln!(self.out, "{}", record.to_text())?,
+        }
+        Ok(result)
+    }
+
+    // Runs the program to its end, or until it faults, like VM::run
+    pub fn run(&mut self, vm: &mut VM) -> std::io::Result<VMResult> {
+        loop {
+            let result = self.cycle(vm)?;
+            if result.is_err() || vm.get_pc() >= vm.get_program().len() {
+                self.out.flush()?;
+                return Ok(result);
+            }
+        }
+    }
+}
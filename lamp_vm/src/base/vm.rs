@@ -2,11 +2,16 @@ use super::opcodes::decode_opcode;
 use lamp_common::op::Opcode;
 use log::{error, info};
 
+#[derive(Clone)]
 pub struct VM {
     // The binary VM has to execute
     bin: Vec<u8>,
+    // The data memory, where rodata, data and bss sections are mapped
+    memory: Vec<u8>,
     // The program counter, it's utility is to remind where we are in the program
     pc: usize,
+    // Where the instruction being executed (or the last one) starts, to locate faults
+    instruction_pc: usize,
     // Registers used to store i32 values the program needs
     registers: [i32; 32],
     // When modulo operation is done, the remainder is pushed here
@@ -21,27 +26,51 @@ pub type VMResult = Result<i32, VMError>;
 pub enum VMError {
     InvalidOpcodeError,
     UnexpectedTokenError,
+    DivisionByZero,
+    // The code ends in the middle of an instruction
+    EndOfCode,
+    // An operand names a register past the last one
+    InvalidRegister(u8),
     // More errors will be added
 }
 
+impl std::fmt::Display for VMError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidOpcodeError => write!(f, "Invalid opcode"),
+            Self::UnexpectedTokenError => write!(f, "Unexpected token"),
+            Self::DivisionByZero => write!(f, "Division by zero"),
+            Self::EndOfCode => write!(f, "Instruction cut by the end of the code"),
+            Self::InvalidRegister(index) => write!(f, "Invalid register {}", index),
+        }
+    }
+}
+
 impl VM {
     pub fn new(binary: Vec<u8>) -> Self {
+        Self::with_memory(binary, vec![], 0)
+    }
+
+    // Creates a VM from an already mapped program, see base::loader.
+    pub fn with_memory(binary: Vec<u8>, memory: Vec<u8>, entry: usize) -> Self {
         Self {
             bin: binary,
-            pc: 0,
+            memory,
+            pc: entry,
+            instruction_pc: entry,
             registers: [0; 32],
             modulo_remainder: 0,
             eq_flag: false,
         }
     }
 
-    // Runs the full binary.
+    // Runs the full binary, or until an error happens.
     pub fn run(&mut self) -> VMResult {
         let mut result;
         loop {
             result = self.cycle();
 
-            if self.pc >= self.bin.len() {
+            if result.is_err() || self.pc >= self.bin.len() {
                 break;
             }
         }
@@ -54,17 +83,16 @@ impl VM {
     // -The opcode execution
     // - Error handling
     pub fn cycle(&mut self) -> VMResult {
-        let opcode = self.next_8_bits();
-        let outcoming_result: VMResult;
+        self.instruction_pc = self.pc;
+        let opcode = self.next_8_bits()?;
 
         match decode_opcode(opcode) {
-            Ok(opcode) => outcoming_result = self.execute_instruction(opcode),
+            Ok(opcode) => self.execute_instruction(opcode),
             Err(e) => {
                 error!("VM's error happened. Aborting. \n {:?}", e);
-                return Err(e);
+                Err(e)
             }
         }
-        outcoming_result
     }
 
     // Executes the given opcode.
@@ -73,67 +101,71 @@ impl VM {
         match opcode {
             // Arithmetical instructions
             Opcode::ADD => {
-                let val_1 = self.registers[self.next_8_bits() as usize];
-                let val_2 = self.registers[self.next_8_bits() as usize];
-                let result_register = self.next_8_bits();
+                let val_1 = self.next_register_value()?;
+                let val_2 = self.next_register_value()?;
+                let result_register = self.next_register()?;
                 self.set_register_value(result_register, val_1 + val_2);
             }
             Opcode::SUB => {
-                let val_1 = self.registers[self.next_8_bits() as usize];
-                let val_2 = self.registers[self.next_8_bits() as usize];
-                let result_register = self.next_8_bits();
+                let val_1 = self.next_register_value()?;
+                let val_2 = self.next_register_value()?;
+                let result_register = self.next_register()?;
                 self.set_register_value(result_register, val_1 - val_2);
             }
             Opcode::MUL => {
-                let val_1 = self.registers[self.next_8_bits() as usize];
-                let val_2 = self.registers[self.next_8_bits() as usize];
-                let result_register = self.next_8_bits();
+                let val_1 = self.next_register_value()?;
+                let val_2 = self.next_register_value()?;
+                let result_register = self.next_register()?;
                 self.set_register_value(result_register, val_1 * val_2);
             }
             Opcode::MOD => {
-                let val_1 = self.registers[self.next_8_bits() as usize];
-                let val_2 = self.registers[self.next_8_bits() as usize];
-                let result_register = self.next_8_bits();
+                let val_1 = self.next_register_value()?;
+                let val_2 = self.next_register_value()?;
+                let result_register = self.next_register()?;
+                if val_2 == 0 {
+                    error!("Division by zero at {:#x}", self.instruction_pc);
+                    return Err(VMError::DivisionByZero);
+                }
                 self.set_register_value(result_register, val_1 / val_2);
                 self.modulo_remainder = val_1 % val_2;
             }
             Opcode::INC => {
-                let to_inc = self.next_8_bits() as usize;
+                let to_inc = self.next_register()? as usize;
                 self.registers[to_inc] += 1;
             }
             Opcode::DEC => {
-                let to_dec = self.next_8_bits() as usize;
+                let to_dec = self.next_register()? as usize;
                 self.registers[to_dec] -= 1;
             }
             // Control Flow instructions
             Opcode::EQ => {
-                let reg_1 = self.next_8_bits() as usize;
-                let reg_2 = self.next_8_bits() as usize;
+                let reg_1 = self.next_register()? as usize;
+                let reg_2 = self.next_register()? as usize;
                 self.eq_flag = self.registers[reg_1] == self.registers[reg_2];
             }
             Opcode::NEQ => {
-                let reg_1 = self.next_8_bits() as usize;
-                let reg_2 = self.next_8_bits() as usize;
+                let reg_1 = self.next_register()? as usize;
+                let reg_2 = self.next_register()? as usize;
                 self.eq_flag = self.registers[reg_1] != self.registers[reg_2];
             }
             Opcode::GT => {
-                let reg_1 = self.next_8_bits() as usize;
-                let reg_2 = self.next_8_bits() as usize;
+                let reg_1 = self.next_register()? as usize;
+                let reg_2 = self.next_register()? as usize;
                 self.eq_flag = self.registers[reg_1] > self.registers[reg_2];
             }
             Opcode::GTE => {
-                let reg_1 = self.next_8_bits() as usize;
-                let reg_2 = self.next_8_bits() as usize;
+                let reg_1 = self.next_register()? as usize;
+                let reg_2 = self.next_register()? as usize;
                 self.eq_flag = self.registers[reg_1] >= self.registers[reg_2];
             }
             Opcode::LT => {
-                let reg_1 = self.next_8_bits() as usize;
-                let reg_2 = self.next_8_bits() as usize;
+                let reg_1 = self.next_register()? as usize;
+                let reg_2 = self.next_register()? as usize;
                 self.eq_flag = self.registers[reg_1] < self.registers[reg_2];
             }
             Opcode::LTE => {
-                let reg_1 = self.next_8_bits() as usize;
-                let reg_2 = self.next_8_bits() as usize;
+                let reg_1 = self.next_register()? as usize;
+                let reg_2 = self.next_register()? as usize;
                 self.eq_flag = self.registers[reg_1] <= self.registers[reg_2];
             }
             Opcode::HLT => {
@@ -141,21 +173,20 @@ impl VM {
             }
             Opcode::NOP => {
                 info!("NOP Opcode encountered, doing nothing.");
-                self.pc += 1;
             }
             Opcode::LOAD => {
-                let register = self.next_8_bits();
-                let value = self.next_16_bits() as i32;
+                let register = self.next_register()?;
+                let value = self.next_16_bits()? as i32;
                 self.set_register_value(register, value);
             }
             Opcode::JMP => {
-                let register = self.next_8_bits();
+                let register = self.next_register()?;
                 let addr = *self.get_register(register);
                 self.pc = addr as usize;
             }
 
             Opcode::MODR => {
-                let register = self.next_8_bits();
+                let register = self.next_register()?;
                 self.set_register_value(register, self.modulo_remainder);
             }
         }
@@ -199,20 +230,84 @@ impl VM {
         }
     }
     // Grabs next 8 bits of the VM's binary
-    // Stupid out of bounds error detected; should be fixed.
-    pub fn next_8_bits(&mut self) -> u8 {
+    pub fn next_8_bits(&mut self) -> Result<u8, VMError> {
+        let byte = *self.bin.get(self.pc).ok_or(VMError::EndOfCode)?;
         self.pc += 1;
-        self.bin[self.pc - 1]
+        Ok(byte)
     }
 
     // Grabs next 16 bytes of the VM's binary
-    fn next_16_bits(&mut self) -> u16 {
-        self.pc += 2;
-        ((u16::from(self.bin[self.pc - 2])) << 8) | u16::from(self.bin[self.pc - 1])
+    fn next_16_bits(&mut self) -> Result<u16, VMError> {
+        let high = self.next_8_bits()?;
+        let low = self.next_8_bits()?;
+        Ok((u16::from(high) << 8) | u16::from(low))
+    }
+
+    // Grabs the next operand, a register's index
+    fn next_register(&mut self) -> Result<u8, VMError> {
+        match self.next_8_bits()? {
+            index if index < 32 => Ok(index),
+            index => {
+                error!("Invalid register {} at {:#x}", index, self.instruction_pc);
+                Err(VMError::InvalidRegister(index))
+            }
+        }
+    }
+
+    // The value of the register given by the next operand
+    fn next_register_value(&mut self) -> Result<i32, VMError> {
+        let index = self.next_register()?;
+        Ok(self.registers[index as usize])
+    }
+
+    pub fn get_pc(&self) -> usize {
+        self.pc
+    }
+
+    // Address of the instruction executed last, or being executed when an error happened
+    pub fn get_instruction_pc(&self) -> usize {
+        self.instruction_pc
+    }
+
+    // The code being executed
+    pub fn get_program(&self) -> &[u8] {
+        &self.bin
+    }
+
+    pub fn get_program_mut(&mut self) -> &mut [u8] {
+        &mut self.bin
+    }
+
+    pub fn get_memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn get_memory_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+
+    pub fn get_registers(&self) -> &[i32; 32] {
+        &self.registers
+    }
+
+    pub fn get_eq_flag(&self) -> bool {
+        self.eq_flag
+    }
+
+    pub fn set_eq_flag(&mut self, value: bool) {
+        self.eq_flag = value;
+    }
+
+    pub fn get_modulo_remainder(&self) -> i32 {
+        self.modulo_remainder
+    }
+
+    pub fn set_modulo_remainder(&mut self, value: i32) {
+        self.modulo_remainder = value;
     }
 
     pub fn set_pc(&mut self, new_pc: usize) -> usize {
-        if new_pc > self.bin.len() {
+        if new_pc < self.bin.len() {
             self.pc = new_pc;
             0
         } else {
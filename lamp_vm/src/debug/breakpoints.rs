@@ -0,0 +1,492 @@
+use super::expr::{Expr, ExprError};
+use super::memory::{parse_address, Space};
+use super::session::DebugSession;
+use super::source::SourceMap;
+use crate::base::vm::VM;
+use lamp_common::container::SectionKind;
+use lamp_common::object::Symbol;
+use lamp_common::op::register_accesses;
+use lamp_common::register::{register_name_with, RegisterAlias};
+use lamp_disasm::{decode, Operand as DecodedOperand};
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+// Different types of errors breakpoints can cause
+#[derive(Debug, Clone, PartialEq)]
+pub enum BreakpointError {
+    UnknownLabel(String),
+    // File and line
+    NoCodeAtLine(String, u32),
+    NoDebugInfo,
+    OutOfCode(usize),
+    InvalidLocation(String),
+    // The condition, and what is wrong with it
+    InvalidCondition(String, ExprError),
+    InvalidWatch(String),
+    ReadWatchOnMemory,
+    // Address and size
+    OutOfMemory(usize, usize),
+    UnknownBreakpoint(usize),
+}
+
+impl std::fmt::Display for BreakpointError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownLabel(name) => write!(f, "No label named \'{}\'", name),
+            Self::NoCodeAtLine(file, line) => {
+                write!(f, "No code at or after line {} of {}", line, file)
+            }
+            Self::NoDebugInfo => write!(
+                f,
+                "The binary has no debug info, assemble it with -g to use file:line"
+            ),
+            Self::OutOfCode(address) => {
+                write!(f, "Address {:#x} is outside of the code", address)
+            }
+            Self::InvalidLocation(text) => write!(
+                f,
+                "Invalid location \'{}\': expected an address, a label or file:line",
+                text
+            ),
+            Self::InvalidCondition(text, e) => {
+                write!(f, "Invalid condition \'{}\': {}", text, e)
+            }
+            Self::InvalidWatch(text) => write!(
+                f,
+                "Invalid watch '{}': expected a register, or mem <address | label> [1 | 2 | 4]",
+                text
+            ),
+            Self::ReadWatchOnMemory => write!(
+                f,
+                "Read watchpoints only support registers, no instruction reads the data memory"
+            ),
+            Self::OutOfMemory(address, size) => write!(
+                f,
+                "Address {:#x} ({} bytes) is outside of the data memory",
+                address, size
+            ),
+            Self::UnknownBreakpoint(id) => write!(f, "No breakpoint number {}", id),
+        }
+    }
+}
+
+// Decimal, or hexadecimal with 0x
+pub fn parse_number(text: &str) -> Option<usize> {
+    match text.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => text.parse::<usize>().ok(),
+    }
+}
+
+// A register value: decimal or hexadecimal, possibly negative
+pub fn parse_value(text: &str) -> Option<i32> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = i64::try_from(parse_number(digits)?).ok()?;
+    let value = if negative {
+        value.checked_neg()?
+    } else {
+        value
+    };
+    i32::try_from(value).ok()
+}
+
+// Finds the address of `<address|label|file:line>`
+pub fn resolve_location(
+    text: &str,
+    vm: &VM,
+    symbols: &[Symbol],
+    source: Option<&SourceMap>,
+) -> Result<usize, BreakpointError> {
+    let address = if let Some(address) = parse_number(text) {
+        address
+    } else if let Some((file, line)) = text.rsplit_once(':') {
+        let line = line
+            .parse::<u32>()
+            .map_err(|_| BreakpointError::InvalidLocation(text.to_owned()))?;
+        let source = source.ok_or(BreakpointError::NoDebugInfo)?;
+        address_of_line(source, file, line)
+            .ok_or_else(|| BreakpointError::NoCodeAtLine(file.to_owned(), line))?
+    } else {
+        symbols
+            .iter()
+            .find(|s| s.name == text && s.section == Some(SectionKind::Code))
+            .map(|s| s.value as usize)
+            .ok_or_else(|| BreakpointError::UnknownLabel(text.to_owned()))?
+    };
+    if address >= vm.get_program().len() {
+        return Err(BreakpointError::OutOfCode(address));
+    }
+    Ok(address)
+}
+
+// The first instruction of a line, or of the next line with code.
+// A line invoking a macro has the code of the macro.
+fn address_of_line(source: &SourceMap, file: &str, line: u32) -> Option<usize> {
+    // The file can be given without its directories
+    let same_file = |path: &str| path == file || path.ends_with(&format!("/{}", file));
+    let lines = &source.info().lines;
+
+    let exact = lines.iter().find(|entry| {
+        (same_file(&entry.location.file) && entry.location.line == line)
+            || entry
+                .expansions
+                .iter()
+                .any(|e| same_file(&e.file) && e.line == line)
+    });
+    let next = || {
+        lines
+            .iter()
+            .filter(|entry| same_file(&entry.location.file) && entry.location.line > line)
+            .min_by_key(|entry| (entry.location.line, entry.address))
+    };
+    exact.or_else(next).map(|entry| entry.address as usize)
+}
+
+// An expression stopping the program when it isn't 0, like `r3 == 10`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    expr: Expr,
+}
+
+impl Condition {
+    // Labels of the symbol table and register aliases can be used in the condition
+    pub fn parse(
+        text: &str,
+        symbols: &[Symbol],
+        aliases: &[RegisterAlias],
+    ) -> Result<Self, BreakpointError> {
+        Expr::parse(text, symbols, aliases)
+            .map(|expr| Self { expr })
+            .map_err(|e| BreakpointError::InvalidCondition(text.trim().to_owned(), e))
+    }
+
+    // A condition that can't be evaluated, reading outside of the memory for example,
+    // holds so the program stops where it went wrong
+    pub fn holds(&self, vm: &VM) -> bool {
+        !matches!(self.expr.evaluate(vm), Ok(0))
+    }
+
+    // The registers of the condition and their value, like "r3 = 4, r5 = 0"
+    pub fn register_values(&self, vm: &VM) -> String {
+        let values: Vec<String> = self
+            .expr
+            .registers()
+            .iter()
+            .map(|(n, name)| format!("{} = {}", name, vm.get_register(*n)))
+            .collect();
+        values.join(", ")
+    }
+}
+
+impl FromStr for Condition {
+    type Err = BreakpointError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s, &[], &[])
+    }
+}
+
+impl std::fmt::Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expr)
+    }
+}
+
+// What a watchpoint looks at
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum WatchTarget {
+    Register(u8),
+    // `size` bytes of the data memory, read as a big-endian number
+    Memory { address: usize, size: usize },
+}
+
+impl WatchTarget {
+    // `r5`, or `mem 0x100` followed by an optional size. The address can be a data
+    // label, like for x.
+    pub fn parse(
+        args: &[&str],
+        kind: WatchKind,
+        session: &DebugSession,
+    ) -> Result<Self, BreakpointError> {
+        let invalid = || BreakpointError::InvalidWatch(args.join(" "));
+        let target = match args {
+            [register] => Self::Register(session.parse_register(register).ok_or_else(invalid)?),
+            ["mem", address] | ["mem", address, _] => {
+                let size = match args.get(2) {
+                    Some(size) => parse_number(size).ok_or_else(invalid)?,
+                    None => 1,
+                };
+                if ![1, 2, 4].contains(&size) {
+                    return Err(invalid());
+                }
+                if kind == WatchKind::Read {
+                    return Err(BreakpointError::ReadWatchOnMemory);
+                }
+                match parse_address(address, Space::Data, session)? {
+                    (Space::Data, address) => Self::Memory { address, size },
+                    (Space::Code, _) => return Err(invalid()),
+                }
+            }
+            _ => return Err(invalid()),
+        };
+        if let Self::Memory { address, size } = target {
+            if target.value(&session.vm).is_none() {
+                return Err(BreakpointError::OutOfMemory(address, size));
+            }
+        }
+        Ok(target)
+    }
+
+    pub fn value(self, vm: &VM) -> Option<i64> {
+        match self {
+            Self::Register(n) => Some(i64::from(*vm.get_register(n))),
+            Self::Memory { address, size } => {
+                let bytes = vm.get_memory().get(address..address + size)?;
+                Some(bytes.iter().fold(0, |value, b| value << 8 | i64::from(*b)))
+            }
+        }
+    }
+
+    // Registers are shown in decimal, memory in hexadecimal
+    pub fn format_value(self, value: i64) -> String {
+        match self {
+            Self::Register(_) => value.to_string(),
+            Self::Memory { .. } => format!("{:#x}", value),
+        }
+    }
+
+    // How the target is printed, registers with the aliases of the program
+    pub fn name(self, aliases: &[RegisterAlias]) -> String {
+        match self {
+            Self::Register(n) => register_name_with(n, aliases),
+            Self::Memory { address, size: 1 } => format!("mem {:#06x}", address),
+            Self::Memory { address, size } => format!("mem {:#06x} ({} bytes)", address, size),
+        }
+    }
+}
+
+impl std::fmt::Display for WatchTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name(&[]))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum WatchKind {
+    // Stops when the value changes
+    Change,
+    // Stops when an instruction reads the value
+    Read,
+}
+
+#[derive(Debug, Clone)]
+pub struct Watchpoint {
+    pub id: usize,
+    pub target: WatchTarget,
+    pub kind: WatchKind,
+    pub hits: u32,
+    // The value before the instruction being executed
+    value: Option<i64>,
+}
+
+// A watchpoint triggered by the instruction at `pc`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WatchHit {
+    pub id: usize,
+    pub target: WatchTarget,
+    pub kind: WatchKind,
+    pub old: i64,
+    pub new: i64,
+    pub pc: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Breakpoint {
+    pub id: usize,
+    pub address: usize,
+    // What the user wrote to place it
+    pub location: String,
+    pub condition: Option<Condition>,
+    // Number of hits to let go before stopping
+    pub ignore_count: u32,
+    pub hits: u32,
+}
+
+pub struct Breakpoints {
+    list: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    // Registers read by the instruction being executed, when something is watched
+    reads: Vec<u8>,
+    // Shared by breakpoints and watchpoints
+    next_id: usize,
+    // Whether the program stops at a faulting instruction instead of terminating
+    pub catch_faults: bool,
+}
+
+impl Breakpoints {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            list: vec![],
+            watchpoints: vec![],
+            reads: vec![],
+            next_id: 1,
+            catch_faults: false,
+        }
+    }
+
+    // Adds a breakpoint and gives its number
+    pub fn add(&mut self, address: usize, location: &str, condition: Option<Condition>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.list.push(Breakpoint {
+            id,
+            address,
+            location: location.to_owned(),
+            condition,
+            ignore_count: 0,
+            hits: 0,
+        });
+        id
+    }
+
+    // Adds a watchpoint and gives its number
+    pub fn add_watch(&mut self, target: WatchTarget, kind: WatchKind) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.watchpoints.push(Watchpoint {
+            id,
+            target,
+            kind,
+            hits: 0,
+            value: None,
+        });
+        id
+    }
+
+    // Deletes a breakpoint or a watchpoint
+    pub fn delete(&mut self, id: usize) -> Result<(), BreakpointError> {
+        let len = self.list.len() + self.watchpoints.len();
+        self.list.retain(|b| b.id != id);
+        self.watchpoints.retain(|w| w.id != id);
+        if self.list.len() + self.watchpoints.len() == len {
+            return Err(BreakpointError::UnknownBreakpoint(id));
+        }
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.list.clear();
+        self.watchpoints.clear();
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Result<&mut Breakpoint, BreakpointError> {
+        self.list
+            .iter_mut()
+            .find(|b| b.id == id)
+            .ok_or(BreakpointError::UnknownBreakpoint(id))
+    }
+
+    pub fn list(&self) -> &[Breakpoint] {
+        &self.list
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    // Forgets the hits, when the program is run again
+    pub fn reset_hits(&mut self) {
+        for breakpoint in self.list.iter_mut() {
+            breakpoint.hits = 0;
+        }
+        for watchpoint in self.watchpoints.iter_mut() {
+            watchpoint.hits = 0;
+        }
+    }
+
+    // Watching costs a look at the instruction before running it and at the values after,
+    // so it is only done when there are watchpoints
+    pub fn is_watching(&self) -> bool {
+        !self.watchpoints.is_empty()
+    }
+
+    // Remembers the watched values and what the instruction at the pc reads
+    pub fn watch_before(&mut self, vm: &VM) {
+        for watchpoint in self.watchpoints.iter_mut() {
+            watchpoint.value = watchpoint.target.value(vm);
+        }
+        self.reads.clear();
+        // No instruction reads the data memory yet, only registers can be seen being read
+        if let Some(instruction) = decode(vm.get_program(), vm.get_pc()) {
+            let (reads, _) = register_accesses(instruction.opcode);
+            for i in reads {
+                if let Some(DecodedOperand::Register(n)) = instruction.operands.get(*i) {
+                    self.reads.push(*n);
+                }
+            }
+        }
+    }
+
+    // The first watchpoint the instruction just executed triggered.
+    // Every triggered watchpoint counts a hit.
+    pub fn watch_after(&mut self, vm: &VM) -> Option<WatchHit> {
+        let mut stop = None;
+        for watchpoint in self.watchpoints.iter_mut() {
+            let (old, new) = match (watchpoint.value, watchpoint.target.value(vm)) {
+                (Some(old), Some(new)) => (old, new),
+                _ => continue,
+            };
+            let triggered = match (watchpoint.kind, watchpoint.target) {
+                (WatchKind::Change, _) => old != new,
+                (WatchKind::Read, WatchTarget::Register(n)) => self.reads.contains(&n),
+                // Refused by WatchTarget::parse, nothing reads the data memory
+                (WatchKind::Read, WatchTarget::Memory { .. }) => false,
+            };
+            if !triggered {
+                continue;
+            }
+            watchpoint.hits += 1;
+            stop = stop.or(Some(WatchHit {
+                id: watchpoint.id,
+                target: watchpoint.target,
+                kind: watchpoint.kind,
+                old,
+                new,
+                pc: vm.get_instruction_pc(),
+            }));
+        }
+        stop
+    }
+
+    // The breakpoint at the pc whose condition holds, without counting a hit.
+    // Used when going back in time, where ignore counts don't apply.
+    pub fn stops_at(&self, vm: &VM) -> Option<usize> {
+        self.list
+            .iter()
+            .find(|b| b.address == vm.get_pc() && b.condition.as_ref().is_none_or(|c| c.holds(vm)))
+            .map(|b| b.id)
+    }
+
+    // Whether the program should stop at its pc: gives the breakpoint stopping it.
+    // Breakpoints whose condition holds count a hit, even when they are ignored.
+    pub fn hit(&mut self, vm: &VM) -> Option<usize> {
+        let pc = vm.get_pc();
+        let mut stop = None;
+        for breakpoint in self.list.iter_mut().filter(|b| b.address == pc) {
+            if !breakpoint.condition.as_ref().is_none_or(|c| c.holds(vm)) {
+                continue;
+            }
+            breakpoint.hits += 1;
+            if breakpoint.ignore_count > 0 {
+                breakpoint.ignore_count -= 1;
+            } else if stop.is_none() {
+                stop = Some(breakpoint.id);
+            }
+        }
+        stop
+    }
+}
@@ -0,0 +1,74 @@
+use super::command_base::DebugCommand;
+use crate::debug::breakpoints::resolve_location;
+use crate::debug::patch::assemble;
+use crate::debug::session::DebugSession;
+use crate::debug::source::disassemble;
+use lamp_common::op::{instruction_size, Opcode};
+use lamp_disasm::decode;
+use log::error;
+
+pub struct AsmCommand;
+
+impl DebugCommand for AsmCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        if args.len() < 3 {
+            self.display_error();
+            return 1;
+        }
+        let address = match resolve_location(
+            args[1],
+            &session.vm,
+            &session.symbols,
+            session.source.as_ref(),
+        ) {
+            Ok(address) => address,
+            Err(e) => {
+                error!("{}", e);
+                return 1;
+            }
+        };
+        // The instruction can be quoted
+        let text = args[2..].join(" ");
+        let text = text.trim_matches('"');
+        let mut bytes = match assemble(text, address, &session.symbols) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                error!("{}", e);
+                return 1;
+            }
+        };
+
+        // What is left of a longer instruction becomes NOPs, so the next one stays in place
+        let code = session.vm.get_program();
+        let replaced =
+            decode(code, address).map(|instruction| instruction_size(instruction.opcode));
+        if let Some(size) = replaced {
+            if size > bytes.len() {
+                bytes.resize(size, Opcode::NOP as u8);
+            }
+        }
+        let old = disassemble(address, code);
+        if let Err(e) = session.patch_code(address, &bytes) {
+            error!("{}", e);
+            return 1;
+        }
+        outputln!(session, "{:#06x}: {} -> {}", address, old, text.trim());
+        if replaced.is_some_and(|size| size < bytes.len()) {
+            outputln!(
+                session,
+                "The new instruction is longer than the old one, the next one was overwritten."
+            );
+        }
+        0
+    }
+
+    fn name(&self) -> &str {
+        "asm"
+    }
+    fn description(&self) -> &str {
+        "Assembles an instruction in place of the code at an address. The change is kept by \"run\"."
+    }
+    fn syntax(&self) -> &str {
+        "asm <address | label | file:line> \"<instruction>\""
+    }
+}
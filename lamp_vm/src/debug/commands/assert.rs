@@ -0,0 +1,45 @@
+use super::command_base::DebugCommand;
+use crate::debug::breakpoints::Condition;
+use crate::debug::session::DebugSession;
+use log::error;
+
+pub struct AssertCommand;
+
+impl DebugCommand for AssertCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        if args.len() < 2 {
+            self.display_error();
+            return 1;
+        }
+        let condition =
+            match Condition::parse(&args[1..].join(" "), &session.symbols, session.aliases()) {
+                Ok(condition) => condition,
+                Err(e) => {
+                    error!("{}", e);
+                    return 1;
+                }
+            };
+        if condition.holds(&session.vm) {
+            outputln!(session, "Assertion holds: {}", condition);
+            0
+        } else {
+            error!(
+                "Assertion failed: {} ({}), at {}",
+                condition,
+                condition.register_values(&session.vm),
+                session.describe_at(session.vm.get_pc())
+            );
+            1
+        }
+    }
+
+    fn name(&self) -> &str {
+        "assert"
+    }
+    fn description(&self) -> &str {
+        "Checks a condition, an expression like for print, and fails when it is 0. Stops scripts, see --script."
+    }
+    fn syntax(&self) -> &str {
+        "assert <expression>"
+    }
+}
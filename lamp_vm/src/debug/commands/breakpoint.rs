@@ -0,0 +1,69 @@
+use super::command_base::DebugCommand;
+use crate::debug::breakpoints::{resolve_location, Condition};
+use crate::debug::session::DebugSession;
+use log::error;
+
+pub struct BreakCommand;
+
+impl DebugCommand for BreakCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        let location = match args.get(1) {
+            Some(location) if !location.is_empty() => *location,
+            _ => {
+                self.display_error();
+                return 1;
+            }
+        };
+        let condition = match args.get(2) {
+            None => None,
+            Some(&"if") => {
+                match Condition::parse(&args[3..].join(" "), &session.symbols, session.aliases()) {
+                    Ok(condition) => Some(condition),
+                    Err(e) => {
+                        error!("{}", e);
+                        return 1;
+                    }
+                }
+            }
+            Some(_) => {
+                self.display_error();
+                return 1;
+            }
+        };
+
+        match resolve_location(
+            location,
+            &session.vm,
+            &session.symbols,
+            session.source.as_ref(),
+        ) {
+            Ok(address) => {
+                let id = session.breakpoints.add(address, location, condition);
+                outputln!(
+                    session,
+                    "Breakpoint {} at {}",
+                    id,
+                    session.describe(address)
+                );
+                0
+            }
+            Err(e) => {
+                error!("{}", e);
+                1
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "break"
+    }
+    fn aliases(&self) -> &[&str] {
+        &["b"]
+    }
+    fn description(&self) -> &str {
+        "Stops the program when it reaches an address, a label or a source line, optionally only when a condition holds."
+    }
+    fn syntax(&self) -> &str {
+        "break <address | label | file:line> [if <expression>]"
+    }
+}
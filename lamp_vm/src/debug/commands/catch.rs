@@ -0,0 +1,38 @@
+use super::command_base::DebugCommand;
+use crate::debug::session::DebugSession;
+
+pub struct CatchCommand;
+
+impl DebugCommand for CatchCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        match args.get(1) {
+            Some(&"faults") => {
+                session.breakpoints.catch_faults = true;
+                outputln!(
+                    session,
+                    "The program will stop at the instruction causing a fault."
+                );
+                0
+            }
+            Some(&"off") => {
+                session.breakpoints.catch_faults = false;
+                outputln!(session, "Faults will terminate the program.");
+                0
+            }
+            _ => {
+                self.display_error();
+                1
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "catch"
+    }
+    fn description(&self) -> &str {
+        "Stops at faulting instructions instead of terminating the program, so they can be inspected."
+    }
+    fn syntax(&self) -> &str {
+        "catch <faults | off>"
+    }
+}
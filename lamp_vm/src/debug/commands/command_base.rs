@@ -1,9 +1,17 @@
-use crate::base::vm::VM;
+use crate::debug::session::DebugSession;
 use log::error;
 
 pub trait DebugCommand {
-    fn execute(&self, vm: &mut VM, args: Vec<&str>) -> usize;
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize;
     fn name(&self) -> &str;
+    // Other names the command can be called with, usually shorter
+    fn aliases(&self) -> &[&str] {
+        &[]
+    }
+    // Whether an empty line runs the command again, for commands moving the program forward
+    fn repeatable(&self) -> bool {
+        false
+    }
     fn description(&self) -> &str;
     fn syntax(&self) -> &str;
     fn display_error(&self) {
@@ -0,0 +1,37 @@
+use super::command_base::DebugCommand;
+use crate::debug::session::{DebugSession, Stop};
+use log::error;
+
+pub struct ContinueCommand;
+
+impl DebugCommand for ContinueCommand {
+    #[allow(unused_variables)]
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        if session.is_terminated() {
+            error!("The program is not running. Use \"run\" to start it again.");
+            return 1;
+        }
+        let stop = session.resume();
+        session.report(stop);
+        match stop {
+            Stop::Fault(_) => 1,
+            _ => 0,
+        }
+    }
+
+    fn name(&self) -> &str {
+        "continue"
+    }
+    fn aliases(&self) -> &[&str] {
+        &["c"]
+    }
+    fn repeatable(&self) -> bool {
+        true
+    }
+    fn description(&self) -> &str {
+        "Runs the program until a breakpoint, a fault or its end."
+    }
+    fn syntax(&self) -> &str {
+        "continue"
+    }
+}
@@ -0,0 +1,42 @@
+use super::command_base::DebugCommand;
+use crate::debug::breakpoints::parse_number;
+use crate::debug::session::DebugSession;
+use log::error;
+
+pub struct DeleteCommand;
+
+impl DebugCommand for DeleteCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        match args.get(1).map(|arg| parse_number(arg)) {
+            None => {
+                session.breakpoints.clear();
+                outputln!(session, "Deleted every breakpoint.");
+                0
+            }
+            Some(Some(id)) => match session.breakpoints.delete(id) {
+                Ok(()) => 0,
+                Err(e) => {
+                    error!("{}", e);
+                    1
+                }
+            },
+            Some(None) => {
+                self.display_error();
+                1
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "delete"
+    }
+    fn aliases(&self) -> &[&str] {
+        &["d"]
+    }
+    fn description(&self) -> &str {
+        "Deletes a breakpoint or a watchpoint, or all of them."
+    }
+    fn syntax(&self) -> &str {
+        "delete [breakpoint number]"
+    }
+}
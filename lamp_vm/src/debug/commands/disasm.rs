@@ -0,0 +1,57 @@
+use super::command_base::DebugCommand;
+use crate::debug::breakpoints::parse_number;
+use crate::debug::session::DebugSession;
+use lamp_disasm::{decode_items, format_line, Options};
+
+// Number of instructions shown when no count is given
+const DEFAULT_COUNT: usize = 10;
+
+pub struct DisasmCommand;
+
+impl DebugCommand for DisasmCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        let vm = &session.vm;
+        let count = match args.get(1).map(|arg| parse_number(arg)) {
+            Some(Some(count)) => count,
+            Some(None) => {
+                self.display_error();
+                return 1;
+            }
+            None => DEFAULT_COUNT,
+        };
+        let address = match args.get(2).map(|arg| parse_number(arg)) {
+            Some(Some(address)) => address,
+            Some(None) => {
+                self.display_error();
+                return 1;
+            }
+            None => vm.get_pc(),
+        };
+
+        let options = Options {
+            annotate: true,
+            pc: Some(vm.get_pc()),
+        };
+        for item in decode_items(vm.get_program(), address, count) {
+            outputln!(
+                session,
+                "{}",
+                format_line(&item, vm.get_program(), &options)
+            );
+        }
+        0
+    }
+
+    fn name(&self) -> &str {
+        "disasm"
+    }
+    fn aliases(&self) -> &[&str] {
+        &["dis"]
+    }
+    fn description(&self) -> &str {
+        "Disassembles the instructions from the given address, or from the program counter."
+    }
+    fn syntax(&self) -> &str {
+        "disasm [count] [address]"
+    }
+}
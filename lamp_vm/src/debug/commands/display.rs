@@ -0,0 +1,46 @@
+use super::command_base::DebugCommand;
+use super::print::parse_format;
+use crate::debug::expr::Expr;
+use crate::debug::session::DebugSession;
+use log::error;
+
+pub struct DisplayCommand;
+
+impl DebugCommand for DisplayCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        let format = match parse_format(args[0]) {
+            Ok(format) => format,
+            Err(e) => {
+                error!("{}", e);
+                return 1;
+            }
+        };
+        // Without an expression, shows the displays now
+        if args.len() < 2 {
+            session.show_displays();
+            return 0;
+        }
+        let expr = match Expr::parse(&args[1..].join(" "), &session.symbols, session.aliases()) {
+            Ok(expr) => expr,
+            Err(e) => {
+                error!("{}", e);
+                return 1;
+            }
+        };
+        let id = session.displays.add(expr, format);
+        if let Some(display) = session.displays.list().iter().find(|d| d.id == id) {
+            outputln!(session, "{}", display.show(&session.vm));
+        }
+        0
+    }
+
+    fn name(&self) -> &str {
+        "display"
+    }
+    fn description(&self) -> &str {
+        "Shows the value of an expression every time the program stops, see print. Without an expression, shows them all now."
+    }
+    fn syntax(&self) -> &str {
+        "display[/fu] [expression]"
+    }
+}
@@ -0,0 +1,72 @@
+use super::command_base::DebugCommand;
+use crate::debug::memory::{parse_address, read, Space};
+use crate::debug::session::DebugSession;
+use log::error;
+
+pub struct DumpCommand;
+
+impl DebugCommand for DumpCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        let (file, start, end) = match args.as_slice() {
+            [_, "memory", file, start, end] => (file, start, end),
+            _ => {
+                self.display_error();
+                return 1;
+            }
+        };
+        let (space, start) = match parse_address(start, Space::Data, session) {
+            Ok(found) => found,
+            Err(e) => {
+                error!("{}", e);
+                return 1;
+            }
+        };
+        // The end is in the space of the start
+        let end = match parse_address(end, space, session) {
+            Ok((end_space, end)) if end_space == space && end >= start => end,
+            Ok(_) => {
+                error!("The end has to be after the start, in the {}", space);
+                return 1;
+            }
+            Err(e) => {
+                error!("{}", e);
+                return 1;
+            }
+        };
+        let bytes = match read(session, space, start, end - start) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                error!("{}", e);
+                return 1;
+            }
+        };
+        match std::fs::write(file, bytes) {
+            Ok(()) => {
+                outputln!(
+                    session,
+                    "Wrote {} bytes of the {} ({:#06x} to {:#06x}) to {}.",
+                    bytes.len(),
+                    space,
+                    start,
+                    end,
+                    file
+                );
+                0
+            }
+            Err(e) => {
+                error!("Couldn't write {}: {}", file, e);
+                1
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "dump"
+    }
+    fn description(&self) -> &str {
+        "Writes the bytes from start up to end, excluded, to a file."
+    }
+    fn syntax(&self) -> &str {
+        "dump memory <file> <[code:|data:]start> <end>"
+    }
+}
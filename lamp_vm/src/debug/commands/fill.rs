@@ -0,0 +1,61 @@
+use super::command_base::DebugCommand;
+use crate::debug::breakpoints::parse_number;
+use crate::debug::patch::{write_memory, PatchError};
+use crate::debug::session::DebugSession;
+use log::error;
+use std::convert::TryFrom;
+
+pub struct FillCommand;
+
+impl DebugCommand for FillCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        let numbers: Vec<Option<usize>> = args[1..].iter().map(|arg| parse_number(arg)).collect();
+        let (address, count, byte) = match numbers.as_slice() {
+            [Some(address), Some(count), Some(byte)] => match u8::try_from(*byte) {
+                Ok(byte) => (*address, *count, byte),
+                Err(_) => {
+                    self.display_error();
+                    return 1;
+                }
+            },
+            _ => {
+                self.display_error();
+                return 1;
+            }
+        };
+        // Checked first, not to allocate whatever count was given
+        let fits = address
+            .checked_add(count)
+            .is_some_and(|end| end <= session.vm.get_memory().len());
+        if !fits {
+            error!("{}", PatchError::OutOfMemory(address, count));
+            return 1;
+        }
+        match write_memory(&mut session.vm, address, &vec![byte; count]) {
+            Ok(()) => {
+                outputln!(
+                    session,
+                    "Filled {} bytes at {:#06x} with {:#04x}.",
+                    count,
+                    address,
+                    byte
+                );
+                0
+            }
+            Err(e) => {
+                error!("{}", e);
+                1
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "fill"
+    }
+    fn description(&self) -> &str {
+        "Fills a range of the data memory with a byte."
+    }
+    fn syntax(&self) -> &str {
+        "fill <address> <count> <byte>"
+    }
+}
@@ -0,0 +1,67 @@
+use super::command_base::DebugCommand;
+use crate::debug::breakpoints::parse_number;
+use crate::debug::session::{DebugSession, Stop};
+use log::error;
+
+pub struct GotoCommand;
+
+impl DebugCommand for GotoCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        let number = match (args.get(1).and_then(|arg| parse_number(arg)), args.len()) {
+            (Some(number), 2) => number as u64,
+            _ => {
+                self.display_error();
+                return 1;
+            }
+        };
+        // The instructions are numbered from 1, the current one is the next to execute
+        let target = match number.checked_sub(1) {
+            Some(target) => target,
+            None => {
+                self.display_error();
+                return 1;
+            }
+        };
+        if target > session.history.count() && session.is_terminated() {
+            error!("The program is not running. Use \"run\" to start it again.");
+            return 1;
+        }
+        match session.goto(target) {
+            Some(Stop::Step) => {
+                outputln!(
+                    session,
+                    "Instruction #{}, {}",
+                    number,
+                    session.describe(session.vm.get_pc())
+                );
+                session.show_displays();
+                0
+            }
+            Some(stop) => {
+                session.report(stop);
+                match stop {
+                    Stop::Fault(_) => 1,
+                    _ => 0,
+                }
+            }
+            None => {
+                error!(
+                    "Instruction #{} is not recorded anymore, the oldest one is #{}.",
+                    number,
+                    session.history.oldest()
+                );
+                1
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "goto"
+    }
+    fn description(&self) -> &str {
+        "Goes back or forward to an instruction, numbered from 1 since the start of the program. Breakpoints are ignored on the way."
+    }
+    fn syntax(&self) -> &str {
+        "goto <instruction number>"
+    }
+}
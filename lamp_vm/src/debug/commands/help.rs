@@ -0,0 +1,62 @@
+use super::command_base::DebugCommand;
+use super::{find_cmd, get_cmds};
+use crate::debug::session::DebugSession;
+use log::error;
+
+pub struct HelpCommand;
+
+impl DebugCommand for HelpCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        match args.get(1) {
+            None => {
+                let commands = get_cmds();
+                let width = commands.iter().map(|c| c.name().len()).max().unwrap_or(0);
+                outputln!(
+                    session,
+                    "Commands, which can be abbreviated to any unique prefix:"
+                );
+                for command in commands {
+                    outputln!(
+                        session,
+                        "  {:<w$}  {}",
+                        command.name(),
+                        command.description(),
+                        w = width
+                    );
+                }
+                outputln!(
+                    session,
+                    "An empty line repeats the last step or continue. Type \"help <command>\" for more."
+                );
+                0
+            }
+            Some(name) => match find_cmd(name) {
+                Ok(command) => {
+                    outputln!(session, "Usage: {}", command.syntax());
+                    outputln!(session, "{}", command.description());
+                    if !command.aliases().is_empty() {
+                        outputln!(session, "Aliases: {}", command.aliases().join(", "));
+                    }
+                    0
+                }
+                Err(e) => {
+                    error!("{}", e);
+                    1
+                }
+            },
+        }
+    }
+
+    fn name(&self) -> &str {
+        "help"
+    }
+    fn aliases(&self) -> &[&str] {
+        &["?"]
+    }
+    fn description(&self) -> &str {
+        "Lists the commands, or explains one of them."
+    }
+    fn syntax(&self) -> &str {
+        "help [command]"
+    }
+}
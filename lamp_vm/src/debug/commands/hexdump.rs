@@ -0,0 +1,65 @@
+use super::command_base::DebugCommand;
+use crate::debug::breakpoints::parse_number;
+use crate::debug::memory::{hexdump, parse_address, read, Space};
+use crate::debug::session::DebugSession;
+use log::error;
+
+// Number of bytes shown when no length is given
+const DEFAULT_LENGTH: usize = 64;
+
+pub struct HexdumpCommand;
+
+impl DebugCommand for HexdumpCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        let length = match args.get(2).map(|arg| parse_number(arg)) {
+            Some(Some(length)) => length,
+            Some(None) => {
+                self.display_error();
+                return 1;
+            }
+            None => DEFAULT_LENGTH,
+        };
+        let location = match (args.get(1), args.len()) {
+            (Some(location), 2..=3) => location,
+            _ => {
+                self.display_error();
+                return 1;
+            }
+        };
+        let (space, start) = match parse_address(location, Space::Data, session) {
+            Ok(found) => found,
+            Err(e) => {
+                error!("{}", e);
+                return 1;
+            }
+        };
+        // Stops at the end of the space rather than failing
+        let length = length.min(space.bytes(session).len().saturating_sub(start));
+        match read(session, space, start, length) {
+            Ok(bytes) if !bytes.is_empty() => {
+                for line in hexdump(bytes, start) {
+                    outputln!(session, "{}", line);
+                }
+                0
+            }
+            Ok(_) => {
+                error!("Address {:#x} is outside of the {}", start, space);
+                1
+            }
+            Err(e) => {
+                error!("{}", e);
+                1
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "hexdump"
+    }
+    fn description(&self) -> &str {
+        "Shows bytes in hexadecimal and as text, 16 per line."
+    }
+    fn syntax(&self) -> &str {
+        "hexdump <[code:|data:]address|label> [length]"
+    }
+}
@@ -0,0 +1,51 @@
+use super::command_base::DebugCommand;
+use crate::debug::breakpoints::parse_number;
+use crate::debug::session::DebugSession;
+
+pub struct HistoryCommand;
+
+impl DebugCommand for HistoryCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        match args.get(1) {
+            None => {
+                let history = &session.history;
+                outputln!(
+                    session,
+                    "At instruction #{}, {} recorded of at most {}.",
+                    history.count() + 1,
+                    history.len(),
+                    history.size()
+                );
+                if !history.is_empty() {
+                    outputln!(session, "Can go back to instruction #{}.", history.oldest());
+                }
+                0
+            }
+            Some(arg) => match parse_number(arg) {
+                Some(size) if args.len() == 2 => {
+                    session.history.set_size(size);
+                    if size == 0 {
+                        outputln!(session, "Instructions won't be recorded anymore.");
+                    } else {
+                        outputln!(session, "The last {} instructions will be recorded.", size);
+                    }
+                    0
+                }
+                _ => {
+                    self.display_error();
+                    1
+                }
+            },
+        }
+    }
+
+    fn name(&self) -> &str {
+        "history"
+    }
+    fn description(&self) -> &str {
+        "Shows how far back the program can go, or sets how many instructions are recorded (0 to stop recording)."
+    }
+    fn syntax(&self) -> &str {
+        "history [size]"
+    }
+}
@@ -0,0 +1,45 @@
+use super::command_base::DebugCommand;
+use crate::debug::breakpoints::parse_number;
+use crate::debug::session::DebugSession;
+use log::error;
+
+pub struct IgnoreCommand;
+
+impl DebugCommand for IgnoreCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        let numbers: Vec<Option<usize>> = args[1..].iter().map(|arg| parse_number(arg)).collect();
+        let (id, count) = match numbers.as_slice() {
+            [Some(id), Some(count)] => (*id, *count),
+            _ => {
+                self.display_error();
+                return 1;
+            }
+        };
+        match session.breakpoints.get_mut(id) {
+            Ok(breakpoint) => {
+                breakpoint.ignore_count = count as u32;
+                outputln!(
+                    session,
+                    "Breakpoint {} will be ignored the next {} times.",
+                    id,
+                    count
+                );
+                0
+            }
+            Err(e) => {
+                error!("{}", e);
+                1
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "ignore"
+    }
+    fn description(&self) -> &str {
+        "Lets a breakpoint be reached a number of times before it stops the program."
+    }
+    fn syntax(&self) -> &str {
+        "ignore <breakpoint number> <count>"
+    }
+}
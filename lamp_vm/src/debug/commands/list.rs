@@ -0,0 +1,66 @@
+use super::command_base::DebugCommand;
+use crate::debug::breakpoints::WatchKind;
+use crate::debug::session::DebugSession;
+
+pub struct ListCommand;
+
+impl DebugCommand for ListCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        match args.get(1) {
+            None | Some(&"breakpoints") => {}
+            Some(_) => {
+                self.display_error();
+                return 1;
+            }
+        }
+
+        let breakpoints = session.breakpoints.list();
+        let watchpoints = session.breakpoints.watchpoints();
+        if breakpoints.is_empty() && watchpoints.is_empty() {
+            outputln!(session, "No breakpoints.");
+        } else {
+            outputln!(session, "Num  Type        Address  Hits  What");
+            for breakpoint in breakpoints {
+                let mut line = format!(
+                    "{:<3}  breakpoint  {:#06x}   {:<4}  {}",
+                    breakpoint.id, breakpoint.address, breakpoint.hits, breakpoint.location
+                );
+                if let Some(condition) = &breakpoint.condition {
+                    line += &format!(" if {}", condition);
+                }
+                if breakpoint.ignore_count > 0 {
+                    line += &format!(" (ignores the next {} hits)", breakpoint.ignore_count);
+                }
+                outputln!(session, "{}", line);
+            }
+            for watchpoint in watchpoints {
+                outputln!(
+                    session,
+                    "{:<3}  {:<10}  {:7}  {:<4}  {}",
+                    watchpoint.id,
+                    match watchpoint.kind {
+                        WatchKind::Change => "watch",
+                        WatchKind::Read => "rwatch",
+                    },
+                    "",
+                    watchpoint.hits,
+                    watchpoint.target.name(session.aliases())
+                );
+            }
+        }
+        if session.breakpoints.catch_faults {
+            outputln!(session, "Faults are caught.");
+        }
+        0
+    }
+
+    fn name(&self) -> &str {
+        "list"
+    }
+    fn description(&self) -> &str {
+        "Lists the breakpoints and the watchpoints."
+    }
+    fn syntax(&self) -> &str {
+        "list [breakpoints]"
+    }
+}
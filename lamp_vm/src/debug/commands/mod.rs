@@ -1,25 +1,147 @@
 pub mod command_base;
 
 // Commands modules declarations
+pub mod asm;
+pub mod assert;
+pub mod breakpoint;
+pub mod catch;
+pub mod cont;
+pub mod delete;
+pub mod disasm;
+pub mod display;
+pub mod dump;
+pub mod fill;
+pub mod goto;
+pub mod help;
+pub mod hexdump;
+pub mod history;
+pub mod ignore;
+pub mod list;
+pub mod print;
+pub mod quit;
 pub mod regdump;
+pub mod reverse_continue;
+pub mod reverse_step;
+pub mod run;
+pub mod rwatch;
+pub mod search;
+pub mod setflag;
+pub mod setmem;
 pub mod setpc;
+pub mod setreg;
 pub mod step;
+pub mod undisplay;
+pub mod watch;
+pub mod x;
 
 use command_base::DebugCommand;
 
 pub fn get_cmds() -> Vec<Box<dyn DebugCommand>> {
     vec![
+        Box::new(asm::AsmCommand {}),
+        Box::new(assert::AssertCommand {}),
+        Box::new(breakpoint::BreakCommand {}),
+        Box::new(catch::CatchCommand {}),
+        Box::new(cont::ContinueCommand {}),
+        Box::new(delete::DeleteCommand {}),
+        Box::new(disasm::DisasmCommand {}),
+        Box::new(display::DisplayCommand {}),
+        Box::new(dump::DumpCommand {}),
+        Box::new(fill::FillCommand {}),
+        Box::new(goto::GotoCommand {}),
+        Box::new(help::HelpCommand {}),
+        Box::new(hexdump::HexdumpCommand {}),
+        Box::new(history::HistoryCommand {}),
+        Box::new(ignore::IgnoreCommand {}),
+        Box::new(list::ListCommand {}),
+        Box::new(print::PrintCommand {}),
+        Box::new(quit::QuitCommand {}),
         Box::new(regdump::RegdumpCommand {}),
+        Box::new(reverse_continue::ReverseContinueCommand {}),
+        Box::new(reverse_step::ReverseStepCommand {}),
+        Box::new(run::RunCommand {}),
+        Box::new(rwatch::RwatchCommand {}),
+        Box::new(search::SearchCommand {}),
+        Box::new(setflag::SetFlagCommand {}),
+        Box::new(setmem::SetMemCommand {}),
         Box::new(setpc::SetPcCommand {}),
+        Box::new(setreg::SetRegCommand {}),
         Box::new(step::StepCommand {}),
+        Box::new(undisplay::UndisplayCommand {}),
+        Box::new(watch::WatchCommand {}),
+        Box::new(x::ExamineCommand {}),
     ]
 }
 
-pub fn parse_cmd(string: &str) -> Option<Box<dyn DebugCommand>> {
-    for command in get_cmds() {
-        if string.contains(command.name()) {
-            return Some(command);
+// Different types of errors finding a command can cause
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandError {
+    Empty,
+    Unknown(String),
+    // The prefix, and the commands starting with it
+    Ambiguous(String, Vec<String>),
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "No command given."),
+            Self::Unknown(name) => write!(
+                f,
+                "This command doesn\'t exist: \'{}\'. Type \"help\" for an exhaustive list of the available commands.",
+                name
+            ),
+            Self::Ambiguous(prefix, names) => write!(
+                f,
+                "\'{}\' is ambiguous, it can be {}.",
+                prefix,
+                names.join(", ")
+            ),
         }
     }
-    None
+}
+
+// Finds a command by its name, one of its aliases, or a prefix of its name
+// that no other command starts with
+pub fn find_cmd(name: &str) -> Result<Box<dyn DebugCommand>, CommandError> {
+    let commands = get_cmds();
+    let exact = commands
+        .iter()
+        .position(|c| c.name() == name || c.aliases().contains(&name));
+    let mut candidates: Vec<Box<dyn DebugCommand>> = match exact {
+        Some(i) => commands.into_iter().skip(i).take(1).collect(),
+        None => commands
+            .into_iter()
+            .filter(|c| c.name().starts_with(name))
+            .collect(),
+    };
+    match candidates.len() {
+        0 => Err(CommandError::Unknown(name.to_owned())),
+        1 => Ok(candidates.remove(0)),
+        _ => Err(CommandError::Ambiguous(
+            name.to_owned(),
+            candidates.iter().map(|c| c.name().to_owned()).collect(),
+        )),
+    }
+}
+
+// Finds the command named by the first word of the line, which can end with a
+// format like x/4xw
+pub fn parse_cmd(string: &str) -> Result<Box<dyn DebugCommand>, CommandError> {
+    let word = string
+        .split_whitespace()
+        .next()
+        .ok_or(CommandError::Empty)?;
+    let name = word.split('/').next().unwrap_or(word);
+    find_cmd(name)
+}
+
+// The names and aliases of every command, for completion
+pub fn command_names() -> Vec<String> {
+    let mut names = Vec::<String>::new();
+    for command in get_cmds() {
+        names.push(command.name().to_owned());
+        names.extend(command.aliases().iter().map(|alias| alias.to_string()));
+    }
+    names
 }
@@ -0,0 +1,63 @@
+use super::command_base::DebugCommand;
+use crate::debug::display::format_result;
+use crate::debug::expr::Expr;
+use crate::debug::memory::{Examine, Format};
+use crate::debug::session::DebugSession;
+use log::error;
+
+pub struct PrintCommand;
+
+impl DebugCommand for PrintCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        let format = match parse_format(args[0]) {
+            Ok(format) => format,
+            Err(e) => {
+                error!("{}", e);
+                return 1;
+            }
+        };
+        if args.len() < 2 {
+            self.display_error();
+            return 1;
+        }
+        let result = Expr::parse(&args[1..].join(" "), &session.symbols, session.aliases())
+            .and_then(|expr| expr.evaluate(&session.vm).map(|value| (expr, value)));
+        match result {
+            Ok((expr, value)) => {
+                let format = format.as_ref().map(|(_, format)| format);
+                outputln!(session, "{} = {}", expr, format_result(value, format));
+                0
+            }
+            Err(e) => {
+                error!("{}", e);
+                1
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "print"
+    }
+    fn aliases(&self) -> &[&str] {
+        &["p"]
+    }
+    fn description(&self) -> &str {
+        "Evaluates an expression of registers (r3, sp, pc, eq, remainder), labels and numbers, with the operators of C and *address reading a word of the data memory. The value is in decimal, or in the format of x: /x, /d, /u or /c, of bytes (b), halfwords (h) or words (w)."
+    }
+    fn syntax(&self) -> &str {
+        "print[/fu] <expression>"
+    }
+}
+
+// The format glued to the name of the command, like print/x. Instructions can't be printed.
+pub fn parse_format(name: &str) -> Result<Option<(String, Examine)>, String> {
+    match name.split_once('/') {
+        Some((_, text)) => match Examine::parse(text) {
+            Some(format) if format.format != Format::Instruction && format.count == 1 => {
+                Ok(Some((text.to_owned(), format)))
+            }
+            _ => Err(format!("Invalid format \'/{}\'", text)),
+        },
+        None => Ok(None),
+    }
+}
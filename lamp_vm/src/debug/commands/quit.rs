@@ -0,0 +1,25 @@
+use super::command_base::DebugCommand;
+use crate::debug::session::DebugSession;
+
+pub struct QuitCommand;
+
+impl DebugCommand for QuitCommand {
+    #[allow(unused_variables)]
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        session.quit();
+        0
+    }
+
+    fn name(&self) -> &str {
+        "quit"
+    }
+    fn aliases(&self) -> &[&str] {
+        &["q", "exit"]
+    }
+    fn description(&self) -> &str {
+        "Ends the debug session. Ctrl-D does the same."
+    }
+    fn syntax(&self) -> &str {
+        "quit"
+    }
+}
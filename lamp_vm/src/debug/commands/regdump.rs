@@ -1,29 +1,33 @@
 use super::command_base::DebugCommand;
-use crate::base::vm::VM;
+use crate::debug::session::DebugSession;
+use lamp_common::register::REGISTER_COUNT;
 use log::{error, info};
 
 pub struct RegdumpCommand;
 
 impl DebugCommand for RegdumpCommand {
-    fn execute(&self, vm: &mut VM, args: Vec<&str>) -> usize {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
         match args.get(1) {
             Some(arg) => {
                 if arg == &"all" {
-                    for i in 0..32 {
-                        print!("|| {}: {}", i, vm.get_register(i));
+                    for i in 0..REGISTER_COUNT {
+                        let name = session.register_name(i);
+                        output!(session, "|| {}: {}", name, session.vm.get_register(i));
                     }
+                    outputln!(session);
                     0
                 } else {
-                    match arg.parse::<u8>() {
-                        Ok(num) => {
-                            if num > 31 {
-                                error!("Error: wrong arg 2.\nUsage: {}", self.syntax());
-                                return 1;
-                            }
-                            info!("Register {} = {}", num, vm.get_register(num));
+                    // Either a register name (r5, sp) or its index
+                    match session
+                        .parse_register(arg)
+                        .or_else(|| arg.parse::<u8>().ok())
+                    {
+                        Some(num) if num < REGISTER_COUNT => {
+                            let name = session.register_name(num);
+                            info!("Register {} = {}", name, session.vm.get_register(num));
                             0
                         }
-                        Err(_) => {
+                        _ => {
                             error!("Error: wrong arg 2: \'{}\'\nUsage: {}", arg, self.syntax());
                             1
                         }
@@ -46,6 +50,6 @@ impl DebugCommand for RegdumpCommand {
     }
 
     fn syntax(&self) -> &str {
-        "regdump <0..31 | all>"
+        "regdump <r0..r31 | 0..31 | sp | fp | ra | zero | all>"
     }
 }
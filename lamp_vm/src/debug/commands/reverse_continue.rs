@@ -0,0 +1,47 @@
+use super::command_base::DebugCommand;
+use crate::debug::session::DebugSession;
+use log::error;
+
+pub struct ReverseContinueCommand;
+
+impl DebugCommand for ReverseContinueCommand {
+    #[allow(unused_variables)]
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        if session.history.is_empty() {
+            error!("No more history: the previous instructions were not recorded.");
+            return 1;
+        }
+        match session.reverse_continue() {
+            Some(id) => outputln!(
+                session,
+                "Breakpoint {}, {}",
+                id,
+                session.describe(session.vm.get_pc())
+            ),
+            None => outputln!(
+                session,
+                "Reached the oldest instruction recorded, #{}.\n{}",
+                session.history.count() + 1,
+                session.describe(session.vm.get_pc())
+            ),
+        }
+        session.show_displays();
+        0
+    }
+
+    fn name(&self) -> &str {
+        "reverse-continue"
+    }
+    fn aliases(&self) -> &[&str] {
+        &["rc"]
+    }
+    fn repeatable(&self) -> bool {
+        true
+    }
+    fn description(&self) -> &str {
+        "Runs the program backward until the previous breakpoint, or the oldest instruction recorded."
+    }
+    fn syntax(&self) -> &str {
+        "reverse-continue"
+    }
+}
@@ -0,0 +1,34 @@
+use super::command_base::DebugCommand;
+use crate::debug::session::DebugSession;
+use log::error;
+
+pub struct ReverseStepCommand;
+
+impl DebugCommand for ReverseStepCommand {
+    #[allow(unused_variables)]
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        if !session.reverse_step() {
+            error!("No more history: the previous instructions were not recorded.");
+            return 1;
+        }
+        outputln!(session, "{}", session.describe(session.vm.get_pc()));
+        session.show_displays();
+        0
+    }
+
+    fn name(&self) -> &str {
+        "reverse-step"
+    }
+    fn aliases(&self) -> &[&str] {
+        &["rs"]
+    }
+    fn repeatable(&self) -> bool {
+        true
+    }
+    fn description(&self) -> &str {
+        "Undoes the last instruction executed."
+    }
+    fn syntax(&self) -> &str {
+        "reverse-step"
+    }
+}
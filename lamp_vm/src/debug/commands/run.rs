@@ -0,0 +1,34 @@
+use super::command_base::DebugCommand;
+use crate::debug::session::{DebugSession, Stop};
+
+pub struct RunCommand;
+
+impl DebugCommand for RunCommand {
+    #[allow(unused_variables)]
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        session.restart();
+        // A breakpoint can be on the entry point itself
+        let stop = match session.breakpoints.hit(&session.vm) {
+            Some(id) => Stop::Breakpoint(id),
+            None => session.resume(),
+        };
+        session.report(stop);
+        match stop {
+            Stop::Fault(_) => 1,
+            _ => 0,
+        }
+    }
+
+    fn name(&self) -> &str {
+        "run"
+    }
+    fn aliases(&self) -> &[&str] {
+        &["r"]
+    }
+    fn description(&self) -> &str {
+        "Starts the program again from its entry point, until a breakpoint, a fault or its end."
+    }
+    fn syntax(&self) -> &str {
+        "run"
+    }
+}
@@ -0,0 +1,26 @@
+use super::command_base::DebugCommand;
+use super::watch::add_watchpoint;
+use crate::debug::breakpoints::WatchKind;
+use crate::debug::session::DebugSession;
+
+pub struct RwatchCommand;
+
+impl DebugCommand for RwatchCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        if args.len() < 2 {
+            self.display_error();
+            return 1;
+        }
+        add_watchpoint(session, &args[1..], WatchKind::Read)
+    }
+
+    fn name(&self) -> &str {
+        "rwatch"
+    }
+    fn description(&self) -> &str {
+        "Stops the program when an instruction reads a register."
+    }
+    fn syntax(&self) -> &str {
+        "rwatch <register>"
+    }
+}
@@ -0,0 +1,60 @@
+use super::command_base::DebugCommand;
+use crate::debug::breakpoints::parse_number;
+use crate::debug::memory::{find, label_at, Space};
+use crate::debug::session::DebugSession;
+use std::convert::TryFrom;
+
+pub struct SearchCommand;
+
+impl DebugCommand for SearchCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        let (space, pattern) = match args.get(1) {
+            Some(&"code") => (Space::Code, args.get(2..).unwrap_or_default()),
+            Some(&"data") => (Space::Data, args.get(2..).unwrap_or_default()),
+            _ => (Space::Data, args.get(1..).unwrap_or_default()),
+        };
+        let pattern = match parse_pattern(pattern) {
+            Some(pattern) if !pattern.is_empty() => pattern,
+            _ => {
+                self.display_error();
+                return 1;
+            }
+        };
+
+        let matches = find(space.bytes(session), &pattern);
+        if matches.is_empty() {
+            outputln!(session, "Pattern not found in the {}.", space);
+        }
+        for address in &matches {
+            match label_at(session, space, *address) {
+                Some(label) => outputln!(session, "{:#06x} {}", address, label),
+                None => outputln!(session, "{:#06x}", address),
+            }
+        }
+        0
+    }
+
+    fn name(&self) -> &str {
+        "search"
+    }
+    fn description(&self) -> &str {
+        "Lists the addresses where a sequence of bytes, or a string, is found. Searches the data memory unless code is given."
+    }
+    fn syntax(&self) -> &str {
+        "search [code | data] <byte [byte...] | \"string\">"
+    }
+}
+
+// Bytes like `0x12 52`, or a quoted string
+fn parse_pattern(args: &[&str]) -> Option<Vec<u8>> {
+    let text = args.join(" ");
+    if let Some(string) = text
+        .strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+    {
+        return Some(string.as_bytes().to_vec());
+    }
+    args.iter()
+        .map(|arg| parse_number(arg).and_then(|byte| u8::try_from(byte).ok()))
+        .collect()
+}
@@ -0,0 +1,50 @@
+use super::command_base::DebugCommand;
+use crate::debug::breakpoints::parse_value;
+use crate::debug::session::DebugSession;
+
+pub struct SetFlagCommand;
+
+impl DebugCommand for SetFlagCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        match args.as_slice() {
+            [_, "eq", value] => {
+                let value = match *value {
+                    "1" | "true" => true,
+                    "0" | "false" => false,
+                    _ => {
+                        self.display_error();
+                        return 1;
+                    }
+                };
+                session.vm.set_eq_flag(value);
+                outputln!(session, "eq_flag = {}", value);
+                0
+            }
+            [_, "remainder", value] => match parse_value(value) {
+                Some(value) => {
+                    session.vm.set_modulo_remainder(value);
+                    outputln!(session, "remainder = {}", value);
+                    0
+                }
+                None => {
+                    self.display_error();
+                    1
+                }
+            },
+            _ => {
+                self.display_error();
+                1
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "setflag"
+    }
+    fn description(&self) -> &str {
+        "Sets the flag of the comparisons, or the remainder of the last MOD."
+    }
+    fn syntax(&self) -> &str {
+        "setflag <eq <0 | 1> | remainder <value>>"
+    }
+}
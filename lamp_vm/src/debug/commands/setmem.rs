@@ -0,0 +1,47 @@
+use super::command_base::DebugCommand;
+use crate::debug::breakpoints::parse_number;
+use crate::debug::patch::write_memory;
+use crate::debug::session::DebugSession;
+use log::error;
+use std::convert::TryFrom;
+
+pub struct SetMemCommand;
+
+impl DebugCommand for SetMemCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        let address = args.get(1).and_then(|arg| parse_number(arg));
+        let bytes: Option<Vec<u8>> = args
+            .get(2..)
+            .unwrap_or_default()
+            .iter()
+            .map(|arg| parse_number(arg).and_then(|byte| u8::try_from(byte).ok()))
+            .collect();
+        let (address, bytes) = match (address, bytes) {
+            (Some(address), Some(bytes)) if !bytes.is_empty() => (address, bytes),
+            _ => {
+                self.display_error();
+                return 1;
+            }
+        };
+        match write_memory(&mut session.vm, address, &bytes) {
+            Ok(()) => {
+                outputln!(session, "Wrote {} bytes at {:#06x}.", bytes.len(), address);
+                0
+            }
+            Err(e) => {
+                error!("{}", e);
+                1
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "setmem"
+    }
+    fn description(&self) -> &str {
+        "Writes bytes in the data memory."
+    }
+    fn syntax(&self) -> &str {
+        "setmem <address> <byte> [byte...]"
+    }
+}
@@ -1,16 +1,13 @@
 use super::command_base::DebugCommand;
-use crate::base::vm::VM;
+use crate::debug::session::DebugSession;
 
 pub struct SetPcCommand;
 
 impl DebugCommand for SetPcCommand {
-    fn execute(&self, vm: &mut VM, args: Vec<&str>) -> usize {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
         match args.get(1) {
             Some(arg) => match arg.parse::<usize>() {
-                Ok(new_pc) => {
-                    vm.set_pc(new_pc);
-                    0
-                }
+                Ok(new_pc) => session.vm.set_pc(new_pc),
                 Err(_) => {
                     self.display_error();
                     1
@@ -0,0 +1,36 @@
+use super::command_base::DebugCommand;
+use crate::debug::breakpoints::parse_value;
+use crate::debug::session::DebugSession;
+
+pub struct SetRegCommand;
+
+impl DebugCommand for SetRegCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        let (register, value) = match args.as_slice() {
+            [_, register, value] => match (session.parse_register(register), parse_value(value)) {
+                (Some(register), Some(value)) => (register, value),
+                _ => {
+                    self.display_error();
+                    return 1;
+                }
+            },
+            _ => {
+                self.display_error();
+                return 1;
+            }
+        };
+        session.vm.set_register_value(register, value);
+        outputln!(session, "{} = {}", session.register_name(register), value);
+        0
+    }
+
+    fn name(&self) -> &str {
+        "setreg"
+    }
+    fn description(&self) -> &str {
+        "Sets the value of a register."
+    }
+    fn syntax(&self) -> &str {
+        "setreg <r0..r31 | sp | fp | ra | zero> <value>"
+    }
+}
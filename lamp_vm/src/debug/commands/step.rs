@@ -1,19 +1,32 @@
 use super::command_base::DebugCommand;
-use crate::base::vm::VM;
+use crate::debug::session::{DebugSession, Stop};
+use log::error;
 
 pub struct StepCommand;
 
 impl DebugCommand for StepCommand {
     #[allow(unused_variables)]
-    fn execute(&self, vm: &mut VM, args: Vec<&str>) -> usize {
-        match vm.cycle() {
-            Ok(_) => 0,
-            Err(_) => 1,
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        if session.is_terminated() {
+            error!("The program is not running. Use \"run\" to start it again.");
+            return 1;
+        }
+        let stop = session.step();
+        session.report(stop);
+        match stop {
+            Stop::Fault(_) => 1,
+            _ => 0,
         }
     }
     fn name(&self) -> &str {
         "step"
     }
+    fn aliases(&self) -> &[&str] {
+        &["s"]
+    }
+    fn repeatable(&self) -> bool {
+        true
+    }
     fn description(&self) -> &str {
         "Makes 1 VM's cycle."
     }
@@ -0,0 +1,40 @@
+use super::command_base::DebugCommand;
+use crate::debug::breakpoints::parse_number;
+use crate::debug::session::DebugSession;
+use log::error;
+
+pub struct UndisplayCommand;
+
+impl DebugCommand for UndisplayCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        match args.get(1).map(|arg| parse_number(arg)) {
+            None => {
+                session.displays.clear();
+                outputln!(session, "Deleted every display.");
+                0
+            }
+            Some(Some(id)) if session.displays.delete(id) => {
+                outputln!(session, "Deleted display {}.", id);
+                0
+            }
+            Some(Some(id)) => {
+                error!("No display number {}", id);
+                1
+            }
+            Some(None) => {
+                self.display_error();
+                1
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "undisplay"
+    }
+    fn description(&self) -> &str {
+        "Stops showing an expression given to display, or all of them."
+    }
+    fn syntax(&self) -> &str {
+        "undisplay [number]"
+    }
+}
@@ -0,0 +1,53 @@
+use super::command_base::DebugCommand;
+use crate::debug::breakpoints::{WatchKind, WatchTarget};
+use crate::debug::session::DebugSession;
+use log::error;
+
+pub struct WatchCommand;
+
+// Adds a watchpoint, for watch and rwatch
+pub fn add_watchpoint(session: &mut DebugSession, args: &[&str], kind: WatchKind) -> usize {
+    match WatchTarget::parse(args, kind, session) {
+        Ok(target) => {
+            let id = session.breakpoints.add_watch(target, kind);
+            let value = target.value(&session.vm).unwrap_or(0);
+            outputln!(
+                session,
+                "{} {}: {} = {}",
+                if kind == WatchKind::Read {
+                    "Read watchpoint"
+                } else {
+                    "Watchpoint"
+                },
+                id,
+                target,
+                target.format_value(value)
+            );
+            0
+        }
+        Err(e) => {
+            error!("{}", e);
+            1
+        }
+    }
+}
+
+impl DebugCommand for WatchCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        if args.len() < 2 {
+            self.display_error();
+            return 1;
+        }
+        add_watchpoint(session, &args[1..], WatchKind::Change)
+    }
+
+    fn name(&self) -> &str {
+        "watch"
+    }
+    fn description(&self) -> &str {
+        "Stops the program when the value of a register or of the data memory changes."
+    }
+    fn syntax(&self) -> &str {
+        "watch <register | mem <address | label> [1 | 2 | 4]>"
+    }
+}
@@ -0,0 +1,97 @@
+use super::command_base::DebugCommand;
+use crate::debug::memory::{label_at, parse_address, read, Examine, Format, Space};
+use crate::debug::session::DebugSession;
+use lamp_disasm::{decode_items, format_line, Options};
+use log::error;
+
+pub struct ExamineCommand;
+
+impl DebugCommand for ExamineCommand {
+    fn execute(&self, session: &mut DebugSession, args: Vec<&str>) -> usize {
+        // The format is glued to the name: x/4xw
+        let examine = match args[0].split_once('/') {
+            Some((_, format)) => Examine::parse(format),
+            None => Examine::parse(""),
+        };
+        let (examine, location) = match (examine, args.get(1), args.len()) {
+            (Some(examine), Some(location), 2) => (examine, location),
+            _ => {
+                self.display_error();
+                return 1;
+            }
+        };
+        // Bare addresses are in the data memory, unless the values are instructions
+        let default = match examine.format {
+            Format::Instruction => Space::Code,
+            _ => Space::Data,
+        };
+        let (space, address) = match parse_address(location, default, session) {
+            Ok(found) => found,
+            Err(e) => {
+                error!("{}", e);
+                return 1;
+            }
+        };
+
+        if examine.format == Format::Instruction {
+            let bytes = space.bytes(session).to_vec();
+            if address >= bytes.len() {
+                error!("Address {:#x} is outside of the {}", address, space);
+                return 1;
+            }
+            let options = Options {
+                annotate: true,
+                pc: Some(session.vm.get_pc()).filter(|_| space == Space::Code),
+            };
+            // Every instruction takes at least a byte
+            let count = examine.count.min(bytes.len() - address);
+            for item in decode_items(&bytes, address, count) {
+                outputln!(session, "{}", format_line(&item, &bytes, &options));
+            }
+            return 0;
+        }
+
+        let size = match examine.count.checked_mul(examine.unit) {
+            Some(size) => size,
+            None => {
+                error!(
+                    "Can't read {} values of {} bytes",
+                    examine.count, examine.unit
+                );
+                return 1;
+            }
+        };
+        let bytes = match read(session, space, address, size) {
+            Ok(bytes) => bytes.to_vec(),
+            Err(e) => {
+                error!("{}", e);
+                return 1;
+            }
+        };
+        let per_line = examine.per_line();
+        for (i, line) in bytes.chunks(examine.unit * per_line).enumerate() {
+            let start = address + i * examine.unit * per_line;
+            let values: Vec<String> = line
+                .chunks(examine.unit)
+                .map(|value| examine.format_value(value))
+                .collect();
+            match label_at(session, space, start) {
+                Some(label) => {
+                    outputln!(session, "{:#06x} {}:\t{}", start, label, values.join("\t"))
+                }
+                None => outputln!(session, "{:#06x}:\t{}", start, values.join("\t")),
+            }
+        }
+        0
+    }
+
+    fn name(&self) -> &str {
+        "x"
+    }
+    fn description(&self) -> &str {
+        "Examines memory: N values in hexadecimal (x), decimal (d), unsigned (u), characters (c) or instructions (i), of bytes (b), halfwords (h) or words (w). Addresses are in the data memory unless prefixed with code:."
+    }
+    fn syntax(&self) -> &str {
+        "x[/Nfu] <[code:|data:]address|label|file:line|pc>"
+    }
+}
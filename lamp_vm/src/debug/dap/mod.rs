@@ -0,0 +1,740 @@
+pub mod transport;
+
+use super::breakpoints::{parse_number, parse_value, resolve_location, Condition, WatchKind};
+use super::expr::Expr;
+use super::gdb::DATA_ADDRESS;
+use super::session::{DebugSession, Stop, INTERRUPT_INTERVAL};
+use lamp_common::container::SectionKind;
+use lamp_common::register::parse_register;
+use log::info;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::TryFrom;
+use std::io::{BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use transport::{read_message, write_message};
+
+// Lamp runs one thread
+const THREAD_ID: u64 = 1;
+
+// `variablesReference` of the scopes
+const REGISTERS: u64 = 1;
+const STATE: u64 = 2;
+const MEMORY: u64 = 3;
+
+// Bytes of the data memory shown by each variable of the memory scope
+const MEMORY_ROW: usize = 16;
+
+// How far a step request goes
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum StepKind {
+    Instruction,
+    // To the next line of the code being debugged, over the macros it invokes
+    Line,
+    // To the next line, inside macros
+    In,
+    // Out of the current macro
+    Out,
+}
+
+// Answers the requests of an editor, on top of a debug session.
+// Lines come from the debug info of the binary, and macro invocations are shown as
+// stack frames, the innermost macro first.
+pub struct DapAdapter<'a> {
+    session: &'a mut DebugSession,
+    seq: u64,
+    // Breakpoints of each source file, replaced by every setBreakpoints request
+    source_breakpoints: HashMap<String, Vec<usize>>,
+    stop_on_entry: bool,
+    // Events to send after the response being built
+    events: Vec<Value>,
+    disconnected: bool,
+}
+
+impl<'a> DapAdapter<'a> {
+    pub fn new(session: &'a mut DebugSession) -> Self {
+        // Editors enable the "faults" exception breakpoint by default
+        session.breakpoints.catch_faults = true;
+        Self {
+            session,
+            seq: 0,
+            source_breakpoints: HashMap::new(),
+            stop_on_entry: false,
+            events: vec![],
+            disconnected: false,
+        }
+    }
+
+    pub fn is_disconnected(&self) -> bool {
+        self.disconnected
+    }
+
+    // Answers a request, and gives the response followed by the events to send.
+    // `pause_requested` tells whether the editor asked to pause the running program.
+    pub fn handle(
+        &mut self,
+        request: &Value,
+        pause_requested: &mut dyn FnMut() -> bool,
+    ) -> Vec<Value> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let args = &request["arguments"];
+        let result = match command {
+            "initialize" => {
+                self.event("initialized", Value::Null);
+                Ok(capabilities())
+            }
+            "launch" | "attach" => {
+                self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+                Ok(Value::Null)
+            }
+            "setBreakpoints" => self.set_breakpoints(args),
+            "setExceptionBreakpoints" => {
+                let filters = args["filters"].as_array().cloned().unwrap_or_default();
+                self.session.breakpoints.catch_faults = filters.contains(&json!("faults"));
+                Ok(Value::Null)
+            }
+            "configurationDone" => {
+                if self.stop_on_entry {
+                    self.stopped_event("entry", None);
+                } else {
+                    self.run(pause_requested);
+                }
+                Ok(Value::Null)
+            }
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })),
+            "stackTrace" => {
+                let frames = self.stack_frames();
+                Ok(json!({ "totalFrames": frames.len(), "stackFrames": frames }))
+            }
+            "scopes" => Ok(self.scopes()),
+            "variables" => self.variables(args),
+            "setVariable" => self.set_variable(args),
+            "evaluate" => self.evaluate(args["expression"].as_str().unwrap_or_default()),
+            "readMemory" => self.read_memory(args),
+            "continue" => {
+                self.run(pause_requested);
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "next" | "stepIn" | "stepOut" => {
+                let kind = match (command, args["granularity"].as_str()) {
+                    (_, Some("instruction")) => StepKind::Instruction,
+                    ("stepIn", _) => StepKind::In,
+                    ("stepOut", _) => StepKind::Out,
+                    _ => StepKind::Line,
+                };
+                self.step(kind, pause_requested);
+                Ok(Value::Null)
+            }
+            "stepBack" => {
+                if self.session.reverse_step() {
+                    self.stopped_event("step", None);
+                } else {
+                    self.stopped_event("step", Some("Oldest instruction recorded"));
+                }
+                Ok(Value::Null)
+            }
+            "reverseContinue" => {
+                match self.session.reverse_continue() {
+                    Some(id) => self.breakpoint_event(id),
+                    None => self.stopped_event("step", Some("Oldest instruction recorded")),
+                }
+                Ok(Value::Null)
+            }
+            // A running program is paused by `pause_requested`, it is already stopped here
+            "pause" => Ok(Value::Null),
+            "disconnect" | "terminate" => {
+                self.disconnected = true;
+                if command == "terminate" {
+                    self.event("terminated", Value::Null);
+                }
+                Ok(Value::Null)
+            }
+            _ => Err(format!("Unsupported request \'{}\'", command)),
+        };
+
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": command,
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(Value::Null) => {}
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        let mut messages = vec![response];
+        messages.append(&mut self.events);
+        for message in messages.iter_mut() {
+            self.seq += 1;
+            message["seq"] = json!(self.seq);
+        }
+        messages
+    }
+
+    fn event(&mut self, name: &str, body: Value) {
+        let mut event = json!({ "type": "event", "event": name });
+        if !body.is_null() {
+            event["body"] = body;
+        }
+        self.events.push(event);
+    }
+
+    fn stopped_event(&mut self, reason: &str, description: Option<&str>) {
+        let mut body = json!({
+            "reason": reason,
+            "threadId": THREAD_ID,
+            "allThreadsStopped": true,
+        });
+        if let Some(description) = description {
+            body["description"] = json!(description);
+        }
+        self.event("stopped", body);
+    }
+
+    fn breakpoint_event(&mut self, id: usize) {
+        self.event(
+            "stopped",
+            json!({
+                "reason": "breakpoint",
+                "threadId": THREAD_ID,
+                "allThreadsStopped": true,
+                "hitBreakpointIds": [id],
+            }),
+        );
+    }
+
+    fn exit_events(&mut self, code: i32) {
+        self.event("exited", json!({ "exitCode": code }));
+        self.event("terminated", Value::Null);
+    }
+
+    // Tells the editor why the program stopped
+    fn report(&mut self, stop: Stop) {
+        match stop {
+            Stop::Step => self.stopped_event("step", None),
+            Stop::Breakpoint(id) => self.breakpoint_event(id),
+            Stop::Watch(hit) => {
+                let text = match hit.kind {
+                    WatchKind::Change => format!(
+                        "Watchpoint {}: {} changed from {} to {}",
+                        hit.id,
+                        hit.target.name(self.session.aliases()),
+                        hit.target.format_value(hit.old),
+                        hit.target.format_value(hit.new)
+                    ),
+                    WatchKind::Read => {
+                        let target = hit.target.name(self.session.aliases());
+                        format!("Read watchpoint {}: {} was read", hit.id, target)
+                    }
+                };
+                self.stopped_event("data breakpoint", Some(&text));
+            }
+            Stop::Fault(e) => {
+                let vm = &mut self.session.vm;
+                let pc = vm.get_instruction_pc();
+                if self.session.breakpoints.catch_faults {
+                    // Back to the faulting instruction, so it can be inspected
+                    vm.set_pc(pc);
+                    let body = json!({
+                        "reason": "exception",
+                        "threadId": THREAD_ID,
+                        "allThreadsStopped": true,
+                        "description": format!("Fault: {}", e),
+                        "text": e.to_string(),
+                    });
+                    self.event("stopped", body);
+                } else {
+                    let output = format!(
+                        "The program terminated with a fault: {}\nfault at {}\n",
+                        e,
+                        self.session.describe_at(pc)
+                    );
+                    self.event("output", json!({ "category": "stderr", "output": output }));
+                    self.exit_events(1);
+                }
+            }
+            Stop::Exited => self.exit_events(0),
+            Stop::Interrupted => self.stopped_event("pause", None),
+        }
+    }
+
+    fn run(&mut self, pause_requested: &mut dyn FnMut() -> bool) {
+        if self.session.is_terminated() {
+            self.exit_events(0);
+            return;
+        }
+        let stop = self.session.resume_until(pause_requested);
+        self.report(stop);
+    }
+
+    // Steps instructions until the source position changes as `kind` wants
+    fn step(&mut self, kind: StepKind, pause_requested: &mut dyn FnMut() -> bool) {
+        if self.session.is_terminated() {
+            self.exit_events(0);
+            return;
+        }
+        let start = self.position();
+        let mut count = 0u64;
+        loop {
+            let stop = self.session.step();
+            if stop != Stop::Step {
+                return self.report(stop);
+            }
+            let position = self.position();
+            let done = match (kind, &start, &position) {
+                (StepKind::Instruction, _, _) | (_, None, _) | (_, _, None) => true,
+                (StepKind::Line, Some(start), Some(now)) => start[0] != now[0],
+                (StepKind::In, Some(start), Some(now)) => start != now,
+                (StepKind::Out, Some(start), Some(now)) => {
+                    now.len() < start.len() || (start.len() == 1 && start[0] != now[0])
+                }
+            };
+            if done {
+                return self.stopped_event("step", None);
+            }
+            // Breakpoints met on the way stop the step
+            if let Some(id) = self.session.breakpoints.hit(&self.session.vm) {
+                return self.breakpoint_event(id);
+            }
+            count += 1;
+            if count.is_multiple_of(INTERRUPT_INTERVAL) && pause_requested() {
+                return self.stopped_event("pause", None);
+            }
+        }
+    }
+
+    // The source lines the program is at: the invocations of the macros, the outermost
+    // first, then the line of the instruction
+    fn position(&self) -> Option<Vec<(String, u32)>> {
+        let entry = self
+            .session
+            .source
+            .as_ref()?
+            .info()
+            .line_at(self.session.vm.get_pc() as u32)?;
+        let mut lines: Vec<(String, u32)> = entry
+            .expansions
+            .iter()
+            .map(|e| (e.file.clone(), e.line))
+            .collect();
+        lines.push((entry.location.file.clone(), entry.location.line));
+        Some(lines)
+    }
+
+    // The code label the program is in
+    fn label_at(&self, pc: usize) -> String {
+        self.session
+            .symbols
+            .iter()
+            .filter(|s| s.section == Some(SectionKind::Code) && s.value as usize <= pc)
+            .max_by_key(|s| s.value)
+            .map(|s| s.name.clone())
+            .unwrap_or_else(|| format!("{:#06x}", pc))
+    }
+
+    fn stack_frames(&self) -> Vec<Value> {
+        let pc = self.session.vm.get_pc();
+        let reference = format!("{:#x}", pc);
+        let entry = self
+            .session
+            .source
+            .as_ref()
+            .and_then(|source| source.info().line_at(pc as u32));
+        let entry = match entry {
+            Some(entry) => entry,
+            None => {
+                return vec![json!({
+                    "id": 0,
+                    "name": self.label_at(pc),
+                    "line": 0,
+                    "column": 0,
+                    "instructionPointerReference": reference,
+                })]
+            }
+        };
+        // Each line is in the label, or in the macro invoked by the outer line
+        let mut names = vec![self.label_at(pc)];
+        names.extend(entry.expansions.iter().map(|e| e.name.clone()));
+        let mut lines: Vec<(&str, u32, u32)> = entry
+            .expansions
+            .iter()
+            .map(|e| (e.file.as_str(), e.line, 1))
+            .collect();
+        let location = &entry.location;
+        lines.push((&location.file, location.line, location.column));
+
+        lines
+            .iter()
+            .zip(names)
+            .rev()
+            .enumerate()
+            .map(|(id, ((file, line, column), name))| {
+                json!({
+                    "id": id,
+                    "name": name,
+                    "source": source(file),
+                    "line": line,
+                    "column": column,
+                    "instructionPointerReference": reference,
+                })
+            })
+            .collect()
+    }
+
+    fn scopes(&self) -> Value {
+        let rows = self.session.vm.get_memory().len().div_ceil(MEMORY_ROW);
+        json!({ "scopes": [
+            { "name": "Registers", "variablesReference": REGISTERS, "expensive": false },
+            { "name": "State", "variablesReference": STATE, "expensive": false },
+            {
+                "name": "Memory",
+                "variablesReference": MEMORY,
+                "indexedVariables": rows,
+                "expensive": true,
+            },
+        ]})
+    }
+
+    fn variables(&self, args: &Value) -> Result<Value, String> {
+        let vm = &self.session.vm;
+        let variable = |name: String, value: String| json!({ "name": name, "value": value, "variablesReference": 0 });
+        let variables: Vec<Value> = match args["variablesReference"].as_u64() {
+            Some(REGISTERS) => (0..32u8)
+                .map(|n| {
+                    let mut variable = variable(
+                        self.session.register_name(n),
+                        vm.get_register(n).to_string(),
+                    );
+                    variable["evaluateName"] = json!(format!("r{}", n));
+                    variable
+                })
+                .collect(),
+            Some(STATE) => vec![
+                variable("pc".to_owned(), format!("{:#06x}", vm.get_pc())),
+                variable("eq_flag".to_owned(), vm.get_eq_flag().to_string()),
+                variable(
+                    "remainder".to_owned(),
+                    vm.get_modulo_remainder().to_string(),
+                ),
+            ],
+            Some(MEMORY) => {
+                let memory = vm.get_memory();
+                let start = args["start"].as_u64().unwrap_or(0) as usize;
+                let count = args["count"].as_u64().map_or(usize::MAX, |c| c as usize);
+                memory
+                    .chunks(MEMORY_ROW)
+                    .enumerate()
+                    .skip(start)
+                    .take(count)
+                    .map(|(row, bytes)| {
+                        let address = row * MEMORY_ROW;
+                        let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+                        let mut variable = variable(format!("{:#06x}", address), hex.join(" "));
+                        variable["memoryReference"] =
+                            json!(format!("{:#x}", DATA_ADDRESS + address));
+                        variable
+                    })
+                    .collect()
+            }
+            _ => return Err("Unknown variables reference".to_owned()),
+        };
+        Ok(json!({ "variables": variables }))
+    }
+
+    fn set_variable(&mut self, args: &Value) -> Result<Value, String> {
+        let name = args["name"].as_str().unwrap_or_default();
+        let text = args["value"].as_str().unwrap_or_default().trim();
+        let invalid = || format!("Invalid value \'{}\'", text);
+        let vm = &mut self.session.vm;
+        let value = match (args["variablesReference"].as_u64(), name) {
+            (Some(REGISTERS), _) => {
+                // Registers are shown like `r31 (sp)`
+                let register = name
+                    .split_whitespace()
+                    .next()
+                    .and_then(parse_register)
+                    .ok_or_else(|| format!("Unknown register \'{}\'", name))?;
+                let value = parse_value(text).ok_or_else(invalid)?;
+                vm.set_register_value(register, value);
+                value.to_string()
+            }
+            (Some(STATE), "pc") => {
+                let address = parse_number(text).ok_or_else(invalid)?;
+                if vm.set_pc(address) != 0 {
+                    return Err(format!("Address {:#x} is outside of the code", address));
+                }
+                format!("{:#06x}", address)
+            }
+            (Some(STATE), "eq_flag") => {
+                let value = match text {
+                    "true" | "1" => true,
+                    "false" | "0" => false,
+                    _ => return Err(invalid()),
+                };
+                vm.set_eq_flag(value);
+                value.to_string()
+            }
+            (Some(STATE), "remainder") => {
+                let value = parse_value(text).ok_or_else(invalid)?;
+                vm.set_modulo_remainder(value);
+                value.to_string()
+            }
+            _ => return Err(format!("\'{}\' can\'t be changed", name)),
+        };
+        Ok(json!({ "value": value }))
+    }
+
+    // An expression like for print. Comparisons give true or false, the pc is in hexadecimal.
+    fn evaluate(&self, expression: &str) -> Result<Value, String> {
+        let session = &self.session;
+        let expr = Expr::parse(expression, &session.symbols, session.aliases())
+            .map_err(|e| e.to_string())?;
+        let value = expr.evaluate(&session.vm).map_err(|e| e.to_string())?;
+        let result = match expr {
+            _ if expr.is_boolean() => (value != 0).to_string(),
+            Expr::Pc => format!("{:#06x}", value),
+            _ => value.to_string(),
+        };
+        Ok(json!({ "result": result, "variablesReference": 0 }))
+    }
+
+    // Addresses are those GDB uses: the code from 0, the data memory from DATA_ADDRESS
+    fn read_memory(&self, args: &Value) -> Result<Value, String> {
+        let reference = args["memoryReference"].as_str().unwrap_or_default();
+        let address = parse_number(reference)
+            .and_then(|address| {
+                i64::try_from(address)
+                    .ok()?
+                    .checked_add(args["offset"].as_i64().unwrap_or(0))
+            })
+            .and_then(|address| usize::try_from(address).ok())
+            .ok_or_else(|| format!("Invalid memory reference \'{}\'", reference))?;
+        let count = args["count"].as_u64().unwrap_or(0) as usize;
+        let vm = &self.session.vm;
+        let (memory, offset) = if address >= DATA_ADDRESS {
+            (vm.get_memory(), address - DATA_ADDRESS)
+        } else {
+            (vm.get_program(), address)
+        };
+        let bytes = memory
+            .get(offset..memory.len().min(offset.saturating_add(count)))
+            .unwrap_or_default();
+        Ok(json!({
+            "address": format!("{:#x}", address),
+            "data": base64(bytes),
+            "unreadableBytes": count - bytes.len(),
+        }))
+    }
+
+    fn set_breakpoints(&mut self, args: &Value) -> Result<Value, String> {
+        let path = args["source"]["path"]
+            .as_str()
+            .ok_or_else(|| "The source has no path".to_owned())?;
+        for id in self.source_breakpoints.remove(path).unwrap_or_default() {
+            let _ = self.session.breakpoints.delete(id);
+        }
+        let file = self.debug_file(path);
+        let requested = args["breakpoints"].as_array().cloned().unwrap_or_default();
+        let mut ids = vec![];
+        let mut results = vec![];
+        for breakpoint in requested {
+            let line = breakpoint["line"].as_u64().unwrap_or(0) as u32;
+            let unverified =
+                |message: String| json!({ "verified": false, "line": line, "message": message });
+            let condition = match breakpoint["condition"].as_str().map(str::trim) {
+                Some(text) if !text.is_empty() => {
+                    match Condition::parse(text, &self.session.symbols, self.session.aliases()) {
+                        Ok(condition) => Some(condition),
+                        Err(e) => {
+                            results.push(unverified(e.to_string()));
+                            continue;
+                        }
+                    }
+                }
+                _ => None,
+            };
+            let location = format!("{}:{}", file, line);
+            let session = &mut *self.session;
+            let address = match resolve_location(
+                &location,
+                &session.vm,
+                &session.symbols,
+                session.source.as_ref(),
+            ) {
+                Ok(address) => address,
+                Err(e) => {
+                    results.push(unverified(e.to_string()));
+                    continue;
+                }
+            };
+            let id = session.breakpoints.add(address, &location, condition);
+            // A hit condition N stops the N-th time
+            if let Some(count) = breakpoint["hitCondition"]
+                .as_str()
+                .and_then(|text| text.trim().parse::<u32>().ok())
+            {
+                if let Ok(breakpoint) = session.breakpoints.get_mut(id) {
+                    breakpoint.ignore_count = count.saturating_sub(1);
+                }
+            }
+            ids.push(id);
+            results.push(json!({
+                "id": id,
+                "verified": true,
+                "line": self.line_of(address, &file).unwrap_or(line),
+            }));
+        }
+        self.source_breakpoints.insert(path.to_owned(), ids);
+        Ok(json!({ "breakpoints": results }))
+    }
+
+    // The name of the file in the debug info, which is the path given to the assembler
+    fn debug_file(&self, path: &str) -> String {
+        let source = match &self.session.source {
+            Some(source) => source,
+            None => return path.to_owned(),
+        };
+        let mut files = HashSet::<&str>::new();
+        for entry in &source.info().lines {
+            files.insert(&entry.location.file);
+            files.extend(entry.expansions.iter().map(|e| e.file.as_str()));
+        }
+        let wanted = canonical(path);
+        files
+            .into_iter()
+            .find(|file| canonical(file) == wanted)
+            .unwrap_or(path)
+            .to_owned()
+    }
+
+    // The line of `file` where the code at `address` is, to show a breakpoint moved
+    // to the next line with code
+    fn line_of(&self, address: usize, file: &str) -> Option<u32> {
+        let entry = self
+            .session
+            .source
+            .as_ref()?
+            .info()
+            .line_at(address as u32)?;
+        entry
+            .expansions
+            .iter()
+            .map(|e| (e.file.as_str(), e.line))
+            .chain(std::iter::once((
+                entry.location.file.as_str(),
+                entry.location.line,
+            )))
+            .find(|(f, _)| *f == file)
+            .map(|(_, line)| line)
+    }
+}
+
+fn capabilities() -> Value {
+    json!({
+        "supportsConfigurationDoneRequest": true,
+        "supportsConditionalBreakpoints": true,
+        "supportsHitConditionalBreakpoints": true,
+        "supportsEvaluateForHovers": true,
+        "supportsSetVariable": true,
+        "supportsStepBack": true,
+        "supportsReadMemoryRequest": true,
+        "supportsTerminateRequest": true,
+        "supportsSteppingGranularity": true,
+        "exceptionBreakpointFilters": [
+            { "filter": "faults", "label": "Faults", "default": true },
+        ],
+    })
+}
+
+fn canonical(path: &str) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path))
+}
+
+fn source(file: &str) -> Value {
+    let path = canonical(file);
+    let name = path.file_name().map_or_else(
+        || file.to_owned(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    json!({ "name": name, "path": path.display().to_string() })
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (u32::from(*b) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+// Talks with the editor over stdin and stdout
+pub fn serve_stdio(session: &mut DebugSession) -> std::io::Result<()> {
+    serve(session, Box::new(std::io::stdin()), &mut std::io::stdout())
+}
+
+// Waits for an editor on this port of localhost
+pub fn serve_tcp(session: &mut DebugSession, port: u16) -> std::io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    info!("Waiting for a debug adapter client on 127.0.0.1:{}.", port);
+    let (mut stream, address) = listener.accept()?;
+    info!("Client connected from {}.", address);
+    let input = stream.try_clone()?;
+    serve(session, Box::new(input), &mut stream)
+}
+
+// Requests are read by another thread, so a running program can be paused
+fn serve(
+    session: &mut DebugSession,
+    input: Box<dyn Read + Send>,
+    output: &mut dyn Write,
+) -> std::io::Result<()> {
+    let (sender, receiver) = mpsc::channel::<Value>();
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(input);
+        while let Ok(Some(message)) = read_message(&mut reader) {
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut adapter = DapAdapter::new(session);
+    // Requests received while the program was running
+    let mut pending = VecDeque::<Value>::new();
+    while !adapter.is_disconnected() {
+        let request = match pending.pop_front() {
+            Some(request) => request,
+            None => match receiver.recv() {
+                Ok(request) => request,
+                // The editor closed the connection
+                Err(_) => break,
+            },
+        };
+        let messages = adapter.handle(&request, &mut || {
+            let mut paused = false;
+            while let Ok(request) = receiver.try_recv() {
+                paused |= request["command"] == "pause";
+                pending.push_back(request);
+            }
+            paused
+        });
+        for message in messages {
+            write_message(output, &message)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use super::expr::Expr;
+use super::memory::Examine;
+use crate::base::vm::VM;
+
+// The value of an expression, in decimal or in the format given after a `/`
+pub fn format_result(value: i32, format: Option<&Examine>) -> String {
+    match format {
+        Some(format) => format.format_number(value),
+        None => value.to_string(),
+    }
+}
+
+// An expression shown every time the program stops
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutoDisplay {
+    pub id: usize,
+    pub expr: Expr,
+    // What followed the `/`, and what it means
+    pub format: Option<(String, Examine)>,
+}
+
+impl AutoDisplay {
+    // Like "1: /x r3 = 0x0000000a"
+    pub fn show(&self, vm: &VM) -> String {
+        let format = match &self.format {
+            Some((text, _)) => format!("/{} ", text),
+            None => String::new(),
+        };
+        let value = match self.expr.evaluate(vm) {
+            Ok(value) => format_result(value, self.format.as_ref().map(|(_, f)| f)),
+            Err(e) => format!("<{}>", e),
+        };
+        format!("{}: {}{} = {}", self.id, format, self.expr, value)
+    }
+}
+
+pub struct Displays {
+    list: Vec<AutoDisplay>,
+    next_id: usize,
+}
+
+impl Displays {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            list: Vec::new(),
+            next_id: 1,
+        }
+    }
+
+    // Gives the number of the new display
+    pub fn add(&mut self, expr: Expr, format: Option<(String, Examine)>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.list.push(AutoDisplay { id, expr, format });
+        id
+    }
+
+    // Whether there was a display with this number
+    pub fn delete(&mut self, id: usize) -> bool {
+        let count = self.list.len();
+        self.list.retain(|display| display.id != id);
+        self.list.len() != count
+    }
+
+    pub fn clear(&mut self) {
+        self.list.clear();
+    }
+
+    pub fn list(&self) -> &[AutoDisplay] {
+        &self.list
+    }
+}
@@ -0,0 +1,121 @@
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::path::PathBuf;
+
+// History of the debugger's commands, kept in the home directory across sessions
+const HISTORY_FILE: &str = ".lamp_history";
+
+// Completes command names on the first word of a line, and arguments after it
+struct Completions {
+    commands: Vec<String>,
+    // Labels, register names and keywords of the commands
+    arguments: Vec<String>,
+}
+
+impl Completer for Completions {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos].rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = &line[start..pos];
+        let words = if line[..start].trim().is_empty() {
+            &self.commands
+        } else {
+            &self.arguments
+        };
+        let mut candidates: Vec<String> = words
+            .iter()
+            .filter(|candidate| candidate.starts_with(word))
+            .cloned()
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for Completions {
+    type Hint = String;
+}
+
+impl Highlighter for Completions {}
+
+impl Validator for Completions {}
+
+impl Helper for Completions {}
+
+// Reads the commands, with line editing, history and completion
+pub struct LineEditor {
+    editor: Option<Editor<Completions, DefaultHistory>>,
+    history: Option<PathBuf>,
+}
+
+impl LineEditor {
+    pub fn new(commands: Vec<String>, arguments: Vec<String>) -> Self {
+        let history = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+        let editor = match Editor::<Completions, DefaultHistory>::new() {
+            Ok(mut editor) => {
+                editor.set_helper(Some(Completions {
+                    commands,
+                    arguments,
+                }));
+                if let Some(path) = &history {
+                    // There is no history the first time
+                    let _ = editor.load_history(path);
+                }
+                Some(editor)
+            }
+            Err(_) => None,
+        };
+        Self { editor, history }
+    }
+
+    // The next line, or None at the end of the input (Ctrl-D)
+    pub fn read_line(&mut self, prompt: &str) -> Option<String> {
+        let editor = match &mut self.editor {
+            Some(editor) => editor,
+            None => return read_stdin_line(prompt),
+        };
+        loop {
+            match editor.readline(prompt) {
+                Ok(line) => {
+                    if !line.trim().is_empty() {
+                        let _ = editor.add_history_entry(line.trim());
+                    }
+                    return Some(line);
+                }
+                // Ctrl-C only drops the line being typed
+                Err(ReadlineError::Interrupted) => continue,
+                Err(_) => return None,
+            }
+        }
+    }
+
+    pub fn save_history(&mut self) {
+        if let (Some(editor), Some(path)) = (&mut self.editor, &self.history) {
+            let _ = editor.save_history(path);
+        }
+    }
+}
+
+// Without a terminal the editor can't be created, lines are read as they come
+fn read_stdin_line(prompt: &str) -> Option<String> {
+    use std::io::Write;
+    print!("{}", prompt);
+    let _ = std::io::stdout().flush();
+    let mut line = String::new();
+    match std::io::stdin().read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line),
+    }
+}
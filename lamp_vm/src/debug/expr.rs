@@ -0,0 +1,380 @@
+// Expressions of the debugger, like `*(r1 + 4) & 0xff` or `r3 == counter`.
+// Values are 32-bit and wrap around like in the VM.
+use crate::base::vm::VM;
+use lamp_common::object::Symbol;
+use lamp_common::register::{parse_register_with, RegisterAlias};
+use std::convert::TryFrom;
+
+// Different types of errors expressions can cause
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprError {
+    Syntax(String),
+    UnknownName(String),
+    DivisionByZero,
+    // Address of the word
+    OutOfMemory(usize),
+}
+
+impl std::fmt::Display for ExprError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Syntax(message) => write!(f, "{}", message),
+            Self::UnknownName(name) => {
+                write!(f, "\'{}\' is neither a register nor a label", name)
+            }
+            Self::DivisionByZero => write!(f, "Division by zero"),
+            Self::OutOfMemory(address) => write!(
+                f,
+                "Can\'t read the word at {:#x}: it is outside of the data memory",
+                address
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum UnaryOp {
+    Negate,
+    Not,
+    Complement,
+    // Reads the big-endian word at the address in the data memory
+    Deref,
+}
+
+impl UnaryOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::Negate => "-",
+            Self::Not => "!",
+            Self::Complement => "~",
+            Self::Deref => "*",
+        }
+    }
+}
+
+// Binary operators, from the loosest to the tightest
+const BINARY_OPS: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", "<=", ">", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+// Every operator, the longest first so `<=` isn't read as `<`
+const OPERATORS: &[&str] = &[
+    "||", "&&", "==", "!=", "<=", ">=", "<<", ">>", "<", ">", "+", "-", "*", "/", "%", "&", "|",
+    "^", "!", "~",
+];
+
+fn precedence(op: &str) -> usize {
+    BINARY_OPS
+        .iter()
+        .position(|ops| ops.contains(&op))
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Constant(i32),
+    // The index, and the name it was written with
+    Register(u8, String),
+    Pc,
+    EqFlag,
+    Remainder,
+    // A label from the symbol table, and its address
+    Symbol(String, i32),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    // Labels and register aliases are resolved now, so the expression keeps working
+    // without the symbol table
+    pub fn parse(
+        text: &str,
+        symbols: &[Symbol],
+        aliases: &[RegisterAlias],
+    ) -> Result<Self, ExprError> {
+        let tokens = tokenize(text)?;
+        let mut parser = ExprParser {
+            tokens: &tokens,
+            at: 0,
+            symbols,
+            aliases,
+        };
+        let expr = parser.binary(0)?;
+        match parser.tokens.get(parser.at) {
+            None => Ok(expr),
+            Some(token) => Err(ExprError::Syntax(format!(
+                "Unexpected \'{}\' in \'{}\'",
+                token,
+                text.trim()
+            ))),
+        }
+    }
+
+    pub fn evaluate(&self, vm: &VM) -> Result<i32, ExprError> {
+        Ok(match self {
+            Self::Constant(value) | Self::Symbol(_, value) => *value,
+            Self::Register(n, _) => *vm.get_register(*n),
+            Self::Pc => vm.get_pc() as i32,
+            Self::EqFlag => i32::from(vm.get_eq_flag()),
+            Self::Remainder => vm.get_modulo_remainder(),
+            Self::Unary(op, operand) => {
+                let value = operand.evaluate(vm)?;
+                match op {
+                    UnaryOp::Negate => value.wrapping_neg(),
+                    UnaryOp::Not => i32::from(value == 0),
+                    UnaryOp::Complement => !value,
+                    UnaryOp::Deref => {
+                        let address = value as u32 as usize;
+                        let bytes = vm
+                            .get_memory()
+                            .get(address..address.saturating_add(4))
+                            .ok_or(ExprError::OutOfMemory(address))?;
+                        i32::from_be_bytes(<[u8; 4]>::try_from(bytes).unwrap())
+                    }
+                }
+            }
+            // Only evaluated when needed, like in C
+            Self::Binary("&&", a, b) => i32::from(a.evaluate(vm)? != 0 && b.evaluate(vm)? != 0),
+            Self::Binary("||", a, b) => i32::from(a.evaluate(vm)? != 0 || b.evaluate(vm)? != 0),
+            Self::Binary(op, a, b) => {
+                let (a, b) = (a.evaluate(vm)?, b.evaluate(vm)?);
+                match *op {
+                    "|" => a | b,
+                    "^" => a ^ b,
+                    "&" => a & b,
+                    "==" => i32::from(a == b),
+                    "!=" => i32::from(a != b),
+                    "<" => i32::from(a < b),
+                    "<=" => i32::from(a <= b),
+                    ">" => i32::from(a > b),
+                    ">=" => i32::from(a >= b),
+                    "<<" => a.wrapping_shl(b as u32),
+                    ">>" => a.wrapping_shr(b as u32),
+                    "+" => a.wrapping_add(b),
+                    "-" => a.wrapping_sub(b),
+                    "*" => a.wrapping_mul(b),
+                    "/" if b == 0 => return Err(ExprError::DivisionByZero),
+                    "/" => a.wrapping_div(b),
+                    "%" if b == 0 => return Err(ExprError::DivisionByZero),
+                    _ => a.wrapping_rem(b),
+                }
+            }
+        })
+    }
+
+    // Whether the value is a truth value, like for `r3 == 10`
+    pub fn is_boolean(&self) -> bool {
+        match self {
+            Self::Unary(UnaryOp::Not, _) => true,
+            Self::Binary(op, _, _) => ["||", "&&", "==", "!=", "<", "<=", ">", ">="].contains(op),
+            _ => false,
+        }
+    }
+
+    // The registers the expression reads, in order
+    pub fn registers(&self) -> Vec<(u8, String)> {
+        let mut registers = Vec::<(u8, String)>::new();
+        self.collect_registers(&mut registers);
+        registers
+    }
+
+    fn collect_registers(&self, registers: &mut Vec<(u8, String)>) {
+        match self {
+            Self::Register(n, name) if !registers.iter().any(|(index, _)| index == n) => {
+                registers.push((*n, name.clone()))
+            }
+            Self::Unary(_, operand) => operand.collect_registers(registers),
+            Self::Binary(_, a, b) => {
+                a.collect_registers(registers);
+                b.collect_registers(registers);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Constant(value) => write!(f, "{}", value),
+            Self::Register(_, name) | Self::Symbol(name, _) => write!(f, "{}", name),
+            Self::Pc => write!(f, "pc"),
+            Self::EqFlag => write!(f, "eq"),
+            Self::Remainder => write!(f, "remainder"),
+            Self::Unary(op, operand) => match **operand {
+                Self::Binary(..) => write!(f, "{}({})", op.symbol(), operand),
+                _ => write!(f, "{}{}", op.symbol(), operand),
+            },
+            Self::Binary(op, a, b) => {
+                // Parentheses only where the precedence needs them
+                let needs = |operand: &Expr, right: bool| match operand {
+                    Self::Binary(inner, _, _) => {
+                        precedence(inner) < precedence(op)
+                            || (right && precedence(inner) == precedence(op))
+                    }
+                    _ => false,
+                };
+                let side = |operand: &Expr, right: bool| {
+                    if needs(operand, right) {
+                        format!("({})", operand)
+                    } else {
+                        operand.to_string()
+                    }
+                };
+                write!(f, "{} {} {}", side(a, false), op, side(b, true))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i32),
+    Name(String),
+    Operator(&'static str),
+    Open,
+    Close,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number(value) => write!(f, "{}", value),
+            Self::Name(name) => write!(f, "{}", name),
+            Self::Operator(op) => write!(f, "{}", op),
+            Self::Open => write!(f, "("),
+            Self::Close => write!(f, ")"),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, ExprError> {
+    let mut tokens = Vec::<Token>::new();
+    let mut rest = text.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let word_len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+            .unwrap_or(rest.len());
+        if c.is_ascii_digit() {
+            let word = &rest[..word_len];
+            // Up to 32 bits, 0xffffffff being -1
+            let value = super::breakpoints::parse_number(word)
+                .and_then(|value| u32::try_from(value).ok())
+                .ok_or_else(|| ExprError::Syntax(format!("Invalid number \'{}\'", word)))?;
+            tokens.push(Token::Number(value as i32));
+            rest = &rest[word_len..];
+        } else if c.is_ascii_alphabetic() || c == '_' || c == '.' {
+            tokens.push(Token::Name(rest[..word_len].to_owned()));
+            rest = &rest[word_len..];
+        } else if c == '(' || c == ')' {
+            tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            rest = &rest[1..];
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            tokens.push(Token::Operator(op));
+            rest = &rest[op.len()..];
+        } else {
+            return Err(ExprError::Syntax(format!("Unexpected \'{}\'", c)));
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+struct ExprParser<'a> {
+    tokens: &'a [Token],
+    at: usize,
+    symbols: &'a [Symbol],
+    aliases: &'a [RegisterAlias],
+}
+
+impl ExprParser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.at)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.at).cloned();
+        self.at += 1;
+        token
+    }
+
+    // Operators of this level and tighter ones
+    fn binary(&mut self, level: usize) -> Result<Expr, ExprError> {
+        if level == BINARY_OPS.len() {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        while let Some(Token::Operator(op)) = self.peek() {
+            let op = *op;
+            if !BINARY_OPS[level].contains(&op) {
+                break;
+            }
+            self.at += 1;
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ExprError> {
+        let op = match self.peek() {
+            Some(Token::Operator("-")) => UnaryOp::Negate,
+            Some(Token::Operator("!")) => UnaryOp::Not,
+            Some(Token::Operator("~")) => UnaryOp::Complement,
+            Some(Token::Operator("*")) => UnaryOp::Deref,
+            _ => return self.primary(),
+        };
+        self.at += 1;
+        let operand = self.unary()?;
+        // So -5 stays a constant
+        Ok(match (op, operand) {
+            (UnaryOp::Negate, Expr::Constant(value)) => Expr::Constant(value.wrapping_neg()),
+            (op, operand) => Expr::Unary(op, Box::new(operand)),
+        })
+    }
+
+    fn primary(&mut self) -> Result<Expr, ExprError> {
+        match self.advance() {
+            Some(Token::Number(value)) => Ok(Expr::Constant(value)),
+            Some(Token::Name(name)) => self.name(name),
+            Some(Token::Open) => {
+                let expr = self.binary(0)?;
+                match self.advance() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err(ExprError::Syntax("Missing \')\'".to_owned())),
+                }
+            }
+            Some(token) => Err(ExprError::Syntax(format!("Unexpected \'{}\'", token))),
+            None => Err(ExprError::Syntax("Incomplete expression".to_owned())),
+        }
+    }
+
+    fn name(&self, name: String) -> Result<Expr, ExprError> {
+        if let Some(register) = parse_register_with(&name, self.aliases) {
+            return Ok(Expr::Register(register, name));
+        }
+        match name.as_str() {
+            "pc" => return Ok(Expr::Pc),
+            "eq" => return Ok(Expr::EqFlag),
+            "remainder" => return Ok(Expr::Remainder),
+            _ => {}
+        }
+        match self
+            .symbols
+            .iter()
+            .find(|s| s.name == name && s.section.is_some())
+        {
+            Some(symbol) => Ok(Expr::Symbol(name, symbol.value as i32)),
+            None => Err(ExprError::UnknownName(name)),
+        }
+    }
+}
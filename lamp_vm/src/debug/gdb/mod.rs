@@ -0,0 +1,505 @@
+pub mod packet;
+
+use super::breakpoints::{WatchKind, WatchTarget};
+use super::session::{DebugSession, Stop};
+use crate::base::vm::VMError;
+use log::info;
+use packet::{encode, from_hex, to_hex, Incoming, PacketReader};
+use std::convert::TryInto;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+// GDB sees the code and the data memory in one address space: the code from 0,
+// the data memory from this address
+pub const DATA_ADDRESS: usize = 0x8000_0000;
+
+// r0 to r31, then pc, eq_flag and remainder, see target_xml
+const GDB_REGISTER_COUNT: usize = 35;
+const PC_REGISTER: usize = 32;
+const EQ_FLAG_REGISTER: usize = 33;
+const REMAINDER_REGISTER: usize = 34;
+
+const SUPPORTED: &str =
+    "PacketSize=1000;qXfer:features:read+;swbreak+;QStartNoAckMode+;ReverseStep+;ReverseContinue+";
+
+// Signals GDB is told about when the program stops
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGFPE: u8 = 8;
+
+// What to send back to GDB
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+    Reply(String),
+    // The connection ends, after the reply if there is one
+    Close(Option<String>),
+}
+
+// Answers the packets of GDB, on top of a debug session
+pub struct GdbTarget<'a> {
+    session: &'a mut DebugSession,
+    // Answer to `?`
+    last_stop: String,
+}
+
+impl<'a> GdbTarget<'a> {
+    pub fn new(session: &'a mut DebugSession) -> Self {
+        Self {
+            session,
+            last_stop: format!("S{:02x}", SIGTRAP),
+        }
+    }
+
+    // Answers one packet. `interrupted` tells whether GDB asked to stop the running program.
+    pub fn handle(&mut self, packet: &str, interrupted: &mut dyn FnMut() -> bool) -> Response {
+        let reply = match packet.chars().next() {
+            Some('?') => self.last_stop.clone(),
+            Some('g') => self.read_registers(),
+            Some('G') => self.write_registers(&packet[1..]),
+            Some('p') => self.read_register(&packet[1..]),
+            Some('P') => self.write_register(&packet[1..]),
+            Some('m') => self.read_memory(&packet[1..]),
+            Some('M') => self.write_memory(&packet[1..]),
+            Some('s') => self.resume(&packet[1..], true, interrupted),
+            Some('c') => self.resume(&packet[1..], false, interrupted),
+            Some('b') => self.reverse(&packet[1..]),
+            Some('Z') => self.set_breakpoint(&packet[1..], true),
+            Some('z') => self.set_breakpoint(&packet[1..], false),
+            // There is one thread
+            Some('H') | Some('T') => "OK".to_owned(),
+            Some('k') => return Response::Close(None),
+            Some('D') => return Response::Close(Some("OK".to_owned())),
+            _ => self.query(packet),
+        };
+        Response::Reply(reply)
+    }
+
+    fn query(&self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return SUPPORTED.to_owned();
+        }
+        if let Some(args) = packet.strip_prefix("qXfer:features:read:") {
+            return read_features(args);
+        }
+        match packet {
+            "QStartNoAckMode" => "OK",
+            "qAttached" => "1",
+            "qC" => "QC1",
+            "qfThreadInfo" => "m1",
+            "qsThreadInfo" => "l",
+            "qSymbol::" => "OK",
+            // Unsupported packets get an empty answer
+            _ => "",
+        }
+        .to_owned()
+    }
+
+    fn register(&self, n: usize) -> Option<i32> {
+        let vm = &self.session.vm;
+        match n {
+            0..=31 => Some(*vm.get_register(n as u8)),
+            PC_REGISTER => Some(vm.get_pc() as i32),
+            EQ_FLAG_REGISTER => Some(vm.get_eq_flag() as i32),
+            REMAINDER_REGISTER => Some(vm.get_modulo_remainder()),
+            _ => None,
+        }
+    }
+
+    fn set_register(&mut self, n: usize, value: i32) -> bool {
+        let vm = &mut self.session.vm;
+        match n {
+            0..=31 => vm.set_register_value(n as u8, value),
+            PC_REGISTER => return vm.set_pc(value as usize) == 0,
+            EQ_FLAG_REGISTER => vm.set_eq_flag(value != 0),
+            REMAINDER_REGISTER => vm.set_modulo_remainder(value),
+            _ => return false,
+        }
+        true
+    }
+
+    fn read_registers(&self) -> String {
+        (0..GDB_REGISTER_COUNT)
+            .filter_map(|n| self.register(n))
+            .map(encode_register)
+            .collect()
+    }
+
+    fn write_registers(&mut self, args: &str) -> String {
+        let values: Option<Vec<i32>> = (0..GDB_REGISTER_COUNT)
+            .map(|n| args.get(n * 8..n * 8 + 8).and_then(decode_register))
+            .collect();
+        match values {
+            Some(values) => {
+                for (n, value) in values.into_iter().enumerate() {
+                    self.set_register(n, value);
+                }
+                "OK".to_owned()
+            }
+            None => "E01".to_owned(),
+        }
+    }
+
+    // `p<n>`
+    fn read_register(&self, args: &str) -> String {
+        usize::from_str_radix(args, 16)
+            .ok()
+            .and_then(|n| self.register(n))
+            .map(encode_register)
+            .unwrap_or_else(|| "E01".to_owned())
+    }
+
+    // `P<n>=<value>`
+    fn write_register(&mut self, args: &str) -> String {
+        let parsed = args.split_once('=').and_then(|(n, value)| {
+            Some((usize::from_str_radix(n, 16).ok()?, decode_register(value)?))
+        });
+        match parsed {
+            Some((n, value)) if self.set_register(n, value) => "OK".to_owned(),
+            _ => "E01".to_owned(),
+        }
+    }
+
+    // The code or the data memory seen at `address`, and the offset in it
+    fn memory(&mut self, address: usize) -> (&mut [u8], usize) {
+        let vm = &mut self.session.vm;
+        if address >= DATA_ADDRESS {
+            (vm.get_memory_mut(), address - DATA_ADDRESS)
+        } else {
+            (vm.get_program_mut(), address)
+        }
+    }
+
+    // `m<address>,<length>`. Gives the bytes up to the end of the memory.
+    fn read_memory(&mut self, args: &str) -> String {
+        let (address, length) = match parse_range(args) {
+            Some(range) => range,
+            None => return "E01".to_owned(),
+        };
+        let (memory, offset) = self.memory(address);
+        if length == 0 {
+            return String::new();
+        }
+        if offset >= memory.len() {
+            return "E14".to_owned();
+        }
+        let end = memory.len().min(offset.saturating_add(length));
+        to_hex(&memory[offset..end])
+    }
+
+    // `M<address>,<length>:<bytes>`, the code can be patched as well
+    fn write_memory(&mut self, args: &str) -> String {
+        let parsed = args.split_once(':').and_then(|(range, data)| {
+            let (address, length) = parse_range(range)?;
+            Some((
+                address,
+                from_hex(data).filter(|bytes| bytes.len() == length)?,
+            ))
+        });
+        let (address, bytes) = match parsed {
+            Some(parsed) => parsed,
+            None => return "E01".to_owned(),
+        };
+        let (memory, offset) = self.memory(address);
+        match memory.get_mut(offset..offset.saturating_add(bytes.len())) {
+            Some(target) => {
+                target.copy_from_slice(&bytes);
+                "OK".to_owned()
+            }
+            None => "E14".to_owned(),
+        }
+    }
+
+    // `s[address]` and `c[address]`
+    fn resume(
+        &mut self,
+        args: &str,
+        single: bool,
+        interrupted: &mut dyn FnMut() -> bool,
+    ) -> String {
+        if !args.is_empty() {
+            match usize::from_str_radix(args, 16) {
+                Ok(address) if self.session.vm.set_pc(address) == 0 => {}
+                _ => return "E01".to_owned(),
+            }
+        }
+        let reply = if self.session.is_terminated() {
+            "W00".to_owned()
+        } else if single {
+            let stop = self.session.step();
+            self.stop_reply(stop)
+        } else {
+            let stop = self.session.resume_until(interrupted);
+            self.stop_reply(stop)
+        };
+        self.last_stop = reply.clone();
+        reply
+    }
+
+    // `bs` and `bc`, going back in the history of the session
+    fn reverse(&mut self, args: &str) -> String {
+        let reply = match args {
+            "s" if self.session.reverse_step() => format!("S{:02x}", SIGTRAP),
+            "c" if self.session.reverse_continue().is_some() => {
+                format!("T{:02x}swbreak:;", SIGTRAP)
+            }
+            // The oldest instruction recorded was reached
+            "s" | "c" => format!("T{:02x}replaylog:begin;", SIGTRAP),
+            _ => return String::new(),
+        };
+        self.last_stop = reply.clone();
+        reply
+    }
+
+    fn stop_reply(&mut self, stop: Stop) -> String {
+        match stop {
+            Stop::Step => format!("S{:02x}", SIGTRAP),
+            Stop::Breakpoint(_) => format!("T{:02x}swbreak:;", SIGTRAP),
+            Stop::Watch(hit) => match hit.target {
+                WatchTarget::Memory { address, .. } => {
+                    let kind = match hit.kind {
+                        WatchKind::Change => "watch",
+                        WatchKind::Read => "rwatch",
+                    };
+                    format!("T{:02x}{}:{:x};", SIGTRAP, kind, DATA_ADDRESS + address)
+                }
+                WatchTarget::Register(_) => format!("S{:02x}", SIGTRAP),
+            },
+            Stop::Fault(e) => {
+                // Stays at the faulting instruction, so it can be inspected
+                let vm = &mut self.session.vm;
+                vm.set_pc(vm.get_instruction_pc());
+                let signal = match e {
+                    VMError::DivisionByZero => SIGFPE,
+                    VMError::InvalidOpcodeError
+                    | VMError::UnexpectedTokenError
+                    | VMError::EndOfCode
+                    | VMError::InvalidRegister(_) => SIGILL,
+                };
+                format!("S{:02x}", signal)
+            }
+            Stop::Exited => "W00".to_owned(),
+            Stop::Interrupted => format!("S{:02x}", SIGINT),
+        }
+    }
+
+    // `Z<type>,<address>,<kind>` and `z...`: software breakpoints (type 0),
+    // and watchpoints on the data memory (2 for writes, 3 for reads)
+    fn set_breakpoint(&mut self, args: &str, insert: bool) -> String {
+        let fields: Vec<&str> = args.split(',').collect();
+        let (kind, address, size) = match fields.as_slice() {
+            [kind, address, size] => match (
+                usize::from_str_radix(address, 16),
+                usize::from_str_radix(size, 16),
+            ) {
+                (Ok(address), Ok(size)) => (*kind, address, size),
+                _ => return "E01".to_owned(),
+            },
+            _ => return "E01".to_owned(),
+        };
+        let breakpoints = &mut self.session.breakpoints;
+        match kind {
+            "0" => {
+                if address >= self.session.vm.get_program().len() {
+                    return "E01".to_owned();
+                }
+                let existing = breakpoints
+                    .list()
+                    .iter()
+                    .find(|b| b.address == address)
+                    .map(|b| b.id);
+                match (existing, insert) {
+                    (None, true) => {
+                        breakpoints.add(address, &format!("{:#x}", address), None);
+                    }
+                    (Some(id), false) => {
+                        let _ = breakpoints.delete(id);
+                    }
+                    _ => {}
+                }
+                "OK".to_owned()
+            }
+            "2" | "3" => {
+                let watch_kind = if kind == "2" {
+                    WatchKind::Change
+                } else {
+                    WatchKind::Read
+                };
+                // Nothing reads the data memory, see WatchTarget::parse
+                if watch_kind == WatchKind::Read
+                    || address < DATA_ADDRESS
+                    || ![1, 2, 4].contains(&size)
+                {
+                    return "E01".to_owned();
+                }
+                let target = WatchTarget::Memory {
+                    address: address - DATA_ADDRESS,
+                    size,
+                };
+                if target.value(&self.session.vm).is_none() {
+                    return "E14".to_owned();
+                }
+                let existing = breakpoints
+                    .watchpoints()
+                    .iter()
+                    .find(|w| w.target == target && w.kind == watch_kind)
+                    .map(|w| w.id);
+                match (existing, insert) {
+                    (None, true) => {
+                        breakpoints.add_watch(target, watch_kind);
+                    }
+                    (Some(id), false) => {
+                        let _ = breakpoints.delete(id);
+                    }
+                    _ => {}
+                }
+                "OK".to_owned()
+            }
+            _ => String::new(),
+        }
+    }
+}
+
+// Registers are sent as 32-bit little-endian numbers
+fn encode_register(value: i32) -> String {
+    to_hex(&value.to_le_bytes())
+}
+
+fn decode_register(text: &str) -> Option<i32> {
+    let bytes = from_hex(text)?;
+    let bytes: [u8; 4] = bytes.as_slice().try_into().ok()?;
+    Some(i32::from_le_bytes(bytes))
+}
+
+// `<address>,<length>` in hexadecimal
+fn parse_range(text: &str) -> Option<(usize, usize)> {
+    let (address, length) = text.split_once(',')?;
+    Some((
+        usize::from_str_radix(address, 16).ok()?,
+        usize::from_str_radix(length, 16).ok()?,
+    ))
+}
+
+// `target.xml:<offset>,<length>`
+fn read_features(args: &str) -> String {
+    let (offset, length) = match args.strip_prefix("target.xml:").and_then(parse_range) {
+        Some(range) => range,
+        None => return "E00".to_owned(),
+    };
+    let xml = target_xml();
+    let xml = xml.as_bytes();
+    if offset > xml.len() {
+        return "E00".to_owned();
+    }
+    let end = xml.len().min(offset.saturating_add(length));
+    let more = if end < xml.len() { "m" } else { "l" };
+    format!("{}{}", more, String::from_utf8_lossy(&xml[offset..end]))
+}
+
+// The registers of lamp, described for GDB
+pub fn target_xml() -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\"?>\n<!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n<target version=\"1.0\">\n  <feature name=\"org.lamp.core\">\n",
+    );
+    for n in 0..32 {
+        xml += &format!(
+            "    <reg name=\"r{}\" bitsize=\"32\" type=\"int32\" regnum=\"{}\"/>\n",
+            n, n
+        );
+    }
+    for (name, n, kind) in &[
+        ("pc", PC_REGISTER, "code_ptr"),
+        ("eq_flag", EQ_FLAG_REGISTER, "int32"),
+        ("remainder", REMAINDER_REGISTER, "int32"),
+    ] {
+        xml += &format!(
+            "    <reg name=\"{}\" bitsize=\"32\" type=\"{}\" regnum=\"{}\"/>\n",
+            name, kind, n
+        );
+    }
+    xml + "  </feature>\n</target>\n"
+}
+
+// Waits for GDB on localhost, then debugs the program until it detaches
+pub fn serve(session: &mut DebugSession, port: u16) -> std::io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    info!(
+        "Waiting for GDB on 127.0.0.1:{}, connect with \"target remote :{}\".",
+        port, port
+    );
+    let (mut stream, address) = listener.accept()?;
+    info!("GDB connected from {}.", address);
+    let mut target = GdbTarget::new(session);
+    let mut reader = PacketReader::new();
+    let mut acks = true;
+    let mut last_sent = Vec::<u8>::new();
+    let mut buffer = [0u8; 4096];
+    loop {
+        let incoming = match reader.next_message() {
+            Some(incoming) => incoming,
+            None => {
+                let read = stream.read(&mut buffer)?;
+                if read == 0 {
+                    info!("GDB disconnected.");
+                    return Ok(());
+                }
+                reader.push(&buffer[..read]);
+                continue;
+            }
+        };
+        let response = match incoming {
+            Incoming::Packet(packet) => {
+                if acks {
+                    stream.write_all(b"+")?;
+                }
+                let response = {
+                    let stream = &stream;
+                    let reader = &mut reader;
+                    target.handle(&packet, &mut || poll_interrupt(stream, reader))
+                };
+                if packet == "QStartNoAckMode" {
+                    acks = false;
+                }
+                response
+            }
+            Incoming::BadChecksum => {
+                if acks {
+                    stream.write_all(b"-")?;
+                }
+                continue;
+            }
+            Incoming::Interrupt => Response::Reply(format!("S{:02x}", SIGINT)),
+            Incoming::Nack => {
+                stream.write_all(&last_sent)?;
+                continue;
+            }
+            Incoming::Ack => continue,
+        };
+        match response {
+            Response::Reply(reply) => {
+                last_sent = encode(&reply);
+                stream.write_all(&last_sent)?;
+            }
+            Response::Close(reply) => {
+                if let Some(reply) = reply {
+                    stream.write_all(&encode(&reply))?;
+                }
+                info!("GDB detached.");
+                return Ok(());
+            }
+        }
+    }
+}
+
+// Reads what GDB sent while the program runs, without waiting
+fn poll_interrupt(mut stream: &TcpStream, reader: &mut PacketReader) -> bool {
+    let mut buffer = [0u8; 64];
+    if stream.set_nonblocking(true).is_err() {
+        return false;
+    }
+    if let Ok(read) = stream.read(&mut buffer) {
+        reader.push(&buffer[..read]);
+    }
+    let _ = stream.set_nonblocking(false);
+    reader.take_interrupt()
+}
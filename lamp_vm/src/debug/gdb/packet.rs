@@ -0,0 +1,132 @@
+// Framing of the GDB Remote Serial Protocol. A packet is `$data#xx`, `xx` being the
+// checksum of the data in hexadecimal, and the receiver answers `+` or `-` to ask it again.
+
+const INTERRUPT: u8 = 0x03;
+
+// What GDB sent
+#[derive(Debug, Clone, PartialEq)]
+pub enum Incoming {
+    Packet(String),
+    // A packet was garbled, GDB will send it again after a `-`
+    BadChecksum,
+    // Ctrl-C, to stop the running program
+    Interrupt,
+    Ack,
+    Nack,
+}
+
+// Splits the bytes received into packets, as they arrive
+pub struct PacketReader {
+    pending: Vec<u8>,
+}
+
+#[allow(clippy::new_without_default)]
+impl PacketReader {
+    pub fn new() -> Self {
+        Self { pending: vec![] }
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+    }
+
+    // The next complete message, or None until more bytes are received
+    pub fn next_message(&mut self) -> Option<Incoming> {
+        loop {
+            let incoming = match *self.pending.first()? {
+                b'+' => Incoming::Ack,
+                b'-' => Incoming::Nack,
+                INTERRUPT => Incoming::Interrupt,
+                b'$' => return self.next_packet(),
+                // Noise between packets
+                _ => {
+                    self.pending.remove(0);
+                    continue;
+                }
+            };
+            self.pending.remove(0);
+            return Some(incoming);
+        }
+    }
+
+    // Whether GDB asked to stop the program. Only Ctrl-C is sent while it runs,
+    // after the acknowledgement of the packet that started it.
+    pub fn take_interrupt(&mut self) -> bool {
+        while let Some(b'+') = self.pending.first() {
+            self.pending.remove(0);
+        }
+        if self.pending.first() == Some(&INTERRUPT) {
+            self.pending.remove(0);
+            return true;
+        }
+        false
+    }
+
+    fn next_packet(&mut self) -> Option<Incoming> {
+        let end = self.pending.iter().position(|b| *b == b'#')?;
+        if self.pending.len() < end + 3 {
+            return None;
+        }
+        let data = &self.pending[1..end];
+        let expected = std::str::from_utf8(&self.pending[end + 1..end + 3])
+            .ok()
+            .and_then(|text| u8::from_str_radix(text, 16).ok());
+        let incoming = if expected == Some(checksum(data)) {
+            Incoming::Packet(String::from_utf8_lossy(&unescape(data)).into_owned())
+        } else {
+            Incoming::BadChecksum
+        };
+        self.pending.drain(..end + 3);
+        Some(incoming)
+    }
+}
+
+pub fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+// Frames the data as a packet, escaping the characters with a meaning
+pub fn encode(data: &str) -> Vec<u8> {
+    let mut escaped = Vec::<u8>::with_capacity(data.len());
+    for b in data.bytes() {
+        if [b'$', b'#', b'}', b'*'].contains(&b) {
+            escaped.push(b'}');
+            escaped.push(b ^ 0x20);
+        } else {
+            escaped.push(b);
+        }
+    }
+    let mut packet = vec![b'$'];
+    packet.extend_from_slice(&escaped);
+    packet.extend_from_slice(format!("#{:02x}", checksum(&escaped)).as_bytes());
+    packet
+}
+
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::<u8>::with_capacity(data.len());
+    let mut iter = data.iter();
+    while let Some(b) = iter.next() {
+        match (b, iter.as_slice().first()) {
+            (b'}', Some(escaped)) => {
+                bytes.push(escaped ^ 0x20);
+                iter.next();
+            }
+            _ => bytes.push(*b),
+        }
+    }
+    bytes
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,129 @@
+use crate::base::vm::VM;
+use std::collections::VecDeque;
+
+// Instructions remembered by default, each one takes a few dozen bytes
+pub const DEFAULT_HISTORY_SIZE: usize = 10_000;
+
+// What an instruction changed, to undo it.
+// Instructions only change registers, flags and the pc: none writes the data memory.
+#[derive(Debug, Clone)]
+struct Change {
+    // Where the instruction was
+    pc: usize,
+    // Registers it wrote, with their value before
+    registers: Vec<(u8, i32)>,
+    modulo_remainder: i32,
+    eq_flag: bool,
+}
+
+// The state of the VM before an instruction, see History::before and History::after
+pub struct Before {
+    pc: usize,
+    registers: [i32; 32],
+    modulo_remainder: i32,
+    eq_flag: bool,
+}
+
+// The last instructions executed, to go back in time.
+// Instructions are numbered from 1 since the program started, the oldest ones are
+// forgotten once there are more than `size` of them.
+pub struct History {
+    changes: VecDeque<Change>,
+    size: usize,
+    // Number of instructions executed since the start
+    count: u64,
+}
+
+impl History {
+    pub fn new(size: usize) -> Self {
+        Self {
+            changes: VecDeque::new(),
+            size,
+            count: 0,
+        }
+    }
+
+    // Number of instructions executed, the current instruction is the next one
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    // Number of the oldest instruction that can be undone
+    pub fn oldest(&self) -> u64 {
+        self.count - self.changes.len() as u64 + 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn set_size(&mut self, size: usize) {
+        self.size = size;
+        while self.changes.len() > size {
+            self.changes.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.changes.clear();
+        self.count = 0;
+    }
+
+    pub fn before(vm: &VM) -> Before {
+        Before {
+            pc: vm.get_pc(),
+            registers: *vm.get_registers(),
+            modulo_remainder: vm.get_modulo_remainder(),
+            eq_flag: vm.get_eq_flag(),
+        }
+    }
+
+    // Records an instruction executed after `before` was taken
+    pub fn after(&mut self, before: Before, vm: &VM) {
+        self.count += 1;
+        if self.size == 0 {
+            return;
+        }
+        let registers = before
+            .registers
+            .iter()
+            .zip(vm.get_registers())
+            .enumerate()
+            .filter(|(_, (old, new))| old != new)
+            .map(|(i, (old, _))| (i as u8, *old))
+            .collect();
+        if self.changes.len() == self.size {
+            self.changes.pop_front();
+        }
+        self.changes.push_back(Change {
+            pc: before.pc,
+            registers,
+            modulo_remainder: before.modulo_remainder,
+            eq_flag: before.eq_flag,
+        });
+    }
+
+    // Undoes the last instruction. Returns false when there is nothing left to undo.
+    pub fn undo(&mut self, vm: &mut VM) -> bool {
+        let change = match self.changes.pop_back() {
+            Some(change) => change,
+            None => return false,
+        };
+        for (register, value) in change.registers {
+            vm.set_register_value(register, value);
+        }
+        vm.set_modulo_remainder(change.modulo_remainder);
+        vm.set_eq_flag(change.eq_flag);
+        vm.set_pc(change.pc);
+        self.count -= 1;
+        true
+    }
+}
@@ -0,0 +1,219 @@
+use super::breakpoints::{parse_number, resolve_location, BreakpointError};
+use super::session::DebugSession;
+use lamp_common::container::SectionKind;
+
+// The VM has two address spaces: the program, and the data memory
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Space {
+    Code,
+    Data,
+}
+
+impl Space {
+    pub fn bytes(self, session: &DebugSession) -> &[u8] {
+        match self {
+            Self::Code => session.vm.get_program(),
+            Self::Data => session.vm.get_memory(),
+        }
+    }
+
+    // Whether a label of this section is in the space
+    fn contains(self, section: Option<SectionKind>) -> bool {
+        match self {
+            Self::Code => section == Some(SectionKind::Code),
+            Self::Data => section.is_some_and(|section| section.is_memory()),
+        }
+    }
+}
+
+impl std::fmt::Display for Space {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Code => write!(f, "code"),
+            Self::Data => write!(f, "data"),
+        }
+    }
+}
+
+// Finds `[code:|data:]<address|label|file:line|pc>`. Labels and lines tell their space,
+// addresses are in `default` unless prefixed.
+pub fn parse_address(
+    text: &str,
+    default: Space,
+    session: &DebugSession,
+) -> Result<(Space, usize), BreakpointError> {
+    let (space, text) = if let Some(text) = text.strip_prefix("code:") {
+        (Some(Space::Code), text)
+    } else if let Some(text) = text.strip_prefix("data:") {
+        (Some(Space::Data), text)
+    } else {
+        (None, text)
+    };
+    if let Some(address) = parse_number(text) {
+        return Ok((space.unwrap_or(default), address));
+    }
+    if text == "pc" {
+        return Ok((Space::Code, session.vm.get_pc()));
+    }
+    let label = session.symbols.iter().find(|symbol| {
+        symbol.name == text
+            && space.is_none_or(|space| space.contains(symbol.section))
+            && (Space::Code.contains(symbol.section) || Space::Data.contains(symbol.section))
+    });
+    match label {
+        Some(label) if Space::Data.contains(label.section) => {
+            Ok((Space::Data, label.value as usize))
+        }
+        _ if space == Some(Space::Data) => Err(BreakpointError::InvalidLocation(text.to_owned())),
+        _ => resolve_location(text, &session.vm, &session.symbols, session.source.as_ref())
+            .map(|address| (Space::Code, address)),
+    }
+}
+
+// `size` bytes at `address`, or an error telling the range is outside of the space
+pub fn read(
+    session: &DebugSession,
+    space: Space,
+    address: usize,
+    size: usize,
+) -> Result<&[u8], BreakpointError> {
+    let bytes = space.bytes(session);
+    bytes
+        .get(address..address.saturating_add(size))
+        .ok_or(match space {
+            Space::Code => BreakpointError::OutOfCode(address),
+            Space::Data => BreakpointError::OutOfMemory(address, size),
+        })
+}
+
+// The closest label at or before the address, like `<loop+4>`
+pub fn label_at(session: &DebugSession, space: Space, address: usize) -> Option<String> {
+    session
+        .symbols
+        .iter()
+        .filter(|symbol| space.contains(symbol.section) && symbol.value as usize <= address)
+        .max_by_key(|symbol| symbol.value)
+        .map(|symbol| match address - symbol.value as usize {
+            0 => format!("<{}>", symbol.name),
+            offset => format!("<{}+{}>", symbol.name, offset),
+        })
+}
+
+// Lines of 16 bytes, with their address and the printable characters
+pub fn hexdump(bytes: &[u8], start: usize) -> Vec<String> {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            let text: String = chunk
+                .iter()
+                .map(|b| {
+                    if b.is_ascii_graphic() || *b == b' ' {
+                        *b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!("{:#06x}  {:<47}  |{}|", start + i * 16, hex.join(" "), text)
+        })
+        .collect()
+}
+
+// Where the pattern starts in the bytes
+pub fn find(bytes: &[u8], pattern: &[u8]) -> Vec<usize> {
+    if pattern.is_empty() {
+        return vec![];
+    }
+    bytes
+        .windows(pattern.len())
+        .enumerate()
+        .filter(|(_, window)| *window == pattern)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+// How `x` shows each value
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Format {
+    Hex,
+    Decimal,
+    Unsigned,
+    Char,
+    Instruction,
+}
+
+// The `/Nfu` of `x/Nfu`: how many values, in which format, of how many bytes each
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Examine {
+    pub count: usize,
+    pub format: Format,
+    pub unit: usize,
+}
+
+impl Examine {
+    // Parses what follows the `/`. Everything is optional: one hexadecimal word by default.
+    pub fn parse(text: &str) -> Option<Self> {
+        let digits: String = text.chars().take_while(|c| c.is_ascii_digit()).collect();
+        let count = if digits.is_empty() {
+            1
+        } else {
+            digits.parse::<usize>().ok()?
+        };
+        let mut format = None;
+        let mut unit = None;
+        for c in text[digits.len()..].chars() {
+            match c {
+                'x' => format = Some(Format::Hex),
+                'd' => format = Some(Format::Decimal),
+                'u' => format = Some(Format::Unsigned),
+                'c' => format = Some(Format::Char),
+                'i' => format = Some(Format::Instruction),
+                'b' => unit = Some(1),
+                'h' => unit = Some(2),
+                'w' => unit = Some(4),
+                _ => return None,
+            }
+        }
+        let format = format.unwrap_or(Format::Hex);
+        // Characters are single bytes
+        let unit = match format {
+            Format::Char | Format::Instruction => 1,
+            _ => unit.unwrap_or(4),
+        };
+        Some(Self {
+            count,
+            format,
+            unit,
+        })
+    }
+
+    // Values shown on each line
+    pub fn per_line(&self) -> usize {
+        match self.unit {
+            4 => 4,
+            _ => 8,
+        }
+    }
+
+    // A value cut to the unit, so print/xb shows the lowest byte
+    pub fn format_number(&self, value: i32) -> String {
+        self.format_value(&value.to_be_bytes()[4 - self.unit..])
+    }
+
+    // One value, read as a big-endian number like the VM does
+    pub fn format_value(&self, bytes: &[u8]) -> String {
+        let value = bytes.iter().fold(0u32, |n, b| (n << 8) | u32::from(*b));
+        let bits = 8 * bytes.len() as u32;
+        // Sign extension of the unit
+        let signed = ((value << (32 - bits)) as i32) >> (32 - bits);
+        match self.format {
+            Format::Hex => format!("{:#0width$x}", value, width = 2 + 2 * bytes.len()),
+            Format::Decimal => signed.to_string(),
+            Format::Unsigned => value.to_string(),
+            Format::Char => format!("{} \'{}\'", signed, std::ascii::escape_default(value as u8)),
+            Format::Instruction => String::new(),
+        }
+    }
+}
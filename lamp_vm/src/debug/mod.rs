@@ -1,2 +1,32 @@
+// Like print! and println!, to the output of a debug session, see DebugSession::out.
+// Like them, what can't be written is lost.
+macro_rules! output {
+    ($session:expr, $($arg:tt)*) => {{
+        use std::io::Write as _;
+        let _ = write!($session.out, $($arg)*);
+    }};
+}
+
+macro_rules! outputln {
+    ($session:expr) => {
+        output!($session, "\n")
+    };
+    ($session:expr, $($arg:tt)*) => {{
+        use std::io::Write as _;
+        let _ = writeln!($session.out, $($arg)*);
+    }};
+}
+
+pub mod breakpoints;
 pub mod commands;
+pub mod dap;
+pub mod display;
+pub mod editor;
+pub mod expr;
+pub mod gdb;
+pub mod history;
+pub mod memory;
+pub mod patch;
 pub mod session;
+pub mod source;
+pub mod tui;
@@ -0,0 +1,128 @@
+use crate::base::vm::VM;
+use lamp_asm_parser::expr::Value;
+use lamp_asm_parser::lexer::{Lexer, TokenType};
+use lamp_asm_parser::parser::{Parser, Statement};
+use lamp_common::container::SectionKind;
+use lamp_common::object::Symbol;
+use lamp_common::op::{get_operands, Opcode, OperandKind};
+
+// Different types of errors changing the program by hand can cause
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchError {
+    // What the assembler's parser said
+    Syntax(String),
+    NotAnInstruction(String),
+    // Opcode, operands expected and found
+    WrongOperandCount(Opcode, usize, usize),
+    // Opcode, index of the operand and what it should be
+    WrongOperandType(Opcode, usize, OperandKind),
+    OutOfRange(i64),
+    // Address and size
+    OutOfCode(usize, usize),
+    OutOfMemory(usize, usize),
+}
+
+impl std::fmt::Display for PatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Syntax(message) => write!(f, "{}", message),
+            Self::NotAnInstruction(text) => write!(f, "\'{}\' is not an instruction", text),
+            Self::WrongOperandCount(opcode, expected, found) => write!(
+                f,
+                "{:?} takes {} operands, {} were given",
+                opcode, expected, found
+            ),
+            Self::WrongOperandType(opcode, operand, OperandKind::Register) => write!(
+                f,
+                "Operand {} of {:?} has to be a register",
+                operand + 1,
+                opcode
+            ),
+            Self::WrongOperandType(opcode, operand, OperandKind::Imm16) => write!(
+                f,
+                "Operand {} of {:?} has to be a value",
+                operand + 1,
+                opcode
+            ),
+            Self::OutOfRange(value) => write!(f, "{} doesn't fit on 16 bits", value),
+            Self::OutOfCode(address, size) => write!(
+                f,
+                "Address {:#x} ({} bytes) is outside of the code",
+                address, size
+            ),
+            Self::OutOfMemory(address, size) => write!(
+                f,
+                "Address {:#x} ({} bytes) is outside of the data memory",
+                address, size
+            ),
+        }
+    }
+}
+
+// Assembles one instruction going at `address`. Labels are those of the symbol table,
+// and `.` is the address.
+pub fn assemble(text: &str, address: usize, symbols: &[Symbol]) -> Result<Vec<u8>, PatchError> {
+    let lexer = Lexer::new();
+    let line = Parser::new(&lexer)
+        .parse_line(text, 0)
+        .map_err(|e| PatchError::Syntax(e.to_string()))?;
+    let instruction = match (line.label, line.statement) {
+        (None, Some(Statement::Instruction(instruction))) => instruction,
+        _ => return Err(PatchError::NotAnInstruction(text.trim().to_owned())),
+    };
+    let opcode = instruction.opcode;
+    let kinds = get_operands(opcode);
+    if instruction.operands.len() != kinds.len() {
+        return Err(PatchError::WrongOperandCount(
+            opcode,
+            kinds.len(),
+            instruction.operands.len(),
+        ));
+    }
+
+    let mut bytes = vec![opcode as u8];
+    for (i, (operand, kind)) in instruction.operands.into_iter().zip(kinds).enumerate() {
+        let wrong_type = PatchError::WrongOperandType(opcode, i, *kind);
+        let expr = match (operand.token, kind) {
+            (TokenType::Register(n), OperandKind::Register) => {
+                bytes.push(n);
+                continue;
+            }
+            (TokenType::Register(_), _) | (_, OperandKind::Register) => return Err(wrong_type),
+            (TokenType::Num8(n), _) | (TokenType::Ptr8(n), _) => {
+                bytes.extend_from_slice(&u16::from(n).to_be_bytes());
+                continue;
+            }
+            // The VM reads 16-bit immediates unsigned
+            (TokenType::NumExpr(expr), _)
+            | (TokenType::Expr(expr), _)
+            | (TokenType::PtrExpr(expr), _) => expr,
+            (TokenType::Opcode(_), _) => return Err(wrong_type),
+        };
+        let resolve = |name: &str| {
+            symbols
+                .iter()
+                .find(|s| s.name == name && s.section == Some(SectionKind::Code))
+                .map(|s| Value::Constant(i64::from(s.value)))
+        };
+        let value = expr
+            .evaluate(Value::Constant(address as i64), &resolve)
+            .and_then(|value| value.constant())
+            .map_err(|e| PatchError::Syntax(e.to_string()))?;
+        if !(0..=0xffff).contains(&value) {
+            return Err(PatchError::OutOfRange(value));
+        }
+        bytes.extend_from_slice(&(value as u16).to_be_bytes());
+    }
+    Ok(bytes)
+}
+
+// Writes bytes in the data memory
+pub fn write_memory(vm: &mut VM, address: usize, bytes: &[u8]) -> Result<(), PatchError> {
+    let target = vm
+        .get_memory_mut()
+        .get_mut(address..address.saturating_add(bytes.len()))
+        .ok_or(PatchError::OutOfMemory(address, bytes.len()))?;
+    target.copy_from_slice(bytes);
+    Ok(())
+}
@@ -1,40 +1,394 @@
+use super::breakpoints::{Breakpoints, WatchHit, WatchKind};
 use super::commands;
-use crate::base::vm::VM;
+use super::display::Displays;
+use super::editor::LineEditor;
+use super::history::{History, DEFAULT_HISTORY_SIZE};
+use super::patch::PatchError;
+use super::source::{disassemble, SourceMap};
+use crate::base::vm::{VMError, VM};
+use lamp_common::container::SectionKind;
+use lamp_common::object::Symbol;
+use lamp_common::register::{
+    parse_register_with, register_name_with, RegisterAlias, DEFAULT_ALIASES, REGISTER_COUNT,
+};
 use log::{error, info};
-use std::io;
 use std::io::Write;
 
+pub const PROMPT: &str = ">>> ";
+
+// Instructions executed between two checks for an interruption, see resume_until
+pub const INTERRUPT_INTERVAL: u64 = 4096;
+
+// Arguments of the commands, for completion
+const KEYWORDS: &[&str] = &["breakpoints", "faults", "off", "mem", "if", "all"];
+
+// Why the program stopped running
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Stop {
+    // One instruction was executed
+    Step,
+    Breakpoint(usize),
+    Watch(WatchHit),
+    Fault(VMError),
+    // The program ran past the end of its code
+    Exited,
+    // Stopped on demand while running, see DebugSession::resume_until
+    Interrupted,
+}
+
 pub struct DebugSession {
-    vm: VM,
+    pub vm: VM,
+    // The program as loaded, to run it again
+    initial: VM,
+    // Code labels, from the symbol table
+    pub symbols: Vec<Symbol>,
+    // Source locations of the code, when the binary has debug info
+    pub source: Option<SourceMap>,
+    pub breakpoints: Breakpoints,
+    // What the last instructions changed, to step back
+    pub history: History,
+    // Expressions shown every time the program stops
+    pub displays: Displays,
+    // Where the commands write, stdout unless changed, like by the TUI
+    pub out: Box<dyn Write>,
+    // Set once the program exited or faulted, until it is run again
+    terminated: bool,
+    quit_requested: bool,
 }
 
 impl DebugSession {
-    pub fn new(vm: VM) -> Self {
-        Self { vm }
+    pub fn new(vm: VM, symbols: Vec<Symbol>, source: Option<SourceMap>) -> Self {
+        Self {
+            initial: vm.clone(),
+            vm,
+            symbols,
+            source,
+            breakpoints: Breakpoints::new(),
+            history: History::new(DEFAULT_HISTORY_SIZE),
+            displays: Displays::new(),
+            out: Box::new(std::io::stdout()),
+            terminated: false,
+            quit_requested: false,
+        }
     }
 
-    pub fn start_debug_session(&mut self) {
-        info!("Starting debug session.");
+    // Register aliases defined by the program, when the binary has debug info
+    pub fn aliases(&self) -> &[RegisterAlias] {
+        match &self.source {
+            Some(source) => &source.info().aliases,
+            None => &[],
+        }
+    }
+
+    // `r5`, a default alias or one of the program's
+    pub fn parse_register(&self, name: &str) -> Option<u8> {
+        parse_register_with(name, self.aliases())
+    }
+
+    // Like `r5 (counter)`, with the aliases of the program
+    pub fn register_name(&self, index: u8) -> String {
+        register_name_with(index, self.aliases())
+    }
+
+    // Starts the program again from its entry point
+    pub fn restart(&mut self) {
+        self.vm = self.initial.clone();
+        self.terminated = false;
+        self.breakpoints.reset_hits();
+        self.history.clear();
+    }
+
+    pub fn is_terminated(&self) -> bool {
+        self.terminated || self.vm.get_pc() >= self.vm.get_program().len()
+    }
+
+    // Executes one instruction
+    pub fn step(&mut self) -> Stop {
+        let watching = self.breakpoints.is_watching();
+        if watching {
+            self.breakpoints.watch_before(&self.vm);
+        }
+        let before = History::before(&self.vm);
+        match self.vm.cycle() {
+            Err(e) => Stop::Fault(e),
+            Ok(_) => {
+                self.history.after(before, &self.vm);
+                let hit = if watching {
+                    self.breakpoints.watch_after(&self.vm)
+                } else {
+                    None
+                };
+                match hit {
+                    Some(hit) => Stop::Watch(hit),
+                    None if self.vm.get_pc() >= self.vm.get_program().len() => Stop::Exited,
+                    None => Stop::Step,
+                }
+            }
+        }
+    }
+
+    // Runs until a breakpoint stops the program, it faults or it exits.
+    // The instruction at the pc is always executed, so a breakpoint there is left.
+    pub fn resume(&mut self) -> Stop {
+        self.resume_until(&mut || false)
+    }
+
+    // Like resume, also stopping when `interrupted` is true. It is asked every
+    // few thousand instructions, to wait for a remote debugger without slowing down.
+    pub fn resume_until(&mut self, interrupted: &mut dyn FnMut() -> bool) -> Stop {
+        let mut count = 0u64;
         loop {
-            print!("\n>>> ");
-            let _ = io::stdout().flush();
-            let mut command_line = String::new();
+            match self.step() {
+                Stop::Step => {}
+                stop => return stop,
+            }
+            if let Some(id) = self.breakpoints.hit(&self.vm) {
+                return Stop::Breakpoint(id);
+            }
+            count += 1;
+            if count.is_multiple_of(INTERRUPT_INTERVAL) && interrupted() {
+                return Stop::Interrupted;
+            }
+        }
+    }
+
+    // Overwrites code, in the program running and in the one "run" starts again
+    pub fn patch_code(&mut self, address: usize, bytes: &[u8]) -> Result<(), PatchError> {
+        let end = address.saturating_add(bytes.len());
+        if end > self.vm.get_program().len() {
+            return Err(PatchError::OutOfCode(address, bytes.len()));
+        }
+        self.vm.get_program_mut()[address..end].copy_from_slice(bytes);
+        self.initial.get_program_mut()[address..end].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    // Undoes the last instruction. Returns false when the history has nothing left.
+    pub fn reverse_step(&mut self) -> bool {
+        if !self.history.undo(&mut self.vm) {
+            return false;
+        }
+        self.terminated = false;
+        true
+    }
+
+    // Goes back until a breakpoint, or the oldest instruction recorded.
+    // Gives the breakpoint stopping the program.
+    pub fn reverse_continue(&mut self) -> Option<usize> {
+        while self.reverse_step() {
+            if let Some(id) = self.breakpoints.stops_at(&self.vm) {
+                return Some(id);
+            }
+        }
+        None
+    }
+
+    // Goes back or forward to the instruction numbered `number` since the start, see
+    // History. Breakpoints are ignored on the way. Gives None when the instruction
+    // is older than the history.
+    pub fn goto(&mut self, number: u64) -> Option<Stop> {
+        if number + 1 < self.history.oldest() {
+            return None;
+        }
+        while self.history.count() > number {
+            self.reverse_step();
+        }
+        while self.history.count() < number {
+            match self.step() {
+                Stop::Step => {}
+                stop => return Some(stop),
+            }
+        }
+        Some(Stop::Step)
+    }
+
+    // Tells why the program stopped and where
+    pub fn report(&mut self, stop: Stop) {
+        match stop {
+            Stop::Step => outputln!(self, "{}", self.describe(self.vm.get_pc())),
+            Stop::Breakpoint(id) => {
+                outputln!(
+                    self,
+                    "Breakpoint {}, {}",
+                    id,
+                    self.describe(self.vm.get_pc())
+                )
+            }
+            Stop::Watch(hit) => {
+                match hit.kind {
+                    WatchKind::Change => outputln!(
+                        self,
+                        "Watchpoint {}: {} changed from {} to {}",
+                        hit.id,
+                        hit.target.name(self.aliases()),
+                        hit.target.format_value(hit.old),
+                        hit.target.format_value(hit.new)
+                    ),
+                    WatchKind::Read => outputln!(
+                        self,
+                        "Read watchpoint {}: {} = {} was read",
+                        hit.id,
+                        hit.target.name(self.aliases()),
+                        hit.target.format_value(hit.new)
+                    ),
+                }
+                outputln!(self, "by the instruction at {}", self.describe_at(hit.pc));
+                if self.vm.get_pc() < self.vm.get_program().len() {
+                    outputln!(self, "{}", self.describe(self.vm.get_pc()));
+                }
+            }
+            Stop::Fault(e) => {
+                let pc = self.vm.get_instruction_pc();
+                if self.breakpoints.catch_faults {
+                    // Back to the faulting instruction, so it can be fixed and retried
+                    self.vm.set_pc(pc);
+                    outputln!(self, "Caught fault: {}", e);
+                } else {
+                    self.terminated = true;
+                    outputln!(self, "The program terminated with a fault: {}", e);
+                }
+                outputln!(self, "fault at {}", self.describe(pc));
+            }
+            Stop::Exited => {
+                self.terminated = true;
+                outputln!(self, "The program exited.");
+            }
+            Stop::Interrupted => {
+                outputln!(self, "Interrupted, {}", self.describe(self.vm.get_pc()))
+            }
+        }
+        if !self.terminated {
+            self.show_displays();
+        }
+    }
 
-            io::stdin()
-                .read_line(&mut command_line)
-                .map_err(|e| {
-                    error!("Unable to read line: {:?}", e);
-                })
-                .unwrap();
+    // The current value of the expressions given to display
+    pub fn show_displays(&mut self) {
+        for display in self.displays.list() {
+            outputln!(self, "{}", display.show(&self.vm));
+        }
+    }
 
-            command_line = String::from(command_line.trim());
-            match commands::parse_cmd(&command_line) {
-                Some(cmd) =>  {
-                    let res = cmd.execute(&mut self.vm, command_line.split(' ').collect());
-                    info!("Command reported the {} result.", res);
-                },
-                None => error!("This command doesn\'t exist. type \"help\" for an exhaustive list of the available commands."),
+    // The source of the instruction at `pc`, or its disassembly without debug info
+    pub fn describe(&self, pc: usize) -> String {
+        let code = self.vm.get_program();
+        match &self.source {
+            Some(source) => source.describe(pc, code),
+            None => format!("{:#06x}: {}", pc, disassemble(pc, code)),
+        }
+    }
+
+    // Like describe, always with the address
+    pub fn describe_at(&self, pc: usize) -> String {
+        match &self.source {
+            Some(source) => format!(
+                "{:#06x}, {}",
+                pc,
+                source.describe(pc, self.vm.get_program())
+            ),
+            None => self.describe(pc),
+        }
+    }
+
+    // Runs one command line, and gives what the command reported
+    pub fn execute(&mut self, command_line: &str) -> usize {
+        match commands::parse_cmd(command_line) {
+            Ok(cmd) => {
+                let res = cmd.execute(self, command_line.split_whitespace().collect());
+                info!("Command reported the {} result.", res);
+                res
+            }
+            Err(e) => {
+                error!("{}", e);
+                1
             }
         }
     }
+
+    // Runs the commands of a script, one per line, see strip_comment.
+    // Stops at the first command that fails and gives 1 then, 0 otherwise.
+    pub fn execute_script(&mut self, name: &str, script: &str) -> usize {
+        for (i, line) in script.lines().enumerate() {
+            let command_line = strip_comment(line).trim();
+            if command_line.is_empty() {
+                continue;
+            }
+            // Echoed, so the output reads like an interactive session
+            outputln!(self, "{}{}", PROMPT, command_line);
+            if self.execute(command_line) != 0 {
+                error!("{}:{}: \"{}\" failed.", name, i + 1, command_line);
+                return 1;
+            }
+            if self.is_quit_requested() {
+                break;
+            }
+        }
+        0
+    }
+
+    // Ends the session once the current command is done
+    pub fn quit(&mut self) {
+        self.quit_requested = true;
+    }
+
+    pub fn is_quit_requested(&self) -> bool {
+        self.quit_requested
+    }
+
+    // Words completed after a command's name
+    fn argument_completions(&self) -> Vec<String> {
+        let mut words: Vec<String> = self
+            .symbols
+            .iter()
+            .filter(|symbol| symbol.section == Some(SectionKind::Code))
+            .map(|symbol| symbol.name.clone())
+            .collect();
+        words.extend((0..REGISTER_COUNT).map(|n| format!("r{}", n)));
+        words.extend(DEFAULT_ALIASES.iter().map(|(alias, _)| alias.to_string()));
+        words.extend(self.aliases().iter().map(|alias| alias.name.clone()));
+        words.extend(commands::command_names());
+        words.extend(KEYWORDS.iter().map(|keyword| keyword.to_string()));
+        words
+    }
+
+    pub fn start_debug_session(&mut self) {
+        info!("Starting debug session.");
+        let mut editor = LineEditor::new(commands::command_names(), self.argument_completions());
+        let mut last_command: Option<String> = None;
+        while !self.is_quit_requested() {
+            outputln!(self);
+            let line = match editor.read_line(PROMPT) {
+                Some(line) => line,
+                None => break,
+            };
+            // An empty line repeats the last command, when it moves the program forward
+            let command_line = match (line.trim(), &last_command) {
+                ("", Some(last)) => last.clone(),
+                ("", None) => continue,
+                (line, _) => line.to_owned(),
+            };
+            self.execute(&command_line);
+            last_command = match commands::parse_cmd(&command_line) {
+                Ok(cmd) if cmd.repeatable() => Some(command_line),
+                _ => None,
+            };
+        }
+        editor.save_history();
+    }
+}
+
+// A line of a script without its comment. `#` starts one at the beginning of the line or
+// after a space, out of quotes, so immediates like `#7` are left alone.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut previous = ' ';
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted && previous.is_whitespace() => return &line[..i],
+            _ => {}
+        }
+        previous = c;
+    }
+    line
 }
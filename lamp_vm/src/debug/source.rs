@@ -0,0 +1,80 @@
+use lamp_common::debug_info::DebugInfo;
+use lamp_disasm::{decode, format_line, Item, Options};
+use std::collections::HashMap;
+
+// Finds the source of the code, thanks to the debug info of the binary.
+// The source files are read when the map is created; those that can't be read are
+// replaced by the disassembly.
+pub struct SourceMap {
+    info: DebugInfo,
+    files: HashMap<String, Vec<String>>,
+}
+
+impl SourceMap {
+    pub fn new(info: DebugInfo) -> Self {
+        let mut files = HashMap::<String, Vec<String>>::new();
+        for entry in &info.lines {
+            let file = &entry.location.file;
+            if files.contains_key(file) {
+                continue;
+            }
+            if let Ok(text) = std::fs::read_to_string(file) {
+                files.insert(file.clone(), text.lines().map(|s| s.to_owned()).collect());
+            }
+        }
+        Self { info, files }
+    }
+
+    pub fn info(&self) -> &DebugInfo {
+        &self.info
+    }
+
+    // `file:line` of the code at `pc`
+    pub fn location(&self, pc: usize) -> Option<String> {
+        let entry = self.info.line_at(pc as u32)?;
+        Some(format!("{}:{}", entry.location.file, entry.location.line))
+    }
+
+    // Where the instruction at `pc` comes from, like "program.lasm:42: MOD r1, r2, r3",
+    // followed by the macro invocations that led to it, innermost first
+    pub fn describe(&self, pc: usize, code: &[u8]) -> String {
+        let entry = match self.info.line_at(pc as u32) {
+            Some(entry) => entry,
+            None => return format!("{:#06x}: {}", pc, disassemble(pc, code)),
+        };
+
+        let location = &entry.location;
+        // Lines of macros have parameters in them, the disassembly is clearer
+        let text = self
+            .files
+            .get(&location.file)
+            .filter(|_| entry.expansions.is_empty())
+            .and_then(|lines| lines.get(location.line as usize - 1))
+            .map(|line| {
+                let text: String = line.chars().skip(location.column as usize - 1).collect();
+                text.trim_end().to_owned()
+            })
+            .unwrap_or_else(|| disassemble(pc, code));
+
+        let mut out = format!("{}:{}: {}", location.file, location.line, text);
+        for expansion in entry.expansions.iter().rev() {
+            out += &format!(
+                "\n    in macro \'{}\' invoked at {}:{}",
+                expansion.name, expansion.file, expansion.line
+            );
+        }
+        out
+    }
+}
+
+// The instruction at `pc`, like "MOD r1, r2, r3"
+pub fn disassemble(pc: usize, code: &[u8]) -> String {
+    match decode(code, pc) {
+        Some(instruction) => {
+            format_line(&Item::Instruction(instruction), code, &Options::default())
+                .trim()
+                .to_owned()
+        }
+        None => "<invalid instruction>".to_owned(),
+    }
+}
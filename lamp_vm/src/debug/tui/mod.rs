@@ -0,0 +1,431 @@
+// A full-screen debugger: registers, flags, stack, disassembly and memory are shown
+// at once above the output of the commands and their prompt. Commands are those of
+// the line debugger.
+pub mod output;
+
+use super::commands;
+use super::memory::hexdump;
+use super::session::{DebugSession, PROMPT};
+use lamp_common::container::SectionKind;
+use lamp_common::register::{register_alias, REGISTER_COUNT};
+use lamp_disasm::{decode_items, format_line, Options};
+use output::{log_to, OutputBuffer};
+use ratatui::backend::{Backend, CrosstermBackend};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{Frame, Terminal};
+use std::convert::TryFrom;
+
+// Registers on each line of their pane
+const REGISTER_COLUMNS: usize = 4;
+// Lines kept in the output pane
+const OUTPUT_LINES: usize = 1000;
+// Words shown from the stack pointer
+const STACK_WORDS: usize = 8;
+
+// What the panes compare with to highlight changes
+#[derive(Debug, Clone, PartialEq)]
+struct Snapshot {
+    registers: [i32; 32],
+    eq_flag: bool,
+    remainder: i32,
+    pc: usize,
+}
+
+impl Snapshot {
+    fn of(session: &DebugSession) -> Self {
+        let vm = &session.vm;
+        Self {
+            registers: *vm.get_registers(),
+            eq_flag: vm.get_eq_flag(),
+            remainder: vm.get_modulo_remainder(),
+            pc: vm.get_pc(),
+        }
+    }
+}
+
+pub struct Tui<'a> {
+    session: &'a mut DebugSession,
+    // The values before the last command
+    previous: Snapshot,
+    input: String,
+    output: Vec<String>,
+    // Lines of output scrolled back from the last one
+    scroll: usize,
+    // First address of the memory pane
+    memory_address: usize,
+    // Command lines typed, for the arrow keys
+    history: Vec<String>,
+    history_index: usize,
+    last_command: Option<String>,
+    // What the session writes, for the output pane
+    buffer: OutputBuffer,
+}
+
+impl<'a> Tui<'a> {
+    pub fn new(session: &'a mut DebugSession) -> Self {
+        let buffer = OutputBuffer::default();
+        session.out = Box::new(buffer.clone());
+        Self {
+            previous: Snapshot::of(session),
+            session,
+            input: String::new(),
+            output: Vec::new(),
+            scroll: 0,
+            memory_address: 0,
+            history: Vec::new(),
+            history_index: 0,
+            last_command: None,
+            buffer,
+        }
+    }
+
+    pub fn output(&self) -> &[String] {
+        &self.output
+    }
+
+    // Runs a command like the prompt of the line debugger: an empty line repeats
+    // the last command moving the program forward
+    pub fn submit(&mut self, line: &str) {
+        let command_line = match (line.trim(), &self.last_command) {
+            ("", Some(last)) => last.clone(),
+            ("", None) => return,
+            (line, _) => line.to_owned(),
+        };
+        if !line.trim().is_empty() {
+            self.history.push(command_line.clone());
+        }
+        self.history_index = self.history.len();
+        self.push_output(vec![format!("{}{}", PROMPT, command_line)]);
+        self.previous = Snapshot::of(self.session);
+        self.session.execute(&command_line);
+        self.push_output(self.buffer.take_lines());
+        self.last_command = match commands::parse_cmd(&command_line) {
+            Ok(cmd) if cmd.repeatable() => Some(command_line),
+            _ => None,
+        };
+    }
+
+    fn push_output(&mut self, lines: Vec<String>) {
+        if lines.is_empty() {
+            return;
+        }
+        self.output.extend(lines);
+        let extra = self.output.len().saturating_sub(OUTPUT_LINES);
+        self.output.drain(..extra);
+        self.scroll = 0;
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('d') if ctrl => self.session.quit(),
+            KeyCode::Char('c') if ctrl => self.input.clear(),
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Enter => {
+                let line = std::mem::take(&mut self.input);
+                self.submit(&line);
+            }
+            // The usual keys of debuggers in editors
+            KeyCode::F(5) => self.submit("continue"),
+            KeyCode::F(10) => self.submit("step"),
+            KeyCode::Up if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                self.memory_address = self.memory_address.saturating_sub(16)
+            }
+            KeyCode::Down
+                if key.modifiers.contains(KeyModifiers::SHIFT)
+                    && self.memory_address + 16 < self.session.vm.get_memory().len() =>
+            {
+                self.memory_address += 16
+            }
+            KeyCode::Down if key.modifiers.contains(KeyModifiers::SHIFT) => {}
+            KeyCode::Up if self.history_index > 0 => {
+                self.history_index -= 1;
+                self.input = self.history[self.history_index].clone();
+            }
+            KeyCode::Down if self.history_index < self.history.len() => {
+                self.history_index += 1;
+                self.input = self
+                    .history
+                    .get(self.history_index)
+                    .cloned()
+                    .unwrap_or_default();
+            }
+            KeyCode::PageUp => {
+                self.scroll = (self.scroll + 5).min(self.output.len().saturating_sub(1))
+            }
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_sub(5),
+            _ => {}
+        }
+    }
+
+    pub fn draw(&self, frame: &mut Frame) {
+        let [top, middle, output, command] = Layout::vertical([
+            Constraint::Length(10),
+            Constraint::Min(6),
+            Constraint::Length(8),
+            Constraint::Length(3),
+        ])
+        .areas(frame.area());
+        let [registers, flags, stack] = Layout::horizontal([
+            Constraint::Length(2 + 16 * REGISTER_COLUMNS as u16),
+            Constraint::Length(24),
+            Constraint::Min(20),
+        ])
+        .areas(top);
+        let [disassembly, memory] =
+            Layout::horizontal([Constraint::Min(30), Constraint::Length(77)]).areas(middle);
+
+        frame.render_widget(self.registers(), registers);
+        frame.render_widget(self.flags(), flags);
+        frame.render_widget(self.stack(), stack);
+        frame.render_widget(self.disassembly(disassembly.height), disassembly);
+        frame.render_widget(self.memory(memory.height), memory);
+        frame.render_widget(self.output_pane(output.height), output);
+        frame.render_widget(
+            Paragraph::new(format!("{}{}", PROMPT, self.input)).block(Block::bordered()),
+            command,
+        );
+        let cursor = (PROMPT.len() + self.input.chars().count()) as u16;
+        frame.set_cursor_position((
+            (command.x + 1 + cursor).min(command.right().saturating_sub(2)),
+            command.y + 1,
+        ));
+    }
+
+    // Changed values are highlighted
+    fn value_style(changed: bool) -> Style {
+        if changed {
+            Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+        } else {
+            Style::new()
+        }
+    }
+
+    fn registers(&self) -> Paragraph<'static> {
+        let values = self.session.vm.get_registers();
+        let aliases = self.session.aliases();
+        let rows = (REGISTER_COUNT as usize).div_ceil(REGISTER_COLUMNS);
+        let lines: Vec<Line> = (0..rows)
+            .map(|row| {
+                // Numbered down the columns
+                let spans: Vec<Span> = (0..REGISTER_COLUMNS)
+                    .map(|column| column * rows + row)
+                    .filter(|i| *i < REGISTER_COUNT as usize)
+                    .map(|i| {
+                        // The default alias, or one of the program
+                        let name = match register_alias(i as u8) {
+                            Some(alias) => alias.to_string(),
+                            None => match aliases.iter().find(|a| usize::from(a.register) == i) {
+                                Some(alias) => alias.name.clone(),
+                                None => format!("r{}", i),
+                            },
+                        };
+                        let changed = values[i] != self.previous.registers[i];
+                        Span::styled(
+                            format!("{:<4}{:>11} ", name, values[i]),
+                            Self::value_style(changed),
+                        )
+                    })
+                    .collect();
+                Line::from(spans)
+            })
+            .collect();
+        Paragraph::new(lines).block(Block::bordered().title("Registers"))
+    }
+
+    fn flags(&self) -> Paragraph<'static> {
+        let vm = &self.session.vm;
+        let previous = &self.previous;
+        let line = |name: &str, value: String, changed: bool| {
+            Line::from(vec![
+                Span::raw(format!("{:<10}", name)),
+                Span::styled(value, Self::value_style(changed)),
+            ])
+        };
+        let state = if self.session.is_terminated() {
+            "terminated"
+        } else {
+            "stopped"
+        };
+        let lines = vec![
+            line("pc", format!("{:#06x}", vm.get_pc()), false),
+            line(
+                "eq",
+                vm.get_eq_flag().to_string(),
+                vm.get_eq_flag() != previous.eq_flag,
+            ),
+            line(
+                "remainder",
+                vm.get_modulo_remainder().to_string(),
+                vm.get_modulo_remainder() != previous.remainder,
+            ),
+            line("program", state.to_owned(), false),
+            line(
+                "executed",
+                self.session.history.count().to_string(),
+                vm.get_pc() != previous.pc,
+            ),
+        ];
+        Paragraph::new(lines).block(Block::bordered().title("Flags"))
+    }
+
+    // Words of the data memory from the stack pointer
+    fn stack(&self) -> Paragraph<'static> {
+        let vm = &self.session.vm;
+        let sp = *vm.get_register(31) as u32 as usize;
+        let memory = vm.get_memory();
+        let mut lines = Vec::<Line>::new();
+        for i in 0..STACK_WORDS {
+            let address = sp + 4 * i;
+            let word = match memory.get(address..address + 4) {
+                Some(bytes) => bytes,
+                None => break,
+            };
+            let value = u32::from_be_bytes(<[u8; 4]>::try_from(word).unwrap());
+            let marker = if i == 0 { "sp->" } else { "    " };
+            lines.push(Line::raw(format!(
+                "{} {:#06x} {:#010x}",
+                marker, address, value
+            )));
+        }
+        if lines.is_empty() {
+            lines.push(Line::raw(format!(
+                "sp ({:#x}) is outside of the data memory",
+                sp
+            )));
+        }
+        Paragraph::new(lines).block(Block::bordered().title("Stack"))
+    }
+
+    // The instructions around the pc, with labels and breakpoints
+    fn disassembly(&self, height: u16) -> Paragraph<'static> {
+        let vm = &self.session.vm;
+        let code = vm.get_program();
+        let pc = vm.get_pc();
+        let options = Options {
+            annotate: true,
+            pc: Some(pc),
+        };
+        let mut lines = Vec::<(usize, Line)>::new();
+        for item in decode_items(code, 0, code.len()) {
+            let address = item.address();
+            for symbol in &self.session.symbols {
+                if symbol.section == Some(SectionKind::Code) && symbol.value as usize == address {
+                    lines.push((address, Line::raw(format!("  {}:", symbol.name))));
+                }
+            }
+            let breakpoint = self
+                .session
+                .breakpoints
+                .list()
+                .iter()
+                .any(|b| b.address == address);
+            let marker = if breakpoint {
+                Span::styled("● ", Style::new().fg(Color::Red))
+            } else {
+                Span::raw("  ")
+            };
+            let style = if address == pc {
+                Style::new().fg(Color::Green).add_modifier(Modifier::BOLD)
+            } else {
+                Style::new()
+            };
+            let text = Span::styled(format_line(&item, code, &options), style);
+            lines.push((address, Line::from(vec![marker, text])));
+        }
+        // A third of the pane above the pc
+        let visible = height.saturating_sub(2) as usize;
+        let at = lines
+            .iter()
+            .position(|(address, _)| *address >= pc)
+            .unwrap_or(lines.len());
+        let start = at
+            .saturating_sub(visible / 3)
+            .min(lines.len().saturating_sub(visible));
+        let lines: Vec<Line> = lines
+            .into_iter()
+            .skip(start)
+            .map(|(_, line)| line)
+            .collect();
+        Paragraph::new(lines).block(Block::bordered().title("Disassembly"))
+    }
+
+    fn memory(&self, height: u16) -> Paragraph<'static> {
+        let memory = self.session.vm.get_memory();
+        let start = self.memory_address.min(memory.len());
+        let end = memory
+            .len()
+            .min(start + 16 * height.saturating_sub(2) as usize);
+        let mut lines: Vec<Line> = hexdump(&memory[start..end], start)
+            .into_iter()
+            .map(Line::raw)
+            .collect();
+        if lines.is_empty() {
+            lines.push(Line::raw("No data memory"));
+        }
+        let title = "Memory (shift+up/down to scroll)";
+        Paragraph::new(lines).block(Block::bordered().title(title))
+    }
+
+    fn output_pane(&self, height: u16) -> Paragraph<'_> {
+        let visible = height.saturating_sub(2) as usize;
+        let end = self.output.len().saturating_sub(self.scroll);
+        let start = end.saturating_sub(visible);
+        let lines: Vec<Line> = self.output[start..end]
+            .iter()
+            .map(|line| Line::raw(line.as_str()))
+            .collect();
+        let title = "Output (page up/down to scroll, F5 continue, F10 step, ctrl+d to quit)";
+        Paragraph::new(lines).block(Block::bordered().title(title))
+    }
+
+    fn event_loop<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> std::io::Result<()> {
+        while !self.session.is_quit_requested() {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    self.handle_key(key);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Tui<'_> {
+    // The session writes to stdout again
+    fn drop(&mut self) {
+        self.session.out = Box::new(std::io::stdout());
+    }
+}
+
+// Runs the debugger full-screen until the quit command, or ctrl+d
+pub fn run(session: &mut DebugSession) -> std::io::Result<()> {
+    let mut screen = std::io::stdout();
+    enable_raw_mode()?;
+    execute!(screen, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(screen))?;
+
+    let mut tui = Tui::new(session);
+    log_to(Some(tui.buffer.clone()));
+    let result = tui.event_loop(&mut terminal);
+    log_to(None);
+    drop(tui);
+
+    // The terminal is given back even when drawing failed
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    result
+}
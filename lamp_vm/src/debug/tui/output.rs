@@ -0,0 +1,71 @@
+// In the TUI, what the commands write to the session's output goes to a buffer shown
+// in a pane instead of over the screen, and so do the logs, in the same order.
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+// Bytes written and not shown yet
+#[derive(Debug, Clone, Default)]
+pub struct OutputBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl OutputBuffer {
+    // The lines written since the last call, the last one even without a newline
+    pub fn take_lines(&self) -> Vec<String> {
+        let taken = std::mem::take(&mut *self.0.lock().unwrap());
+        String::from_utf8_lossy(&taken)
+            .lines()
+            .map(str::to_owned)
+            .collect()
+    }
+}
+
+impl Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// Where the logger writes while the TUI runs, see log_to
+static LOG_OUTPUT: Mutex<Option<OutputBuffer>> = Mutex::new(None);
+
+// Sends the logs to the buffer, or to stdout again with None
+pub fn log_to(output: Option<OutputBuffer>) {
+    *LOG_OUTPUT.lock().unwrap() = output;
+}
+
+// Logs what the commands say, and the warnings and errors of everything else,
+// without the timestamps and the VM's information that would fill the output pane
+struct OutputLogger;
+
+impl Log for OutputLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Warn
+            || (metadata.level() <= Level::Info
+                && metadata.target().starts_with("lamp_vm::debug::commands"))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match LOG_OUTPUT.lock().unwrap().as_mut() {
+            Some(output) => {
+                let _ = writeln!(output, "{}", record.args());
+            }
+            None => println!("{}", record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+pub fn init_logger() -> Result<(), SetLoggerError> {
+    log::set_logger(&OutputLogger)?;
+    log::set_max_level(LevelFilter::Info);
+    Ok(())
+}
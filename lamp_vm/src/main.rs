@@ -1,7 +1,16 @@
-use base::vm::VM;
+use base::loader;
+use base::profile::{Profile, REPORT_LINES};
+use base::trace::{parse_opcode, parse_range, TraceFilter, TraceFormat, Tracer};
+use base::vm::{VMResult, VM};
 use debug::session::DebugSession;
+use debug::source::SourceMap;
+use lamp_common::container::{Container, SectionKind};
+use lamp_common::debug_info::DebugInfo;
+use lamp_common::object::{decode_symbols, Symbol};
+use lamp_common::op::Opcode;
 use log::{error, info};
-use std::path::PathBuf;
+use std::io::{BufWriter, Read};
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 
 pub mod base;
@@ -12,34 +21,164 @@ mod tests;
 #[derive(StructOpt, Debug)]
 #[structopt(name = "lamp")]
 struct LampApp {
+    /// The binary to run
     #[structopt(short)]
     bin_path: PathBuf,
 
+    /// Runs the program in the interactive debugger
     #[structopt(short, long)]
     debug: bool,
+
+    /// Debugger commands to run first, one per line
+    #[structopt(short = "x", long, requires = "debug")]
+    script: Option<PathBuf>,
+
+    /// Runs the script, or the commands read from stdin, without prompting, then exits
+    /// with 1 when a command failed
+    #[structopt(long, requires = "debug")]
+    batch: bool,
+
+    /// Waits for GDB on this port of localhost
+    #[structopt(long, conflicts_with = "debug")]
+    gdb_port: Option<u16>,
+
+    /// Talks the Debug Adapter Protocol with an editor over stdin and stdout
+    #[structopt(long, conflicts_with_all = &["debug", "gdb-port"])]
+    dap: bool,
+
+    /// Like --dap, waiting for the editor on this port of localhost instead
+    #[structopt(long, conflicts_with_all = &["debug", "gdb-port", "dap"])]
+    dap_port: Option<u16>,
+
+    /// Debugs full-screen, with the registers, the code and the memory always shown
+    #[structopt(long, conflicts_with_all = &["debug", "gdb-port", "dap", "dap-port"])]
+    tui: bool,
+
+    /// Writes every instruction executed to this file, with what it changed
+    #[structopt(long, conflicts_with_all = &["debug", "gdb-port", "dap", "dap-port", "tui"])]
+    trace: Option<PathBuf>,
+
+    /// How the trace is written: jsonl or text
+    #[structopt(long, default_value = "jsonl", possible_values = &["jsonl", "text"])]
+    trace_format: TraceFormat,
+
+    /// Only traces the instructions in this range, like 0x10..0x40 or loop..done
+    #[structopt(long, requires = "trace", number_of_values = 1)]
+    trace_range: Vec<String>,
+
+    /// Only traces these instructions, like MOD
+    #[structopt(long, requires = "trace", number_of_values = 1, parse(try_from_str = parse_opcode))]
+    trace_opcode: Vec<Opcode>,
+
+    /// Counts the instructions executed, and prints where the program spends its time
+    #[structopt(long, conflicts_with_all = &["debug", "gdb-port", "dap", "dap-port", "tui", "trace"])]
+    profile: bool,
+
+    /// Where the profile's stacks are written for flamegraph tools, the binary's path
+    /// with the .folded extension by default
+    #[structopt(long, requires = "profile")]
+    folded: Option<PathBuf>,
+
+    /// Runs a flat binary made of opcodes only, without any container
+    #[structopt(long)]
+    raw: bool,
 }
 
 fn main() {
     let lamp = LampApp::from_args();
-    simple_logger::init().unwrap();
+    // The VM logs at the info level, below is the line editor's noise.
+    // Logs would be mixed with the messages of the protocol on stdout,
+    // and the TUI shows them in its output pane.
+    if lamp.tui {
+        debug::tui::output::init_logger().unwrap();
+    } else if !lamp.dap {
+        simple_logger::init_with_level(log::Level::Info).unwrap();
+    }
     let bin = std::fs::read(&lamp.bin_path);
 
     match bin {
         Ok(v) => {
+            let (mut lamp_vm, symbols, source) = if lamp.raw {
+                (VM::new(v), vec![], None)
+            } else {
+                let loaded = Container::from_bytes(&v)
+                    .map_err(loader::LoadError::from)
+                    .and_then(|container| {
+                        loader::map(&container)
+                            .map(|vm| (vm, symbols(&container), source_map(&container)))
+                    });
+                match loaded {
+                    Ok(loaded) => loaded,
+                    Err(e) => {
+                        error!("Unable to load the binary: {}", e);
+                        return;
+                    }
+                }
+            };
+
+            if let Some(port) = lamp.gdb_port {
+                let mut debug_session = DebugSession::new(lamp_vm, symbols, source);
+                if let Err(e) = debug::gdb::serve(&mut debug_session, port) {
+                    error!("GDB server error: {}", e);
+                }
+                return;
+            }
+
+            if lamp.dap || lamp.dap_port.is_some() {
+                let mut debug_session = DebugSession::new(lamp_vm, symbols, source);
+                let served = match lamp.dap_port {
+                    Some(port) => debug::dap::serve_tcp(&mut debug_session, port),
+                    None => debug::dap::serve_stdio(&mut debug_session),
+                };
+                if let Err(e) = served {
+                    error!("Debug adapter error: {}", e);
+                }
+                return;
+            }
+
+            if lamp.tui {
+                let mut debug_session = DebugSession::new(lamp_vm, symbols, source);
+                if let Err(e) = debug::tui::run(&mut debug_session) {
+                    error!("Terminal error: {}", e);
+                }
+                return;
+            }
+
             if lamp.debug {
                 info!("Debug session started.");
-                let mut debug_session = DebugSession::new(VM::new(v));
-                debug_session.start_debug_session();
+                let mut debug_session = DebugSession::new(lamp_vm, symbols, source);
+                let status = run_debug_session(&mut debug_session, &lamp);
                 info!("Debug session ended.");
+                if lamp.batch {
+                    std::process::exit(status as i32);
+                }
                 return;
             }
 
-            let mut lamp_vm = VM::new(v);
-            let exit_status = lamp_vm.run();
+            let exit_status = match &lamp.trace {
+                Some(path) => match trace(&mut lamp_vm, &symbols, path, &lamp) {
+                    Ok(status) => status,
+                    Err(e) => {
+                        error!("Unable to trace the program: {}", e);
+                        return;
+                    }
+                },
+                None if lamp.profile => profile(&mut lamp_vm, &symbols, source.as_ref(), &lamp),
+                None => lamp_vm.run(),
+            };
 
             match exit_status {
                 Ok(code) => info!("VM exited successfully (code {})", code),
-                Err(e) => error!("VM exited with an error.\nReason: {:?}", e),
+                Err(e) => {
+                    error!("VM exited with an error.\nReason: {}", e);
+                    let pc = lamp_vm.get_instruction_pc();
+                    match &source {
+                        Some(source) => {
+                            error!("fault at {}", source.describe(pc, lamp_vm.get_program()))
+                        }
+                        None => error!("fault at {:#06x}", pc),
+                    }
+                }
             }
         }
         Err(e) => error!("Unable to read the binary's content: {:?}", e),
@@ -47,3 +186,106 @@ fn main() {
 
     info!("VM Shutdown.");
 }
+
+// Runs the script, then the interactive session unless in batch mode.
+// Gives the exit status of the batch mode.
+fn run_debug_session(session: &mut DebugSession, lamp: &LampApp) -> usize {
+    if let Some(path) = &lamp.script {
+        let script = match std::fs::read_to_string(path) {
+            Ok(script) => script,
+            Err(e) => {
+                error!("Unable to read the script {}: {}", path.display(), e);
+                return 2;
+            }
+        };
+        let status = session.execute_script(&path.display().to_string(), &script);
+        if lamp.batch || session.is_quit_requested() {
+            return status;
+        }
+    } else if lamp.batch {
+        let mut commands = String::new();
+        if let Err(e) = std::io::stdin().read_to_string(&mut commands) {
+            error!("Unable to read the commands: {}", e);
+            return 2;
+        }
+        return session.execute_script("<stdin>", &commands);
+    }
+    session.start_debug_session();
+    0
+}
+
+// Runs the program, writing each instruction the filters let through to the file
+fn trace(vm: &mut VM, symbols: &[Symbol], path: &Path, lamp: &LampApp) -> Result<VMResult, String> {
+    let ranges = lamp
+        .trace_range
+        .iter()
+        .map(|range| parse_range(range, symbols))
+        .collect::<Result<Vec<_>, String>>()?;
+    let filter = TraceFilter {
+        ranges,
+        opcodes: lamp.trace_opcode.clone(),
+    };
+    let file = std::fs::File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Tracer::new(BufWriter::new(file), lamp.trace_format, filter)
+        .run(vm)
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// Runs the program, then prints the report and writes the folded stacks
+fn profile(
+    vm: &mut VM,
+    symbols: &[Symbol],
+    source: Option<&SourceMap>,
+    lamp: &LampApp,
+) -> VMResult {
+    let mut profile = Profile::new(vm);
+    let result = profile.run(vm);
+    let info = source.map(|source| source.info());
+    println!(
+        "{}",
+        profile.report(vm.get_program(), symbols, info, REPORT_LINES)
+    );
+
+    let path = match &lamp.folded {
+        Some(path) => path.clone(),
+        None => lamp.bin_path.with_extension("folded"),
+    };
+    let mut folded = profile.folded(symbols, info).join("\n");
+    folded.push('\n');
+    match std::fs::write(&path, folded) {
+        Ok(_) => info!("Folded stacks written to {}", path.display()),
+        Err(e) => error!(
+            "Unable to write the folded stacks to {}: {}",
+            path.display(),
+            e
+        ),
+    }
+    result
+}
+
+// The symbols of the binary, unless it was stripped
+fn symbols(container: &Container) -> Vec<Symbol> {
+    let section = match container.section(SectionKind::Symtab) {
+        Some(section) => section,
+        None => return vec![],
+    };
+    match decode_symbols(&section.data) {
+        Ok(symbols) => symbols,
+        Err(e) => {
+            error!("Ignoring the symbol table: {}", e);
+            vec![]
+        }
+    }
+}
+
+// The source locations of the code, when the binary was assembled with debug info
+fn source_map(container: &Container) -> Option<SourceMap> {
+    let section = container.section(SectionKind::Debug)?;
+    match DebugInfo::decode(&section.data) {
+        Ok(info) => Some(SourceMap::new(info)),
+        Err(e) => {
+            error!("Ignoring the debug info: {}", e);
+            None
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use super::{label, looping_program, symbol};
+use crate::base::vm::{VMError, VM};
+use crate::debug::breakpoints::{
+    resolve_location, BreakpointError, Condition, WatchHit, WatchKind, WatchTarget,
+};
+use crate::debug::session::{DebugSession, Stop};
+use crate::debug::source::SourceMap;
+use lamp_common::container::SectionKind;
+use lamp_common::debug_info::{DebugInfo, LineEntry, SourceLocation};
+
+fn source_map() -> SourceMap {
+    let line = |address: u32, line: u32| LineEntry {
+        address,
+        location: SourceLocation {
+            file: "src/loop.lasm".to_string(),
+            line,
+            column: 5,
+        },
+        expansions: vec![],
+    };
+    SourceMap::new(DebugInfo {
+        lines: vec![line(0, 2), line(2, 3), line(4, 5), line(8, 6)],
+        symbols: vec![],
+        aliases: vec![],
+    })
+}
+
+#[test]
+pub fn breakpoints_resolve_location_test() {
+    let vm = looping_program();
+    let symbols = vec![label("loop", 0), label("again", 4)];
+    let source = source_map();
+    let resolve = |text: &str| resolve_location(text, &vm, &symbols, Some(&source));
+
+    assert_eq!(resolve("0x8"), Ok(8));
+    assert_eq!(resolve("again"), Ok(4));
+    assert_eq!(resolve("loop.lasm:3"), Ok(2));
+    // Line 4 has no code, the next line does
+    assert_eq!(resolve("src/loop.lasm:4"), Ok(4));
+    assert_eq!(
+        resolve("loop.lasm:7"),
+        Err(BreakpointError::NoCodeAtLine("loop.lasm".to_string(), 7))
+    );
+    assert_eq!(resolve("10"), Err(BreakpointError::OutOfCode(10)));
+    assert_eq!(
+        resolve_location("loop.lasm:3", &vm, &symbols, None),
+        Err(BreakpointError::NoDebugInfo)
+    );
+}
+
+#[test]
+pub fn breakpoints_conditions_test() {
+    let mut vm = looping_program();
+    vm.set_register_value(3, 10);
+    let holds = |text: &str| text.parse::<Condition>().unwrap().holds(&vm);
+    assert!(holds("r3 == 10"));
+    assert!(holds("r3>=0xa"));
+    assert!(holds("r3 != r4"));
+    assert!(!holds("r3 < 10"));
+    assert!(holds("-5 <= zero"));
+    assert!("r3 = 10".parse::<Condition>().is_err());
+    assert!("r3 == r99".parse::<Condition>().is_err());
+    assert_eq!(
+        "r3==10".parse::<Condition>().unwrap().to_string(),
+        "r3 == 10"
+    );
+}
+
+#[test]
+pub fn breakpoints_stops_test() {
+    let mut session = DebugSession::new(looping_program(), vec![], None);
+    let id = session
+        .breakpoints
+        .add(2, "2", Some("r3 >= 5".parse().unwrap()));
+    // r3 is 5 after the third INC
+    assert_eq!(session.resume(), Stop::Breakpoint(id));
+    assert_eq!(*session.vm.get_register(3), 5);
+
+    session.breakpoints.get_mut(id).unwrap().ignore_count = 2;
+    assert_eq!(session.resume(), Stop::Breakpoint(id));
+    assert_eq!(*session.vm.get_register(3), 11);
+    assert_eq!(session.breakpoints.list()[0].hits, 4);
+
+    // Starting again forgets the hits and the registers
+    session.restart();
+    assert_eq!(session.breakpoints.list()[0].hits, 0);
+    assert_eq!(session.resume(), Stop::Breakpoint(id));
+    assert_eq!(*session.vm.get_register(3), 5);
+}
+
+#[test]
+pub fn breakpoints_catch_faults_test() {
+    // LOAD r1, 1; MOD r1, r2, r3 with r2 = 0
+    let vm = VM::new(vec![15, 1, 0, 1, 4, 1, 2, 3]);
+    let mut session = DebugSession::new(vm, vec![], None);
+    session.breakpoints.catch_faults = true;
+    let stop = session.resume();
+    assert_eq!(stop, Stop::Fault(VMError::DivisionByZero));
+    session.report(stop);
+    // Stopped on the faulting instruction, it can be fixed and retried
+    assert_eq!(session.vm.get_pc(), 4);
+    assert!(!session.is_terminated());
+    session.vm.set_register_value(2, 1);
+    assert_eq!(session.resume(), Stop::Exited);
+    assert_eq!(*session.vm.get_register(3), 1);
+
+    // The last byte of the code is fetched as an instruction, MUL, without operands
+    session.restart();
+    assert_eq!(session.execute("setpc 7"), 0);
+    assert_eq!(session.step(), Stop::Fault(VMError::EndOfCode));
+    assert_eq!(session.vm.get_instruction_pc(), 7);
+}
+
+#[test]
+pub fn breakpoints_watchpoints_test() {
+    let mut session = DebugSession::new(looping_program(), vec![], None);
+    let r3 = WatchTarget::parse(&["r3"], WatchKind::Change, &session).unwrap();
+    let r5 = WatchTarget::parse(&["r5"], WatchKind::Read, &session).unwrap();
+    let change = session.breakpoints.add_watch(r3, WatchKind::Change);
+    let read = session.breakpoints.add_watch(r5, WatchKind::Read);
+    let hit = |id: usize, target: WatchTarget, kind: WatchKind, old: i64, new: i64, pc: usize| {
+        Stop::Watch(WatchHit {
+            id,
+            target,
+            kind,
+            old,
+            new,
+            pc,
+        })
+    };
+
+    assert_eq!(
+        session.resume(),
+        hit(change, r3, WatchKind::Change, 0, 1, 0)
+    );
+    assert_eq!(
+        session.resume(),
+        hit(change, r3, WatchKind::Change, 1, 2, 2)
+    );
+    // LOAD r5 writes r5, JMP r5 reads it
+    assert_eq!(session.resume(), hit(read, r5, WatchKind::Read, 0, 0, 8));
+
+    session.breakpoints.delete(change).unwrap();
+    assert_eq!(session.resume(), hit(read, r5, WatchKind::Read, 0, 0, 8));
+    assert_eq!(*session.vm.get_register(3), 4);
+    assert_eq!(session.breakpoints.watchpoints()[0].hits, 2);
+}
+
+#[test]
+pub fn breakpoints_memory_watch_test() {
+    let vm = VM::with_memory(vec![14], vec![0x12, 0x34, 0x56], 0);
+    let symbols = vec![label("start", 0), symbol("value", SectionKind::Data, 1)];
+    let session = DebugSession::new(vm.clone(), symbols, None);
+    let parse = |args: &[&str]| WatchTarget::parse(args, WatchKind::Change, &session);
+    assert_eq!(parse(&["mem", "1"]).unwrap().value(&vm), Some(0x34));
+    assert_eq!(
+        parse(&["mem", "0x0", "2"]).unwrap().value(&vm),
+        Some(0x1234)
+    );
+    assert_eq!(
+        parse(&["mem", "0", "4"]),
+        Err(BreakpointError::OutOfMemory(0, 4))
+    );
+    assert!(parse(&["mem", "0", "3"]).is_err());
+    assert!(parse(&["r32"]).is_err());
+
+    // Labels of the data
+    assert_eq!(
+        parse(&["mem", "value", "2"]).unwrap().value(&vm),
+        Some(0x3456)
+    );
+    assert!(parse(&["mem", "start"]).is_err());
+    // Nothing reads the data memory
+    assert_eq!(
+        WatchTarget::parse(&["mem", "1"], WatchKind::Read, &session),
+        Err(BreakpointError::ReadWatchOnMemory)
+    );
+}
@@ -0,0 +1,112 @@
+use crate::base::vm::VM;
+use crate::debug::commands::{parse_cmd, CommandError};
+use crate::debug::session::DebugSession;
+use crate::debug::source::SourceMap;
+use lamp_common::debug_info::DebugInfo;
+use lamp_common::register::RegisterAlias;
+
+fn name(text: &str) -> Result<String, CommandError> {
+    parse_cmd(text).map(|command| command.name().to_owned())
+}
+
+#[test]
+pub fn commands_parse_test() {
+    assert_eq!(name("step"), Ok("step".to_owned()));
+    // Aliases win over prefixes
+    assert_eq!(name("s"), Ok("step".to_owned()));
+    assert_eq!(name("b loop if r3 == 2"), Ok("break".to_owned()));
+    assert_eq!(name("exit"), Ok("quit".to_owned()));
+    // Unique prefixes
+    assert_eq!(name("cont"), Ok("continue".to_owned()));
+    assert_eq!(name("  dis 4"), Ok("disasm".to_owned()));
+    // The name has to be a prefix, not just somewhere in the line
+    assert_eq!(
+        name("xstep"),
+        Err(CommandError::Unknown("xstep".to_owned()))
+    );
+    assert_eq!(
+        name("steps"),
+        Err(CommandError::Unknown("steps".to_owned()))
+    );
+    assert_eq!(name(""), Err(CommandError::Empty));
+}
+
+#[test]
+pub fn commands_execute_test() {
+    // INC r1; INC r1
+    let mut session = DebugSession::new(VM::new(vec![5, 1, 5, 1]), vec![], None);
+    assert_eq!(session.execute("st"), 0);
+    assert_eq!(*session.vm.get_register(1), 1);
+    assert_eq!(session.execute("nope"), 1);
+    assert_eq!(session.execute("help break"), 0);
+    assert_eq!(session.execute("help nope"), 1);
+    assert!(!session.is_quit_requested());
+    assert_eq!(session.execute("q"), 0);
+    assert!(session.is_quit_requested());
+}
+
+#[test]
+pub fn commands_assert_test() {
+    // INC r1; INC r1
+    let mut session = DebugSession::new(VM::new(vec![5, 1, 5, 1]), vec![], None);
+    session.execute("step");
+    assert_eq!(session.execute("assert r1 == 1"), 0);
+    assert_eq!(session.execute("assert r1 > r2"), 0);
+    assert_eq!(session.execute("assert r1 == 2"), 1);
+    assert_eq!(session.execute("assert r1 ="), 1);
+    assert_eq!(session.execute("assert"), 1);
+}
+
+#[test]
+pub fn commands_execute_script_test() {
+    let mut session = DebugSession::new(VM::new(vec![5, 1, 5, 1]), vec![], None);
+    let script = "# Comments and blank lines are skipped\n\nstep\nstep # twice\nassert r1 == 2\n";
+    assert_eq!(session.execute_script("test.ldb", script), 0);
+    assert_eq!(*session.vm.get_register(1), 2);
+
+    // Stops at the first failure
+    session.restart();
+    assert_eq!(
+        session.execute_script("test.ldb", "assert r1 == 2\nstep"),
+        1
+    );
+    assert_eq!(*session.vm.get_register(1), 0);
+
+    // And at quit
+    assert_eq!(session.execute_script("test.ldb", "quit\nstep"), 0);
+    assert_eq!(*session.vm.get_register(1), 0);
+}
+
+#[test]
+pub fn commands_script_immediates_test() {
+    // LOAD r1, 3
+    let mut session = DebugSession::new(VM::new(vec![15, 1, 0, 3]), vec![], None);
+    let script = "asm 0 \"LOAD r1, #7\" # not 3\nstep\nassert r1 == 7\n";
+    assert_eq!(session.execute_script("test.ldb", script), 0);
+    assert_eq!(*session.vm.get_register(1), 7);
+}
+
+#[test]
+pub fn commands_register_aliases_test() {
+    // INC r1; INC r1, with `.alias counter, r1` in the debug info
+    let source = SourceMap::new(DebugInfo {
+        aliases: vec![RegisterAlias {
+            name: "counter".to_string(),
+            register: 1,
+        }],
+        ..DebugInfo::default()
+    });
+    let mut session = DebugSession::new(VM::new(vec![5, 1, 5, 1]), vec![], Some(source));
+    assert_eq!(session.execute("setreg counter 5"), 0);
+    assert_eq!(*session.vm.get_register(1), 5);
+    assert_eq!(session.execute("assert COUNTER == 5"), 0);
+    assert_eq!(session.execute("regdump counter"), 0);
+    assert_eq!(session.execute("watch counter"), 0);
+    assert_eq!(session.register_name(1), "r1 (counter)");
+    assert_eq!(session.register_name(31), "r31 (sp)");
+
+    // Without debug info only the default aliases are known
+    let mut session = DebugSession::new(VM::new(vec![5, 1, 5, 1]), vec![], None);
+    assert_eq!(session.execute("setreg counter 5"), 1);
+    assert_eq!(session.execute("setreg sp 5"), 0);
+}
@@ -0,0 +1,193 @@
+use super::looping_program;
+use crate::debug::dap::transport::{read_message, write_message};
+use crate::debug::dap::DapAdapter;
+use crate::debug::session::DebugSession;
+use crate::debug::source::SourceMap;
+use lamp_common::debug_info::{DebugInfo, LineEntry, MacroOrigin, SourceLocation};
+use serde_json::{json, Value};
+
+// loop: INC r3; INC r3; LOAD r5, loop; JMP r5
+// The second INC comes from a macro invoked at line 3
+fn session() -> DebugSession {
+    let location = |line: u32| SourceLocation {
+        file: "src/loop.lasm".to_string(),
+        line,
+        column: 5,
+    };
+    let line = |address: u32, line: u32, expansions: Vec<MacroOrigin>| LineEntry {
+        address,
+        location: location(line),
+        expansions,
+    };
+    let expansion = MacroOrigin {
+        name: "twice".to_string(),
+        file: "src/loop.lasm".to_string(),
+        line: 3,
+    };
+    let source = SourceMap::new(DebugInfo {
+        lines: vec![
+            line(0, 2, vec![]),
+            line(2, 10, vec![expansion]),
+            line(4, 5, vec![]),
+            line(8, 6, vec![]),
+        ],
+        symbols: vec![],
+        aliases: vec![],
+    });
+    DebugSession::new(looping_program(), vec![], Some(source))
+}
+
+fn request(adapter: &mut DapAdapter, command: &str, arguments: Value) -> Vec<Value> {
+    let request =
+        json!({ "seq": 1, "type": "request", "command": command, "arguments": arguments });
+    adapter.handle(&request, &mut || false)
+}
+
+fn body(adapter: &mut DapAdapter, command: &str, arguments: Value) -> Value {
+    let messages = request(adapter, command, arguments);
+    assert_eq!(messages[0]["success"], true, "{}", messages[0]);
+    messages[0]["body"].clone()
+}
+
+#[test]
+pub fn dap_transport_test() {
+    let mut buffer = Vec::<u8>::new();
+    write_message(&mut buffer, &json!({ "seq": 1 })).unwrap();
+    assert_eq!(buffer, b"Content-Length: 9\r\n\r\n{\"seq\":1}".to_vec());
+    write_message(&mut buffer, &json!({ "seq": 2 })).unwrap();
+    let mut reader = std::io::Cursor::new(buffer);
+    assert_eq!(
+        read_message(&mut reader).unwrap(),
+        Some(json!({ "seq": 1 }))
+    );
+    assert_eq!(
+        read_message(&mut reader).unwrap(),
+        Some(json!({ "seq": 2 }))
+    );
+    assert_eq!(read_message(&mut reader).unwrap(), None);
+}
+
+#[test]
+pub fn dap_launch_and_breakpoints_test() {
+    let mut session = session();
+    let mut adapter = DapAdapter::new(&mut session);
+    let messages = request(&mut adapter, "initialize", json!({}));
+    assert_eq!(messages[0]["body"]["supportsStepBack"], true);
+    assert_eq!(messages[1]["event"], "initialized");
+    assert_eq!(
+        (messages[0]["seq"].clone(), messages[1]["seq"].clone()),
+        (json!(1), json!(2))
+    );
+    request(&mut adapter, "launch", json!({}));
+
+    let breakpoints = body(
+        &mut adapter,
+        "setBreakpoints",
+        json!({ "source": { "path": "src/loop.lasm" }, "breakpoints": [
+            { "line": 4 },
+            { "line": 6, "condition": "r3 >= 4" },
+            { "line": 11 },
+            { "line": 2, "condition": "r3 ==" },
+        ]}),
+    )["breakpoints"]
+        .clone();
+    // Line 4 has no code, the breakpoint is on the next line
+    assert_eq!(breakpoints[0]["line"], 5);
+    assert_eq!(breakpoints[1]["verified"], true);
+    assert_eq!(breakpoints[2]["verified"], false);
+    assert_eq!(breakpoints[3]["verified"], false);
+
+    let messages = request(&mut adapter, "configurationDone", json!({}));
+    assert_eq!(messages[1]["body"]["reason"], "breakpoint");
+    assert_eq!(messages[1]["body"]["hitBreakpointIds"], json!([1]));
+    let frames = body(&mut adapter, "stackTrace", json!({}))["stackFrames"].clone();
+    assert_eq!(frames[0]["line"], 5);
+
+    // The condition of the second one holds the second time around
+    request(
+        &mut adapter,
+        "setBreakpoints",
+        json!({ "source": { "path": "src/loop.lasm" }, "breakpoints": [{ "line": 6, "condition": "r3 >= 4" }] }),
+    );
+    let messages = request(&mut adapter, "continue", json!({}));
+    assert_eq!(messages[1]["body"]["reason"], "breakpoint");
+    assert_eq!(
+        body(&mut adapter, "evaluate", json!({ "expression": "r3" }))["result"],
+        "4"
+    );
+    assert_eq!(
+        body(&mut adapter, "evaluate", json!({ "expression": "r3 == 4" }))["result"],
+        "true"
+    );
+    assert_eq!(
+        request(&mut adapter, "evaluate", json!({ "expression": "r99" }))[0]["success"],
+        false
+    );
+}
+
+#[test]
+pub fn dap_steps_and_variables_test() {
+    let mut session = session();
+    let mut adapter = DapAdapter::new(&mut session);
+    request(&mut adapter, "launch", json!({ "stopOnEntry": true }));
+    let messages = request(&mut adapter, "configurationDone", json!({}));
+    assert_eq!(messages[1]["body"]["reason"], "entry");
+
+    // Into the macro: its line is the innermost frame
+    request(&mut adapter, "stepIn", json!({}));
+    let frames = body(&mut adapter, "stackTrace", json!({}))["stackFrames"].clone();
+    assert_eq!(frames.as_array().unwrap().len(), 2);
+    assert_eq!(
+        (frames[0]["name"].clone(), frames[0]["line"].clone()),
+        (json!("twice"), json!(10))
+    );
+    assert_eq!(frames[1]["line"], 3);
+    request(&mut adapter, "stepOut", json!({}));
+    let frames = body(&mut adapter, "stackTrace", json!({}))["stackFrames"].clone();
+    assert_eq!(frames[0]["line"], 5);
+    request(&mut adapter, "stepBack", json!({}));
+    assert_eq!(
+        body(&mut adapter, "stackTrace", json!({}))["stackFrames"][0]["line"],
+        10
+    );
+
+    let registers = body(
+        &mut adapter,
+        "variables",
+        json!({ "variablesReference": 1 }),
+    )["variables"]
+        .clone();
+    assert_eq!(registers[3]["value"], "1");
+    assert_eq!(registers[31]["name"], "r31 (sp)");
+    let set = json!({ "variablesReference": 1, "name": "r31 (sp)", "value": "-0x10" });
+    assert_eq!(body(&mut adapter, "setVariable", set)["value"], "-16");
+    let set = json!({ "variablesReference": 2, "name": "pc", "value": "0x40" });
+    assert_eq!(
+        request(&mut adapter, "setVariable", set)[0]["success"],
+        false
+    );
+    let memory = body(
+        &mut adapter,
+        "readMemory",
+        json!({ "memoryReference": "0x0", "offset": 8, "count": 4 }),
+    );
+    assert_eq!(
+        (memory["data"].clone(), memory["unreadableBytes"].clone()),
+        (json!("EAU="), json!(2))
+    );
+
+    // Runs to the end once the loop is broken
+    request(&mut adapter, "next", json!({}));
+    request(&mut adapter, "next", json!({}));
+    let set = json!({ "variablesReference": 1, "name": "r5", "value": "10" });
+    request(&mut adapter, "setVariable", set);
+    let messages = request(
+        &mut adapter,
+        "next",
+        json!({ "granularity": "instruction" }),
+    );
+    assert_eq!(messages[1]["event"], "exited");
+    assert_eq!(messages[2]["event"], "terminated");
+    request(&mut adapter, "disconnect", json!({}));
+    assert!(adapter.is_disconnected());
+}
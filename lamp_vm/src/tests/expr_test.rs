@@ -0,0 +1,109 @@
+use super::symbol;
+use crate::base::vm::VM;
+use crate::debug::breakpoints::{BreakpointError, Condition};
+use crate::debug::expr::{Expr, ExprError};
+use crate::debug::session::DebugSession;
+use lamp_common::container::SectionKind;
+use lamp_common::object::Symbol;
+
+fn symbols() -> Vec<Symbol> {
+    vec![symbol("counter", SectionKind::Data, 2)]
+}
+
+fn vm() -> VM {
+    // INC r1; INC r1
+    let mut vm = VM::with_memory(vec![5, 1, 5, 1], vec![0, 0, 0, 0, 1, 2, 0xff, 0xff], 0);
+    vm.set_register_value(3, 10);
+    vm
+}
+
+fn evaluate(text: &str) -> Result<i32, ExprError> {
+    Expr::parse(text, &symbols(), &[])?.evaluate(&vm())
+}
+
+#[test]
+pub fn expr_evaluate_test() {
+    assert_eq!(evaluate("r3 + 2 * 3"), Ok(16));
+    assert_eq!(evaluate("(r3 + 2) * 3"), Ok(36));
+    assert_eq!(evaluate("r3 - 4 - 3"), Ok(3));
+    assert_eq!(evaluate("-r3 / 3"), Ok(-3));
+    assert_eq!(evaluate("r3 % 4 << 2"), Ok(8));
+    assert_eq!(evaluate("~0 & 0xf0 | 1"), Ok(0xf1));
+    assert_eq!(evaluate("0xffffffff"), Ok(-1));
+    assert_eq!(evaluate("r3 == 10 && !eq"), Ok(1));
+    assert_eq!(evaluate("pc || remainder"), Ok(0));
+    // Labels are addresses, `*` reads a word of the data memory
+    assert_eq!(evaluate("counter"), Ok(2));
+    assert_eq!(evaluate("*counter"), Ok(0x0102));
+    assert_eq!(evaluate("*(counter + 4)"), Err(ExprError::OutOfMemory(6)));
+    assert_eq!(evaluate("r3 / (r1 - r1)"), Err(ExprError::DivisionByZero));
+    // The right side of && isn't evaluated when the left one is false
+    assert_eq!(evaluate("r1 && r3 / r1"), Ok(0));
+    assert_eq!(
+        evaluate("nowhere + 1"),
+        Err(ExprError::UnknownName("nowhere".to_owned()))
+    );
+    assert!(matches!(evaluate("r3 +"), Err(ExprError::Syntax(_))));
+    assert!(matches!(evaluate("(r3"), Err(ExprError::Syntax(_))));
+    assert!(matches!(evaluate("r3 = 4"), Err(ExprError::Syntax(_))));
+    assert!(matches!(evaluate("0x1ffffffff"), Err(ExprError::Syntax(_))));
+}
+
+#[test]
+pub fn expr_display_expressions_test() {
+    let text = |text: &str| Expr::parse(text, &symbols(), &[]).unwrap().to_string();
+    assert_eq!(text("r3+2*3"), "r3 + 2 * 3");
+    assert_eq!(text("((r3 + 2)) * 3"), "(r3 + 2) * 3");
+    assert_eq!(text("r3 - (4 - 3)"), "r3 - (4 - 3)");
+    assert_eq!(text("*(counter+4)==-1"), "*(counter + 4) == -1");
+    assert_eq!(
+        Expr::parse("sp == r3 + sp", &[], &[]).unwrap().registers(),
+        vec![(31, "sp".to_owned()), (3, "r3".to_owned())]
+    );
+}
+
+#[test]
+pub fn expr_conditions_with_symbols_test() {
+    let vm = vm();
+    let condition = Condition::parse("*counter == 258 && r3 > 5", &symbols(), &[]).unwrap();
+    assert!(condition.holds(&vm));
+    assert_eq!(condition.register_values(&vm), "r3 = 10");
+    assert!(matches!(
+        "counter == 2".parse::<Condition>(),
+        Err(BreakpointError::InvalidCondition(
+            _,
+            ExprError::UnknownName(_)
+        ))
+    ));
+    // A condition that can't be evaluated stops the program
+    assert!(Condition::parse("*0x100", &[], &[]).unwrap().holds(&vm));
+}
+
+#[test]
+pub fn expr_print_and_display_test() {
+    let mut session = DebugSession::new(vm(), symbols(), None);
+    assert_eq!(session.execute("print r3 * 2"), 0);
+    assert_eq!(session.execute("p/x *counter"), 0);
+    assert_eq!(session.execute("p/i r3"), 1);
+    assert_eq!(session.execute("p *0x100"), 1);
+    assert_eq!(session.execute("p"), 1);
+    assert_eq!(session.execute("display/x r1"), 0);
+    assert_eq!(session.execute("display r1 + r3"), 0);
+    assert_eq!(session.execute("display nowhere"), 1);
+    assert_eq!(session.displays.list().len(), 2);
+    assert_eq!(
+        session.displays.list()[1].show(&session.vm),
+        "2: r1 + r3 = 10"
+    );
+    assert_eq!(session.execute("step"), 0);
+    assert_eq!(
+        session.displays.list()[0].show(&session.vm),
+        "1: /x r1 = 0x00000001"
+    );
+    assert_eq!(session.execute("undisplay 1"), 0);
+    assert_eq!(session.execute("undisplay 1"), 1);
+    assert_eq!(session.execute("undisplay"), 0);
+    assert!(session.displays.list().is_empty());
+    assert_eq!(session.execute("break 2 if r1 + 1 == counter"), 0);
+    assert_eq!(session.execute("assert r1 == 1"), 0);
+}
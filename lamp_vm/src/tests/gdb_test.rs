@@ -0,0 +1,132 @@
+use crate::base::vm::VM;
+use crate::debug::gdb::packet::{encode, Incoming, PacketReader};
+use crate::debug::gdb::{GdbTarget, Response, DATA_ADDRESS};
+use crate::debug::session::DebugSession;
+
+// loop: INC r3; INC r3; LOAD r5, loop; JMP r5
+fn session() -> DebugSession {
+    let vm = VM::with_memory(vec![5, 3, 5, 3, 15, 5, 0, 0, 16, 5], vec![1, 2, 3, 4], 0);
+    DebugSession::new(vm, vec![], None)
+}
+
+fn reply(target: &mut GdbTarget, packet: &str) -> String {
+    match target.handle(packet, &mut || false) {
+        Response::Reply(reply) => reply,
+        Response::Close(_) => panic!("the connection was closed"),
+    }
+}
+
+#[test]
+pub fn gdb_packets_test() {
+    let mut reader = PacketReader::new();
+    reader.push(b"+$qSupported:swbreak+#8b$m0,4");
+    assert_eq!(reader.next_message(), Some(Incoming::Ack));
+    assert_eq!(
+        reader.next_message(),
+        Some(Incoming::Packet("qSupported:swbreak+".to_owned()))
+    );
+    assert_eq!(reader.next_message(), None);
+    reader.push(b"#00\x03");
+    assert_eq!(reader.next_message(), Some(Incoming::BadChecksum));
+    assert!(reader.take_interrupt());
+
+    // Escaped characters
+    assert_eq!(encode("OK"), b"$OK#9a".to_vec());
+    assert_eq!(encode("a#"), b"$a}\x03#e1".to_vec());
+    reader.push(&encode("X}*$"));
+    assert_eq!(
+        reader.next_message(),
+        Some(Incoming::Packet("X}*$".to_owned()))
+    );
+}
+
+#[test]
+pub fn gdb_registers_and_memory_test() {
+    let mut session = session();
+    let mut target = GdbTarget::new(&mut session);
+    assert!(reply(&mut target, "qSupported:multiprocess+").contains("qXfer:features:read+"));
+    let xml = reply(&mut target, "qXfer:features:read:target.xml:0,fff");
+    assert!(xml.starts_with('l') && xml.contains("name=\"r31\"") && xml.contains("name=\"pc\""));
+    let xml = reply(
+        &mut target,
+        "qXfer:features:read:target.xml:10,ffffffffffffffff",
+    );
+    assert!(xml.starts_with('l') && xml.ends_with("</target>\n"));
+    assert_eq!(
+        reply(&mut target, "qXfer:features:read:target.xml:ffff,10"),
+        "E00"
+    );
+
+    assert_eq!(reply(&mut target, "P3=2a000000"), "OK");
+    assert_eq!(reply(&mut target, "p3"), "2a000000");
+    assert_eq!(reply(&mut target, "g").len(), 35 * 8);
+    // pc
+    assert_eq!(reply(&mut target, "P20=02000000"), "OK");
+    assert_eq!(reply(&mut target, "p20"), "02000000");
+    assert_eq!(reply(&mut target, "P20=ff000000"), "E01");
+
+    assert_eq!(reply(&mut target, "m0,4"), "05030503");
+    assert_eq!(
+        reply(&mut target, &format!("m{:x},8", DATA_ADDRESS)),
+        "01020304"
+    );
+    assert_eq!(
+        reply(&mut target, &format!("M{:x},2:abcd", DATA_ADDRESS + 1)),
+        "OK"
+    );
+    assert_eq!(
+        reply(&mut target, &format!("m{:x},4", DATA_ADDRESS)),
+        "01abcd04"
+    );
+    assert_eq!(
+        reply(&mut target, &format!("M{:x},2:abcd", DATA_ADDRESS + 3)),
+        "E14"
+    );
+    assert_eq!(reply(&mut target, "m100,4"), "E14");
+    // Lengths reaching past the end of the address space
+    assert_eq!(
+        reply(&mut target, "m1,ffffffffffffffff"),
+        "0305030f0500001005"
+    );
+    assert_eq!(reply(&mut target, "Mffffffffffffffff,2:abcd"), "E14");
+}
+
+#[test]
+pub fn gdb_execution_test() {
+    let mut session = session();
+    let mut target = GdbTarget::new(&mut session);
+    assert_eq!(reply(&mut target, "s"), "S05");
+    assert_eq!(reply(&mut target, "p3"), "01000000");
+    assert_eq!(reply(&mut target, "Z0,2,1"), "OK");
+    assert_eq!(reply(&mut target, "c"), "T05swbreak:;");
+    assert_eq!(reply(&mut target, "p3"), "03000000");
+    assert_eq!(reply(&mut target, "?"), "T05swbreak:;");
+    assert_eq!(reply(&mut target, "bs"), "S05");
+    assert_eq!(reply(&mut target, "bc"), "T05swbreak:;");
+    assert_eq!(reply(&mut target, "p3"), "01000000");
+    assert_eq!(reply(&mut target, "z0,2,1"), "OK");
+    assert_eq!(reply(&mut target, "bc"), "T05replaylog:begin;");
+
+    // Interrupted while looping forever
+    let mut polls = 0;
+    let stop = target.handle("c", &mut || {
+        polls += 1;
+        polls == 3
+    });
+    assert_eq!(stop, Response::Reply("S02".to_owned()));
+    assert_eq!(target.handle("k", &mut || false), Response::Close(None));
+}
+
+#[test]
+pub fn gdb_faults_and_exit_test() {
+    // MOD r1, r2, r3; INC r1
+    let vm = VM::new(vec![4, 1, 2, 3, 5, 1]);
+    let mut session = DebugSession::new(vm, vec![], None);
+    let mut target = GdbTarget::new(&mut session);
+    assert_eq!(reply(&mut target, "c"), "S08");
+    // Still at the faulting instruction
+    assert_eq!(reply(&mut target, "p20"), "00000000");
+    assert_eq!(reply(&mut target, "P2=01000000"), "OK");
+    assert_eq!(reply(&mut target, "c"), "W00");
+    assert_eq!(reply(&mut target, "s"), "W00");
+}
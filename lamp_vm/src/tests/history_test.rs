@@ -0,0 +1,123 @@
+use super::looping_program;
+use crate::base::vm::VM;
+use crate::debug::commands::{parse_cmd, CommandError};
+use crate::debug::history::History;
+use crate::debug::session::{DebugSession, Stop};
+
+// LOAD r1, 7; LOAD r2, 3; MOD r1, r2, r3; EQ r1, r1; INC r3
+fn program() -> VM {
+    VM::new(vec![15, 1, 0, 7, 15, 2, 0, 3, 4, 1, 2, 3, 7, 1, 1, 5, 3])
+}
+
+#[test]
+pub fn history_undo_test() {
+    let mut vm = program();
+    let mut history = History::new(10);
+    for _ in 0..5 {
+        let before = History::before(&vm);
+        vm.cycle().unwrap();
+        history.after(before, &vm);
+    }
+    assert_eq!(history.count(), 5);
+    assert_eq!(*vm.get_register(3), 3);
+    assert_eq!(vm.get_modulo_remainder(), 1);
+    assert!(vm.get_eq_flag());
+
+    assert!(history.undo(&mut vm));
+    assert_eq!(*vm.get_register(3), 2);
+    assert_eq!(vm.get_pc(), 15);
+    assert!(history.undo(&mut vm));
+    assert!(!vm.get_eq_flag());
+    assert!(history.undo(&mut vm));
+    assert_eq!(*vm.get_register(3), 0);
+    assert_eq!(vm.get_modulo_remainder(), 0);
+    assert_eq!(vm.get_pc(), 8);
+    assert!(history.undo(&mut vm));
+    assert!(history.undo(&mut vm));
+    assert_eq!(*vm.get_register(1), 0);
+    assert_eq!(vm.get_pc(), 0);
+    assert_eq!(history.count(), 0);
+    assert!(!history.undo(&mut vm));
+}
+
+#[test]
+pub fn history_size_test() {
+    let mut session = DebugSession::new(looping_program(), vec![], None);
+    session.history.set_size(3);
+    for _ in 0..10 {
+        assert_eq!(session.step(), Stop::Step);
+    }
+    assert_eq!(session.history.count(), 10);
+    assert_eq!(session.history.len(), 3);
+    assert_eq!(session.history.oldest(), 8);
+    assert!(session.reverse_step());
+    assert!(session.reverse_step());
+    assert!(session.reverse_step());
+    assert!(!session.reverse_step());
+    assert_eq!(session.history.count(), 7);
+
+    // Nothing is recorded, instructions are still counted
+    session.history.set_size(0);
+    session.step();
+    assert_eq!(session.history.count(), 8);
+    assert!(!session.reverse_step());
+
+    session.restart();
+    assert_eq!(session.history.count(), 0);
+}
+
+#[test]
+pub fn history_reverse_continue_test() {
+    let mut session = DebugSession::new(looping_program(), vec![], None);
+    let id = session.breakpoints.add(2, "0x2", None);
+    assert_eq!(session.resume(), Stop::Breakpoint(id));
+    assert_eq!(session.resume(), Stop::Breakpoint(id));
+    assert_eq!(*session.vm.get_register(3), 3);
+    // Back to the previous time the breakpoint was reached
+    assert_eq!(session.reverse_continue(), Some(id));
+    assert_eq!(session.vm.get_pc(), 2);
+    assert_eq!(*session.vm.get_register(3), 1);
+    // Then to the start
+    assert_eq!(session.reverse_continue(), None);
+    assert_eq!(session.vm.get_pc(), 0);
+    assert_eq!(*session.vm.get_register(3), 0);
+}
+
+#[test]
+pub fn history_goto_test() {
+    let mut session = DebugSession::new(looping_program(), vec![], None);
+    assert_eq!(session.goto(6), Some(Stop::Step));
+    assert_eq!(*session.vm.get_register(3), 4);
+    assert_eq!(session.goto(1), Some(Stop::Step));
+    assert_eq!(*session.vm.get_register(3), 1);
+    assert_eq!(session.history.count(), 1);
+
+    session.history.set_size(2);
+    session.goto(8);
+    assert_eq!(session.goto(5), None);
+    assert_eq!(session.history.count(), 8);
+    assert_eq!(session.goto(6), Some(Stop::Step));
+
+    assert_eq!(session.execute("goto 3"), 1);
+    assert_eq!(session.execute("goto 0"), 1);
+    assert_eq!(session.execute("goto 10"), 0);
+    assert_eq!(session.history.count(), 9);
+}
+
+#[test]
+pub fn history_reverse_commands_test() {
+    assert!(matches!(
+        parse_cmd("re"),
+        Err(CommandError::Ambiguous(_, _))
+    ));
+    assert_eq!(parse_cmd("rs").unwrap().name(), "reverse-step");
+    assert_eq!(parse_cmd("reverse-c").unwrap().name(), "reverse-continue");
+
+    let mut session = DebugSession::new(program(), vec![], None);
+    assert_eq!(session.execute("reverse-step"), 1);
+    session.execute("step");
+    assert_eq!(session.execute("rs"), 0);
+    assert_eq!(session.vm.get_pc(), 0);
+    assert_eq!(session.execute("history 5"), 0);
+    assert_eq!(session.history.size(), 5);
+}
@@ -0,0 +1,87 @@
+use crate::base::loader::{load, LoadError};
+use lamp_common::container::{Container, ContainerError, Section, SectionKind, FLAG_WORD_64};
+
+// Same program as vm_add_test: LOAD 13, 15, 15; LOAD 14, 16, 16; ADD 13, 14, 15
+fn add_program() -> Vec<u8> {
+    vec![15, 13, 15, 15, 15, 14, 16, 16, 1, 13, 14, 15]
+}
+
+#[test]
+pub fn loader_runs_container_test() {
+    let mut container = Container::executable(add_program(), 0);
+    container
+        .sections
+        .push(Section::new(SectionKind::Rodata, 0, vec![1, 2, 3]));
+    container.sections.push(Section::bss(16, 8));
+
+    let mut vm = load(&container.to_bytes()).unwrap();
+    assert_eq!(vm.get_memory().len(), 24);
+    assert_eq!(&vm.get_memory()[..4], &[1, 2, 3, 0]);
+
+    let _ = vm.run();
+    let expected_value = ((15 << 8) | 15) + ((16 << 8) | 16);
+    assert_eq!(*vm.get_register(15), expected_value);
+}
+
+#[test]
+pub fn loader_entry_point_test() {
+    let vm = load(&Container::executable(add_program(), 4).to_bytes()).unwrap();
+    assert_eq!(vm.get_pc(), 4);
+
+    let res = load(&Container::executable(add_program(), 12).to_bytes());
+    assert_eq!(res.err(), Some(LoadError::EntryOutOfBounds(12)));
+
+    // There is nothing to run, even from the entry point 0
+    let res = load(&Container::executable(vec![], 0).to_bytes());
+    assert_eq!(res.err(), Some(LoadError::EmptyCodeSection));
+}
+
+#[test]
+pub fn loader_rejects_foreign_file_test() {
+    let res = load(&add_program());
+    assert_eq!(
+        res.err(),
+        Some(LoadError::InvalidContainer(ContainerError::BadMagic))
+    );
+}
+
+#[test]
+pub fn loader_rejects_corrupted_file_test() {
+    let mut bytes = Container::executable(add_program(), 0).to_bytes();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xFF;
+
+    match load(&bytes) {
+        Err(LoadError::InvalidContainer(ContainerError::ChecksumMismatch { .. })) => {}
+        other => panic!("Expected a checksum mismatch, got {:?}", other.err()),
+    }
+
+    let bytes = Container::executable(add_program(), 0).to_bytes();
+    assert_eq!(
+        load(&bytes[..10]).err(),
+        Some(LoadError::InvalidContainer(ContainerError::Truncated))
+    );
+}
+
+#[test]
+pub fn loader_rejects_unsupported_target_test() {
+    let mut container = Container::executable(add_program(), 0);
+    container.flags |= FLAG_WORD_64;
+    assert_eq!(
+        load(&container.to_bytes()).err(),
+        Some(LoadError::UnsupportedWordSize(64))
+    );
+
+    let mut container = Container::executable(add_program(), 0);
+    container
+        .sections
+        .push(Section::new(SectionKind::Data, 0, vec![1, 2, 3]));
+    container.sections.push(Section::bss(2, 8));
+    assert_eq!(
+        load(&container.to_bytes()).err(),
+        Some(LoadError::OverlappingSections(
+            SectionKind::Data,
+            SectionKind::Bss
+        ))
+    );
+}
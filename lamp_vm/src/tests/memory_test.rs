@@ -0,0 +1,135 @@
+use super::symbol;
+use crate::base::vm::VM;
+use crate::debug::breakpoints::BreakpointError;
+use crate::debug::memory::{find, hexdump, label_at, parse_address, read, Examine, Format, Space};
+use crate::debug::session::DebugSession;
+use lamp_common::container::SectionKind;
+
+fn session() -> DebugSession {
+    // LOAD r1, 3; INC r1; NOP
+    let code = vec![15, 1, 0, 3, 5, 1, 14];
+    let memory = b"hello\0\x12\x34\xff\xfe".to_vec();
+    let symbols = vec![
+        symbol("_start", SectionKind::Code, 0),
+        symbol("msg", SectionKind::Data, 0),
+        symbol("nums", SectionKind::Rodata, 6),
+    ];
+    DebugSession::new(VM::with_memory(code, memory, 0), symbols, None)
+}
+
+#[test]
+pub fn memory_parse_address_test() {
+    let session = session();
+    assert_eq!(
+        parse_address("0x4", Space::Data, &session),
+        Ok((Space::Data, 4))
+    );
+    assert_eq!(
+        parse_address("code:0x4", Space::Data, &session),
+        Ok((Space::Code, 4))
+    );
+    assert_eq!(
+        parse_address("nums", Space::Code, &session),
+        Ok((Space::Data, 6))
+    );
+    assert_eq!(
+        parse_address("_start", Space::Data, &session),
+        Ok((Space::Code, 0))
+    );
+    assert_eq!(
+        parse_address("pc", Space::Data, &session),
+        Ok((Space::Code, 0))
+    );
+    assert_eq!(
+        parse_address("data:_start", Space::Data, &session),
+        Err(BreakpointError::InvalidLocation("_start".to_owned()))
+    );
+    assert_eq!(
+        parse_address("nowhere", Space::Data, &session),
+        Err(BreakpointError::UnknownLabel("nowhere".to_owned()))
+    );
+}
+
+#[test]
+pub fn memory_read_and_labels_test() {
+    let session = session();
+    assert_eq!(read(&session, Space::Data, 6, 2), Ok(&[0x12, 0x34][..]));
+    assert_eq!(
+        read(&session, Space::Data, 8, 4),
+        Err(BreakpointError::OutOfMemory(8, 4))
+    );
+    assert_eq!(
+        read(&session, Space::Code, 7, 1),
+        Err(BreakpointError::OutOfCode(7))
+    );
+    assert_eq!(
+        label_at(&session, Space::Data, 8),
+        Some("<nums+2>".to_owned())
+    );
+    assert_eq!(
+        label_at(&session, Space::Code, 0),
+        Some("<_start>".to_owned())
+    );
+}
+
+#[test]
+pub fn memory_examine_format_test() {
+    let examine = Examine::parse("4dh").unwrap();
+    assert_eq!(examine.count, 4);
+    assert_eq!(examine.format, Format::Decimal);
+    assert_eq!(examine.unit, 2);
+    assert_eq!(examine.format_value(&[0xff, 0xfe]), "-2");
+    // One hexadecimal word by default
+    let examine = Examine::parse("").unwrap();
+    assert_eq!((examine.count, examine.unit), (1, 4));
+    assert_eq!(examine.format_value(&[0x12, 0x34, 0, 1]), "0x12340001");
+    assert_eq!(Examine::parse("ub").unwrap().format_value(&[0xff]), "255");
+    // Characters are bytes, whatever the unit
+    let examine = Examine::parse("2cw").unwrap();
+    assert_eq!(examine.unit, 1);
+    assert_eq!(examine.format_value(b"h"), "104 'h'");
+    assert_eq!(examine.format_value(&[0]), "0 '\\x00'");
+    assert_eq!(Examine::parse("4q"), None);
+}
+
+#[test]
+pub fn memory_hexdump_and_find_test() {
+    let bytes: Vec<u8> = (0x41..0x53).collect();
+    assert_eq!(
+        hexdump(&bytes, 0x10),
+        vec![
+            "0x0010  41 42 43 44 45 46 47 48 49 4a 4b 4c 4d 4e 4f 50  |ABCDEFGHIJKLMNOP|",
+            "0x0020  51 52                                            |QR|",
+        ]
+    );
+    assert_eq!(find(b"abcabc", b"bc"), vec![1, 4]);
+    assert_eq!(find(b"abc", b""), Vec::<usize>::new());
+}
+
+#[test]
+pub fn memory_commands_test() {
+    let mut session = session();
+    assert_eq!(session.execute("x/6c msg"), 0);
+    assert_eq!(session.execute("x/2xh nums"), 0);
+    assert_eq!(session.execute("x/3i pc"), 0);
+    assert_eq!(session.execute("x/2xw nums"), 1);
+    assert_eq!(session.execute("x/4q msg"), 1);
+    // The size of the values read overflows
+    assert_eq!(session.execute("x/4611686018427387904xw 0"), 1);
+    assert_eq!(session.execute("x/4611686018427387904i 0"), 0);
+    assert_eq!(session.execute("hexdump code:0"), 0);
+    assert_eq!(session.execute("hexdump 0x100"), 1);
+    assert_eq!(session.execute("search \"ll\""), 0);
+    assert_eq!(session.execute("search code 5 1"), 0);
+    assert_eq!(session.execute("search 0x100"), 1);
+
+    let path = std::env::temp_dir().join("lamp_vm_memory_test.bin");
+    let file = path.to_str().unwrap();
+    assert_eq!(
+        session.execute(&format!("dump memory {} msg nums", file)),
+        0
+    );
+    assert_eq!(std::fs::read(&path).unwrap(), b"hello\0");
+    assert_eq!(session.execute(&format!("dump memory {} 4 2", file)), 1);
+    let _ = std::fs::remove_file(&path);
+}
@@ -1,2 +1,51 @@
+use crate::base::vm::VM;
+use lamp_common::container::SectionKind;
+use lamp_common::object::Symbol;
+
+#[allow(dead_code)]
+mod breakpoints_test;
+#[allow(dead_code)]
+mod commands_test;
+#[allow(dead_code)]
+mod dap_test;
+#[allow(dead_code)]
+mod expr_test;
+#[allow(dead_code)]
+mod gdb_test;
+#[allow(dead_code)]
+mod history_test;
+#[allow(dead_code)]
+mod loader_test;
+#[allow(dead_code)]
+mod memory_test;
+#[allow(dead_code)]
+mod patch_test;
+#[allow(dead_code)]
+mod profile_test;
+#[allow(dead_code)]
+mod trace_test;
+#[allow(dead_code)]
+mod tui_test;
 #[allow(dead_code)]
 mod vm_test;
+
+// Shared by the tests of the debugger
+
+// loop: INC r3; INC r3; LOAD r5, loop; JMP r5
+pub fn looping_program() -> VM {
+    VM::new(vec![5, 3, 5, 3, 15, 5, 0, 0, 16, 5])
+}
+
+pub fn symbol(name: &str, section: SectionKind, value: u32) -> Symbol {
+    Symbol {
+        name: name.to_string(),
+        section: Some(section),
+        value,
+        global: false,
+    }
+}
+
+// A code label
+pub fn label(name: &str, value: u32) -> Symbol {
+    symbol(name, SectionKind::Code, value)
+}
@@ -0,0 +1,125 @@
+use super::label;
+use crate::base::vm::VM;
+use crate::debug::patch::{assemble, write_memory, PatchError};
+use crate::debug::session::DebugSession;
+use lamp_common::op::{Opcode, OperandKind};
+
+#[test]
+pub fn patch_assemble_test() {
+    let symbols = vec![label("loop", 0x10)];
+    assert_eq!(
+        assemble("ADD r1, r2, sp", 0, &symbols),
+        Ok(vec![1, 1, 2, 31])
+    );
+    assert_eq!(
+        assemble("LOAD r5, loop + 2", 0, &symbols),
+        Ok(vec![15, 5, 0, 0x12])
+    );
+    assert_eq!(
+        assemble("LOAD r5, #(. + 1)", 8, &symbols),
+        Ok(vec![15, 5, 0, 9])
+    );
+    assert_eq!(
+        assemble("LOAD r5, 0xffff", 0, &symbols),
+        Ok(vec![15, 5, 0xff, 0xff])
+    );
+    assert_eq!(
+        assemble("LOAD r5, -1", 0, &symbols),
+        Err(PatchError::OutOfRange(-1))
+    );
+    assert_eq!(
+        assemble("ADD r1, r2", 0, &symbols),
+        Err(PatchError::WrongOperandCount(Opcode::ADD, 3, 2))
+    );
+    assert_eq!(
+        assemble("JMP 4", 0, &symbols),
+        Err(PatchError::WrongOperandType(
+            Opcode::JMP,
+            0,
+            OperandKind::Register
+        ))
+    );
+    assert_eq!(
+        assemble("LOAD r1, 70000", 0, &symbols),
+        Err(PatchError::OutOfRange(70000))
+    );
+    assert!(matches!(
+        assemble("LOAD r1, nowhere", 0, &symbols),
+        Err(PatchError::Syntax(_))
+    ));
+    assert!(matches!(
+        assemble("here: NOP", 0, &symbols),
+        Err(PatchError::NotAnInstruction(_))
+    ));
+}
+
+#[test]
+pub fn patch_write_memory_test() {
+    let mut vm = VM::with_memory(vec![], vec![0; 4], 0);
+    assert_eq!(write_memory(&mut vm, 1, &[7, 8]), Ok(()));
+    assert_eq!(vm.get_memory(), &[0, 7, 8, 0]);
+    assert_eq!(
+        write_memory(&mut vm, 3, &[1, 2]),
+        Err(PatchError::OutOfMemory(3, 2))
+    );
+}
+
+#[test]
+pub fn patch_commands_test() {
+    // LOAD r1, 7; INC r1
+    let vm = VM::with_memory(vec![15, 1, 0, 7, 5, 1], vec![0; 8], 0);
+    let mut session = DebugSession::new(vm, vec![], None);
+    assert_eq!(session.execute("setreg r2 -3"), 0);
+    assert_eq!(*session.vm.get_register(2), -3);
+    assert_eq!(session.execute("setreg r2"), 1);
+    // Out of the i32 range, without overflowing on the way
+    assert_eq!(session.execute("setreg r2 -0x8000000000000000"), 1);
+    assert_eq!(session.execute("setreg r2 -0xffffffffffffffff"), 1);
+    assert_eq!(session.execute("setreg r2 -0x80000000"), 0);
+    assert_eq!(*session.vm.get_register(2), i32::MIN);
+    assert_eq!(session.execute("setflag eq 1"), 0);
+    assert!(session.vm.get_eq_flag());
+    assert_eq!(session.execute("setflag remainder 0x10"), 0);
+    assert_eq!(session.vm.get_modulo_remainder(), 16);
+    assert_eq!(session.execute("setmem 2 0xab 1"), 0);
+    assert_eq!(session.execute("setmem 2 256"), 1);
+    assert_eq!(session.execute("fill 5 3 0xff"), 0);
+    assert_eq!(session.execute("fill 6 3 0xff"), 1);
+    assert_eq!(session.execute("fill 0 18446744073709551615 1"), 1);
+    assert_eq!(session.execute("fill 1 18446744073709551615 1"), 1);
+    assert_eq!(
+        session.vm.get_memory(),
+        &[0, 0, 0xab, 1, 0, 0xff, 0xff, 0xff]
+    );
+
+    // A shorter instruction is followed by NOPs
+    assert_eq!(session.execute("asm 0 \"INC r1\""), 0);
+    assert_eq!(&session.vm.get_program()[..4], &[5, 1, 14, 14]);
+    assert_eq!(session.execute("asm 4 \"LOAD r1, 1\""), 1);
+    assert_eq!(session.execute("asm 4 MUL r1, r1"), 1);
+    // Patches are kept when the program runs again
+    assert_eq!(session.execute("run"), 0);
+    assert_eq!(*session.vm.get_register(1), 2);
+}
+
+#[test]
+pub fn patch_asm_odd_padding_test() {
+    // MOD r1, r2, r3; INC r5
+    let vm = VM::new(vec![4, 1, 2, 3, 5, 5]);
+    let mut session = DebugSession::new(vm, vec![], None);
+    // One NOP is left, the INC after it is still executed
+    assert_eq!(session.execute("asm 0 \"EQ r1, r2\""), 0);
+    assert_eq!(session.vm.get_program(), &[7, 1, 2, 14, 5, 5]);
+    assert_eq!(session.execute("continue"), 0);
+    assert_eq!(*session.vm.get_register(5), 1);
+}
+
+#[test]
+pub fn patch_unsigned_load_test() {
+    let vm = VM::new(vec![14, 14, 14, 14]);
+    let mut session = DebugSession::new(vm, vec![], None);
+    assert_eq!(session.execute("asm 0 \"LOAD r1, #-1\""), 1);
+    assert_eq!(session.execute("asm 0 \"LOAD r1, #65535\""), 0);
+    assert_eq!(session.execute("step"), 0);
+    assert_eq!(*session.vm.get_register(1), 65535);
+}
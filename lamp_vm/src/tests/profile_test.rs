@@ -0,0 +1,125 @@
+use super::label;
+use crate::base::profile::Profile;
+use crate::base::vm::{VMError, VM};
+use lamp_common::debug_info::{DebugInfo, LineEntry, MacroOrigin, SourceLocation};
+use lamp_common::op::Opcode;
+
+// _start: LOAD r1, 3; LOAD r4, 8
+// loop: DEC r1; MOD r2, r1, r3 (r2 / r1); JMP r4
+fn program() -> VM {
+    VM::new(vec![15, 1, 0, 3, 15, 4, 0, 8, 6, 1, 4, 2, 1, 3, 16, 4])
+}
+
+fn line(address: u32, line: u32, macros: &[&str]) -> LineEntry {
+    LineEntry {
+        address,
+        location: SourceLocation {
+            file: "p.lasm".to_string(),
+            line,
+            column: 1,
+        },
+        expansions: macros
+            .iter()
+            .map(|name| MacroOrigin {
+                name: name.to_string(),
+                file: "p.lasm".to_string(),
+                line: 20,
+            })
+            .collect(),
+    }
+}
+
+fn profile() -> Profile {
+    let mut vm = program();
+    let mut profile = Profile::new(&vm);
+    assert_eq!(profile.run(&mut vm), Err(VMError::DivisionByZero));
+    profile
+}
+
+#[test]
+pub fn profile_counts_test() {
+    let profile = profile();
+    assert_eq!(profile.total(), 10);
+    assert_eq!(
+        profile.hot_spots(),
+        vec![(8, 3), (10, 3), (14, 2), (0, 1), (4, 1)]
+    );
+    assert_eq!(profile.count(2), 0);
+    assert_eq!(
+        profile.by_opcode(program().get_program()),
+        vec![
+            (Opcode::MOD, 3),
+            (Opcode::DEC, 3),
+            (Opcode::LOAD, 2),
+            (Opcode::JMP, 2)
+        ]
+    );
+}
+
+#[test]
+pub fn profile_labels_and_lines_test() {
+    let profile = profile();
+    let symbols = vec![label("_start", 0), label("loop", 8)];
+    assert_eq!(
+        profile.by_label(&symbols),
+        vec![("loop".to_string(), 8), ("_start".to_string(), 2)]
+    );
+    // Nothing labels the start of the code
+    assert_eq!(
+        profile.by_label(&symbols[1..]),
+        vec![("loop".to_string(), 8), ("[unknown]".to_string(), 2)]
+    );
+
+    let info = DebugInfo {
+        lines: vec![line(0, 1, &[]), line(8, 3, &[]), line(10, 8, &["div"])],
+        symbols: vec![],
+        aliases: vec![],
+    };
+    assert_eq!(
+        profile.by_line(&info),
+        vec![
+            ("p.lasm:20".to_string(), 5),
+            ("p.lasm:3".to_string(), 3),
+            ("p.lasm:1".to_string(), 2),
+        ]
+    );
+}
+
+#[test]
+pub fn profile_folded_stacks_test() {
+    let profile = profile();
+    let symbols = vec![label("_start", 0), label("loop", 8)];
+    let info = DebugInfo {
+        lines: vec![line(0, 1, &[]), line(10, 8, &["div", "check"])],
+        symbols: vec![],
+        aliases: vec![],
+    };
+    assert_eq!(
+        profile.folded(&symbols, Some(&info)),
+        vec!["_start 2", "loop 3", "loop;div;check 5"]
+    );
+    assert_eq!(profile.folded(&symbols, None), vec!["_start 2", "loop 8"]);
+}
+
+#[test]
+pub fn profile_report_test() {
+    let profile = profile();
+    let vm = program();
+    let symbols = vec![label("_start", 0), label("loop", 8)];
+    let report = profile.report(vm.get_program(), &symbols, None, 2);
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines[0], "10 instructions executed");
+    assert_eq!(
+        lines[4],
+        "          3  30.00%  0x0008   DEC r1                  <loop>"
+    );
+    assert_eq!(
+        lines[5],
+        "          3  30.00%  0x000a   MOD r2, r1, r3          <loop+2>"
+    );
+    assert!(!report.contains("JMP"));
+    assert!(report.contains("\nLabels:\n"));
+    assert!(report.contains("          8  80.00%  loop\n"));
+    assert!(!report.contains("Source lines"));
+    assert!(report.contains("          3  30.00%  DEC\n"));
+}
@@ -0,0 +1,77 @@
+use super::label;
+use crate::base::trace::{parse_opcode, parse_range, TraceFilter, TraceFormat, Tracer};
+use crate::base::vm::{VMError, VM};
+use lamp_common::op::Opcode;
+use serde_json::{json, Value};
+
+// LOAD r1, 3; INC r2; EQ r1, r1; MOD r1, r0, r3 (r1 / r0)
+fn program() -> VM {
+    VM::new(vec![15, 1, 0, 3, 5, 2, 7, 1, 1, 4, 1, 0, 3])
+}
+
+fn trace(format: TraceFormat, filter: TraceFilter) -> (Vec<String>, Result<i32, VMError>) {
+    let mut vm = program();
+    let mut out = Vec::<u8>::new();
+    let result = Tracer::new(&mut out, format, filter).run(&mut vm).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    (text.lines().map(str::to_owned).collect(), result)
+}
+
+#[test]
+pub fn trace_jsonl_test() {
+    let (lines, result) = trace(TraceFormat::Jsonl, TraceFilter::default());
+    assert_eq!(result, Err(VMError::DivisionByZero));
+    let records: Vec<Value> = lines
+        .iter()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(
+        records,
+        vec![
+            json!({ "seq": 1, "pc": 0, "instruction": "LOAD r1, #3", "registers": { "r1": 3 } }),
+            json!({ "seq": 2, "pc": 4, "instruction": "INC r2", "registers": { "r2": 1 } }),
+            json!({ "seq": 3, "pc": 6, "instruction": "EQ r1, r1", "eq_flag": true }),
+            json!({
+                "seq": 4,
+                "pc": 9,
+                "instruction": "MOD r1, r0, r3",
+                "fault": "Division by zero"
+            }),
+        ]
+    );
+}
+
+#[test]
+pub fn trace_text_and_filters_test() {
+    let filter = TraceFilter {
+        ranges: vec![4..6, 6..9],
+        opcodes: vec![],
+    };
+    let (lines, _) = trace(TraceFormat::Text, filter);
+    assert_eq!(
+        lines,
+        vec![
+            "       2 0x0004  INC r2                  r2=1",
+            "       3 0x0006  EQ r1, r1               eq=true",
+        ]
+    );
+    let filter = TraceFilter {
+        ranges: vec![],
+        opcodes: vec![Opcode::LOAD, Opcode::MOD],
+    };
+    let (lines, _) = trace(TraceFormat::Text, filter);
+    assert_eq!(lines.len(), 2);
+    assert!(lines[1].ends_with("fault: Division by zero"));
+}
+
+#[test]
+pub fn trace_parse_filters_test() {
+    let symbols = vec![label("loop", 0x10)];
+    assert_eq!(parse_range("4..0x20", &symbols), Ok(4..0x20));
+    assert_eq!(parse_range("loop..0x20", &symbols), Ok(0x10..0x20));
+    assert!(parse_range("loop", &symbols).is_err());
+    assert!(parse_range("0..done", &symbols).is_err());
+    assert_eq!(parse_opcode("mod"), Ok(Opcode::MOD));
+    assert!(parse_opcode("FOO").is_err());
+    assert_eq!("text".parse::<TraceFormat>(), Ok(TraceFormat::Text));
+}
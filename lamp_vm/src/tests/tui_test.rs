@@ -0,0 +1,101 @@
+use super::label;
+use crate::base::vm::VM;
+use crate::debug::session::DebugSession;
+use crate::debug::tui::Tui;
+use ratatui::backend::TestBackend;
+use ratatui::buffer::Buffer;
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::style::Color;
+use ratatui::Terminal;
+
+fn session() -> DebugSession {
+    // LOAD r1, 3; INC r1; INC r1
+    let code = vec![15, 1, 0, 3, 5, 1, 5, 1];
+    let symbols = vec![label("_start", 0)];
+    let mut vm = VM::with_memory(code, b"lamp, a tiny VM\0".to_vec(), 0);
+    vm.set_register_value(31, 8);
+    DebugSession::new(vm, symbols, None)
+}
+
+fn render(tui: &Tui) -> Buffer {
+    let mut terminal = Terminal::new(TestBackend::new(140, 40)).unwrap();
+    terminal.draw(|frame| tui.draw(frame)).unwrap();
+    terminal.backend().buffer().clone()
+}
+
+fn lines(buffer: &Buffer) -> Vec<String> {
+    let area = buffer.area;
+    (0..area.height)
+        .map(|y| (0..area.width).map(|x| buffer[(x, y)].symbol()).collect())
+        .collect()
+}
+
+fn find(buffer: &Buffer, text: &str) -> Option<(u16, u16)> {
+    lines(buffer).iter().enumerate().find_map(|(y, line)| {
+        line.find(text)
+            .map(|at| (line[..at].chars().count() as u16, y as u16))
+    })
+}
+
+#[test]
+pub fn tui_panes_test() {
+    let mut session = session();
+    let mut tui = Tui::new(&mut session);
+    tui.submit("break 6");
+    tui.submit("step");
+    let buffer = render(&tui);
+    let screen = lines(&buffer).join("\n");
+    for text in &[
+        "Registers",
+        "Flags",
+        "Stack",
+        "Disassembly",
+        "Memory",
+        "_start:",
+        "=>  INC r1",
+        "● ",
+        "remainder 0",
+        "sp-> 0x0008",
+        "|lamp, a tiny VM.|",
+        ">>> step",
+    ] {
+        assert!(
+            screen.contains(text),
+            "{} is missing from\n{}",
+            text,
+            screen
+        );
+    }
+    // r1 changed with the step, r2 didn't
+    let (x, y) = find(&buffer, "r1 ").unwrap();
+    assert_eq!(buffer[(x + 14, y)].symbol(), "3");
+    assert_eq!(buffer[(x + 14, y)].fg, Color::Yellow);
+    let (x, y) = find(&buffer, "r2 ").unwrap();
+    assert_eq!(buffer[(x + 14, y)].fg, Color::Reset);
+}
+
+#[test]
+pub fn tui_command_line_test() {
+    let mut session = session();
+    let mut tui = Tui::new(&mut session);
+    let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+    for c in "st".chars() {
+        tui.handle_key(key(KeyCode::Char(c)));
+    }
+    tui.handle_key(key(KeyCode::Enter));
+    // An empty line steps again, the arrow brings the command back
+    tui.handle_key(key(KeyCode::Enter));
+    tui.handle_key(key(KeyCode::Up));
+    tui.handle_key(key(KeyCode::Backspace));
+    // What the commands write is shown below them
+    assert_eq!(
+        tui.output(),
+        &[">>> st", "0x0004: INC r1", ">>> st", "0x0006: INC r1"]
+    );
+    let buffer = render(&tui);
+    assert!(find(&buffer, ">>> s ").is_some());
+    tui.handle_key(KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL));
+    drop(tui);
+    assert_eq!(*session.vm.get_register(1), 4);
+    assert!(session.is_quit_requested());
+}
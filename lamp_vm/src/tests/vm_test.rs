@@ -1,4 +1,4 @@
-use crate::base::vm::VM;
+use crate::base::vm::{VMError, VM};
 
 #[test]
 pub fn vm_add_test() {
@@ -86,3 +86,36 @@ pub fn vm_load_test() {
     let expected_value = (15 << 8) | 15;
     assert_eq!(*vm.get_register(13), expected_value);
 }
+
+#[test]
+pub fn vm_division_by_zero_test() {
+    let bin = vec![
+        // LOAD 13, 0, 15: Put 15 in the register 13
+        15, 13, 0, 15, // MOD 13, 14, 15: the register 14 holds 0
+        4, 13, 14, 15, // INC 15: never executed, the VM stops at the error
+        5, 15,
+    ];
+
+    let mut vm = VM::new(bin);
+    assert_eq!(vm.run(), Err(VMError::DivisionByZero));
+    assert_eq!(vm.get_instruction_pc(), 4);
+    assert_eq!(*vm.get_register(15), 0);
+}
+
+#[test]
+pub fn vm_truncated_instruction_test() {
+    // LOAD 1, 0: the code ends in the middle of the value
+    let mut vm = VM::new(vec![15, 1, 0]);
+    assert_eq!(vm.run(), Err(VMError::EndOfCode));
+    assert_eq!(vm.get_instruction_pc(), 0);
+}
+
+#[test]
+pub fn vm_invalid_register_test() {
+    // INC 32: there are 32 registers only
+    let mut vm = VM::new(vec![5, 32]);
+    assert_eq!(vm.run(), Err(VMError::InvalidRegister(32)));
+    // ADD 1, 40, 2
+    let mut vm = VM::new(vec![1, 1, 40, 2]);
+    assert_eq!(vm.run(), Err(VMError::InvalidRegister(40)));
+}